@@ -1,6 +1,10 @@
 use crate::config::Config;
 use crate::hyprland::{EventListener, HyprEvent, HyprlandClient};
 use crate::modules;
+use crate::modules::media::MediaClient;
+use crate::modules::systray::TrayClient;
+use crate::modules::visualizer::Spectrum;
+use crate::system::audio::AudioEventListener;
 use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4_layer_shell::LayerShell;
@@ -17,12 +21,50 @@ pub struct Bar {
     connectivity: Option<modules::connectivity::Connectivity>,
     audio: Option<modules::audio::Audio>,
     brightness: Option<modules::brightness::Brightness>,
+    privacy: Option<modules::privacy::Privacy>,
     power: Option<modules::power::Power>,
     battery: Option<modules::battery::Battery>,
     clock: Option<modules::clock::Clock>,
     _power_menu: Option<modules::power_menu::PowerMenu>,
     _settings: Option<modules::settings::Settings>,
-    event_listener: Option<EventListener>,
+}
+
+/// Services run once for the whole app and handed to every bar's modules,
+/// rather than each bar running its own.
+pub struct Services {
+    // Also the OSD's, for track changes
+    pub media: Option<Rc<MediaClient>>,
+    pub spectrum: Option<Rc<Spectrum>>,
+    pub tray: Option<Rc<TrayClient>>,
+}
+
+impl Services {
+    pub fn new(config: &Config) -> Self {
+        let media_shown = shows_module(config, "media");
+        let osd_media = config.osd.enabled && config.osd.media;
+        Self {
+            media: (media_shown || osd_media).then(|| Rc::new(MediaClient::new())),
+            spectrum: (media_shown && config.visualizer.enabled)
+                .then(|| Rc::new(Spectrum::new(&config.visualizer))),
+            tray: shows_module(config, "systray").then(|| Rc::new(TrayClient::new())),
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(ref media) = self.media {
+            media.stop();
+        }
+        if let Some(ref spectrum) = self.spectrum {
+            spectrum.stop();
+        }
+    }
+}
+
+/// Whether `name` is on either side of the bar.
+pub fn shows_module(config: &Config, name: &str) -> bool {
+    [&config.modules.left, &config.modules.right]
+        .iter()
+        .any(|side| side.iter().any(|m| m == name))
 }
 
 impl Bar {
@@ -31,6 +73,7 @@ impl Bar {
         client: Option<Rc<HyprlandClient>>,
         config: &Config,
         shared_config: Rc<RefCell<Config>>,
+        services: &Services,
         monitor: Option<&gtk4::gdk::Monitor>,
         all_windows: Rc<RefCell<Vec<gtk4::Window>>>,
     ) -> Self {
@@ -85,6 +128,7 @@ impl Bar {
         let mut connectivity = None;
         let mut audio = None;
         let mut brightness = None;
+        let mut privacy = None;
         let mut power = None;
         let mut battery = None;
         let mut clock = None;
@@ -93,6 +137,7 @@ impl Bar {
 
         // Build left modules
         for module_name in &config.modules.left {
+            let before = container.last_child();
             match module_name.as_str() {
                 "workspaces" => {
                     if let Some(ref c) = client {
@@ -109,24 +154,38 @@ impl Bar {
                     if let Some(ref c) = client {
                         let at = Rc::new(modules::app_tracker::AppTracker::new(
                             c.clone(),
-                            config.intervals.app_tracker,
+                            &config.app_tracker,
+                            &config.launch,
                         ));
                         container.append(&at.widget);
                         app_tracker = Some(at);
                     }
                 }
                 "media" => {
-                    let m = modules::media::Media::new(config.intervals.media);
-                    container.append(&m.widget);
-                    media = Some(m);
+                    if let Some(ref client) = services.media {
+                        let m = modules::media::Media::new(
+                            config.intervals.media,
+                            &config.media,
+                            &config.visualizer,
+                            client,
+                            services.spectrum.clone(),
+                        );
+                        container.append(&m.widget);
+                        media = Some(m);
+                    }
                 }
                 "app_finder" => {
-                    let af = modules::app_finder::AppFinder::new();
+                    let af = modules::app_finder::AppFinder::new(
+                        &config.app_finder,
+                        &config.launch,
+                        client.clone(),
+                    );
                     container.append(&af.widget);
                     app_finder = Some(af);
                 }
                 _ => {}
             }
+            attach_command_actions(&container, before, module_name, config);
         }
 
         // Spacer
@@ -140,11 +199,14 @@ impl Bar {
 
         // Build right modules
         for module_name in &config.modules.right {
+            let before = container.last_child();
             match module_name.as_str() {
                 "systray" => {
-                    let st = modules::systray::Systray::new();
-                    container.append(&st.widget);
-                    systray = Some(st);
+                    if let Some(ref tray) = services.tray {
+                        let st = modules::systray::Systray::new(&config.systray, tray);
+                        container.append(&st.widget);
+                        systray = Some(st);
+                    }
                 }
                 "connectivity" => {
                     let conn =
@@ -153,15 +215,28 @@ impl Bar {
                     connectivity = Some(conn);
                 }
                 "audio" => {
-                    let a = modules::audio::Audio::new();
+                    let a = modules::audio::Audio::new(&config.audio, config.actions.get("audio"));
                     container.append(&a.widget);
                     audio = Some(a);
                 }
                 "brightness" => {
-                    let b = modules::brightness::Brightness::new(config.intervals.brightness);
+                    let b = modules::brightness::Brightness::new(
+                        config.intervals.brightness,
+                        &config.brightness,
+                        config.actions.get("brightness"),
+                    );
                     container.append(&b.widget);
                     brightness = Some(b);
                 }
+                "privacy" => {
+                    let p = modules::privacy::Privacy::new(
+                        config.intervals.privacy,
+                        &config.privacy,
+                        config.actions.get("privacy"),
+                    );
+                    container.append(&p.widget);
+                    privacy = Some(p);
+                }
                 "power" => {
                     let p = modules::power::Power::new(config.intervals.power);
                     container.append(&p.widget);
@@ -192,12 +267,17 @@ impl Bar {
                     power_menu = Some(pm);
                 }
                 "app_finder" => {
-                    let af = modules::app_finder::AppFinder::new();
+                    let af = modules::app_finder::AppFinder::new(
+                        &config.app_finder,
+                        &config.launch,
+                        client.clone(),
+                    );
                     container.append(&af.widget);
                     app_finder = Some(af);
                 }
                 _ => {}
             }
+            attach_command_actions(&container, before, module_name, config);
         }
 
         Self {
@@ -210,70 +290,13 @@ impl Bar {
             connectivity,
             audio,
             brightness,
+            privacy,
             power,
             battery,
             clock,
             _power_menu: power_menu,
             _settings: settings,
-            event_listener: None,
-        }
-    }
-
-    pub fn setup_events(&mut self) {
-        // Audio events (independent of Hyprland)
-        if let Some(ref mut audio) = self.audio {
-            audio.setup_events();
-        }
-
-        if self.workspaces.is_none() {
-            return;
-        }
-
-        let listener = match EventListener::new() {
-            Ok(l) => l,
-            Err(e) => {
-                eprintln!("Warning: Could not set up event listener: {e}");
-                return;
-            }
-        };
-
-        let (sender, receiver) = async_channel::unbounded::<HyprEvent>();
-
-        if let Err(e) = listener.start(sender) {
-            eprintln!("Warning: Could not start event listener: {e}");
-            return;
         }
-
-        // Clone Rc handles for safe sharing with the async event handler
-        let workspaces = self.workspaces.clone();
-        let app_tracker = self.app_tracker.clone();
-
-        glib::spawn_future_local(async move {
-            while let Ok(event) = receiver.recv().await {
-                match event {
-                    HyprEvent::Workspace(_)
-                    | HyprEvent::CreateWorkspace(_)
-                    | HyprEvent::DestroyWorkspace(_) => {
-                        if let Some(ref ws) = workspaces {
-                            ws.refresh();
-                        }
-                    }
-                    HyprEvent::ActiveWindow(_) | HyprEvent::WindowTitle(_) => {}
-                    HyprEvent::CloseWindow(_) => {
-                        if let Some(ref at) = app_tracker {
-                            at.refresh();
-                        }
-                    }
-                    HyprEvent::OpenWindow(_) => {
-                        if let Some(ref at) = app_tracker {
-                            at.refresh();
-                        }
-                    }
-                }
-            }
-        });
-
-        self.event_listener = Some(listener);
     }
 
     pub fn show(&self) {
@@ -281,9 +304,6 @@ impl Bar {
     }
 
     pub fn stop(&mut self) {
-        if let Some(ref at) = self.app_tracker {
-            at.stop();
-        }
         if let Some(ref mut m) = self.media {
             m.stop();
         }
@@ -293,12 +313,12 @@ impl Bar {
         if let Some(ref mut conn) = self.connectivity {
             conn.stop();
         }
-        if let Some(ref audio) = self.audio {
-            audio.stop();
-        }
         if let Some(ref mut b) = self.brightness {
             b.stop();
         }
+        if let Some(ref p) = self.privacy {
+            p.stop();
+        }
         if let Some(ref mut p) = self.power {
             p.stop();
         }
@@ -308,12 +328,104 @@ impl Bar {
         if let Some(ref mut c) = self.clock {
             c.stop();
         }
-        if let Some(ref el) = self.event_listener {
-            el.stop();
+    }
+}
+
+/// The Hyprland and audio event listeners, shared by every bar so each event
+/// is read once however many monitors there are.
+pub struct BarEvents {
+    hyprland: Option<EventListener>,
+    audio: Option<AudioEventListener>,
+}
+
+impl BarEvents {
+    pub fn stop(&self) {
+        if let Some(ref listener) = self.hyprland {
+            listener.stop();
+        }
+        if let Some(ref listener) = self.audio {
+            listener.stop();
         }
     }
 }
 
+/// Starts one listener per event source and hands its events to every bar.
+pub fn setup_events(bars: &[Bar]) -> BarEvents {
+    BarEvents {
+        hyprland: setup_hyprland_events(bars),
+        audio: setup_audio_events(bars),
+    }
+}
+
+fn setup_hyprland_events(bars: &[Bar]) -> Option<EventListener> {
+    // Clone Rc handles for safe sharing with the async event handler
+    let workspaces: Vec<_> = bars.iter().filter_map(|b| b.workspaces.clone()).collect();
+    let app_trackers: Vec<_> = bars.iter().filter_map(|b| b.app_tracker.clone()).collect();
+    if workspaces.is_empty() && app_trackers.is_empty() {
+        return None;
+    }
+
+    let listener = match EventListener::new() {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Warning: Could not set up event listener: {e}");
+            return None;
+        }
+    };
+
+    let (sender, receiver) = async_channel::unbounded::<HyprEvent>();
+
+    if let Err(e) = listener.start(sender) {
+        eprintln!("Warning: Could not start event listener: {e}");
+        return None;
+    }
+
+    glib::spawn_future_local(async move {
+        while let Ok(event) = receiver.recv().await {
+            for ws in &workspaces {
+                ws.handle_event(&event);
+            }
+            for at in &app_trackers {
+                at.handle_event(&event);
+            }
+            // Dispatches back to the compositor go out once, not once per bar
+            if let (HyprEvent::Minimized(data), Some(at)) = (&event, app_trackers.first()) {
+                at.handle_minimize_request(data);
+            }
+        }
+    });
+
+    Some(listener)
+}
+
+fn setup_audio_events(bars: &[Bar]) -> Option<AudioEventListener> {
+    let senders: Vec<_> = bars
+        .iter()
+        .filter_map(|b| b.audio.as_ref())
+        .map(|audio| {
+            let (sender, receiver) = async_channel::unbounded::<()>();
+            audio.setup_events(receiver);
+            sender
+        })
+        .collect();
+    if senders.is_empty() {
+        return None;
+    }
+
+    let (sender, receiver) = async_channel::unbounded::<()>();
+    glib::spawn_future_local(async move {
+        while receiver.recv().await.is_ok() {
+            for sender in &senders {
+                let _ = sender.send(()).await;
+            }
+        }
+    });
+
+    let listener = AudioEventListener::new();
+    listener.start(sender);
+    Some(listener)
+}
+
 pub fn apply_position_anchors(window: &gtk4::Window, position: &str, thickness: i32) {
     let is_vertical = position == "left" || position == "right";
 
@@ -423,6 +535,7 @@ pub fn create_bars(
     client: Option<Rc<HyprlandClient>>,
     config: &Config,
     shared_config: Rc<RefCell<Config>>,
+    services: &Services,
 ) -> Vec<Bar> {
     let mut bars = Vec::new();
     let all_windows: Rc<RefCell<Vec<gtk4::Window>>> = Rc::new(RefCell::new(Vec::new()));
@@ -435,7 +548,7 @@ pub fn create_bars(
 
         if n == 0 {
             // Fallback: create single bar without specific monitor
-            let bar = Bar::new(app, client.clone(), config, shared_config.clone(), None, all_windows.clone());
+            let bar = Bar::new(app, client.clone(), config, shared_config.clone(), services, None, all_windows.clone());
             bars.push(bar);
         } else {
             for i in 0..n {
@@ -443,7 +556,7 @@ pub fn create_bars(
                     .item(i)
                     .and_then(|obj| obj.downcast::<gtk4::gdk::Monitor>().ok());
 
-                let bar = Bar::new(app, client.clone(), config, shared_config.clone(), monitor.as_ref(), all_windows.clone());
+                let bar = Bar::new(app, client.clone(), config, shared_config.clone(), services, monitor.as_ref(), all_windows.clone());
                 bars.push(bar);
             }
         }
@@ -466,7 +579,7 @@ pub fn create_bars(
             }
         }
 
-        let bar = Bar::new(app, client.clone(), config, shared_config.clone(), target_monitor.as_ref(), all_windows.clone());
+        let bar = Bar::new(app, client.clone(), config, shared_config.clone(), services, target_monitor.as_ref(), all_windows.clone());
         bars.push(bar);
     }
 
@@ -478,11 +591,6 @@ pub fn create_bars(
         }
     }
 
-    // Setup events after bars are constructed.
-    if !bars.is_empty() {
-        bars[0].setup_events();
-    }
-
     // Show all bars
     for bar in &bars {
         bar.show();
@@ -490,3 +598,24 @@ pub fn create_bars(
 
     bars
 }
+
+/// Hooks up `[actions.<module>]` for modules without built-in actions of
+/// their own, so they can still run commands on click or scroll.
+fn attach_command_actions(
+    container: &gtk4::Box,
+    before: Option<gtk4::Widget>,
+    name: &str,
+    config: &Config,
+) {
+    if matches!(name, "audio" | "brightness" | "privacy" | "separator") {
+        return;
+    }
+    let Some(module_actions) = config.actions.get(name) else {
+        return;
+    };
+    // Nothing was added when the module couldn't start, e.g. without Hyprland
+    let Some(widget) = container.last_child().filter(|w| Some(w) != before.as_ref()) else {
+        return;
+    };
+    modules::actions::attach(&widget, module_actions, |_, _| false);
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +10,111 @@ pub struct ClockConfig {
     pub show_ntp_status: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppTrackerConfig {
+    pub previews: bool,
+    pub hover_preview: bool,
+    pub preview_backend: String,
+    pub preview_width: i32,
+    pub kill_grace_secs: u32,
+    pub confirm_force_quit: bool,
+    pub icon_overrides: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppFinderConfig {
+    pub focus_running: bool,
+    pub providers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystrayConfig {
+    pub icon_size: i32,
+    pub hidden: Vec<String>,
+    pub order: Vec<String>,
+    pub overflow_passive: bool,
+    pub max_visible: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub scroll_step: i32,
+    pub max_volume: i32,
+    pub limits: HashMap<String, i32>,
+    pub sink_priority: Vec<String>,
+    pub source_priority: Vec<String>,
+    pub remember_volume: bool,
+    pub remember_streams: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrightnessConfig {
+    pub scroll_step: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaConfig {
+    pub priority: Vec<String>,
+    pub ignore: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualizerConfig {
+    pub enabled: bool,
+    pub bars: usize,
+    pub framerate: u32,
+    pub smoothing: f64,
+    pub bar: bool,
+    pub popover: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyConfig {
+    pub microphone: bool,
+    pub camera: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OsdConfig {
+    pub enabled: bool,
+    pub position: String,
+    pub margin: i32,
+    pub timeout_ms: u32,
+    pub volume: bool,
+    pub microphone: bool,
+    pub brightness: bool,
+    pub media: bool,
+}
+
+/// What clicking or scrolling on a bar module does: one of the module's
+/// built-in actions, "popover", "none", or a shell command. Empty keeps the
+/// module's default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModuleActions {
+    pub on_click: String,
+    pub on_middle_click: String,
+    pub on_right_click: String,
+    pub on_scroll_up: String,
+    pub on_scroll_down: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchConfig {
+    pub strategy: String,
+    pub terminal: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
@@ -17,6 +123,17 @@ pub struct Config {
     pub intervals: IntervalsConfig,
     pub theme: ThemeConfig,
     pub clock: ClockConfig,
+    pub app_tracker: AppTrackerConfig,
+    pub app_finder: AppFinderConfig,
+    pub systray: SystrayConfig,
+    pub audio: AudioConfig,
+    pub brightness: BrightnessConfig,
+    pub osd: OsdConfig,
+    pub privacy: PrivacyConfig,
+    pub media: MediaConfig,
+    pub visualizer: VisualizerConfig,
+    pub actions: HashMap<String, ModuleActions>,
+    pub launch: LaunchConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,7 +160,7 @@ pub struct IntervalsConfig {
     pub brightness: u32,
     pub connectivity: u32,
     pub media: u32,
-    pub app_tracker: u32,
+    pub privacy: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,6 +213,121 @@ impl Default for Config {
             intervals: IntervalsConfig::default(),
             theme: ThemeConfig::default(),
             clock: ClockConfig::default(),
+            app_tracker: AppTrackerConfig::default(),
+            app_finder: AppFinderConfig::default(),
+            systray: SystrayConfig::default(),
+            audio: AudioConfig::default(),
+            brightness: BrightnessConfig::default(),
+            osd: OsdConfig::default(),
+            privacy: PrivacyConfig::default(),
+            media: MediaConfig::default(),
+            visualizer: VisualizerConfig::default(),
+            actions: HashMap::new(),
+            launch: LaunchConfig::default(),
+        }
+    }
+}
+
+impl Default for AppTrackerConfig {
+    fn default() -> Self {
+        Self {
+            previews: true,
+            hover_preview: true,
+            preview_backend: "grim".to_string(),
+            preview_width: 240,
+            kill_grace_secs: 5,
+            confirm_force_quit: true,
+            icon_overrides: HashMap::new(),
+        }
+    }
+}
+
+impl Default for AppFinderConfig {
+    fn default() -> Self {
+        Self {
+            focus_running: true,
+            providers: ["apps", "windows", "calculator", "commands", "files", "units"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Default for SystrayConfig {
+    fn default() -> Self {
+        Self {
+            icon_size: 16,
+            hidden: Vec::new(),
+            order: Vec::new(),
+            overflow_passive: true,
+            max_visible: 0,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            scroll_step: 5,
+            max_volume: 100,
+            limits: HashMap::new(),
+            sink_priority: Vec::new(),
+            source_priority: Vec::new(),
+            remember_volume: true,
+            remember_streams: true,
+        }
+    }
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self { scroll_step: 5 }
+    }
+}
+
+impl Default for VisualizerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bars: 16,
+            framerate: 30,
+            smoothing: 0.7,
+            bar: true,
+            popover: true,
+        }
+    }
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            microphone: true,
+            camera: true,
+        }
+    }
+}
+
+impl Default for OsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            position: "bottom".to_string(),
+            margin: 120,
+            timeout_ms: 1500,
+            volume: true,
+            microphone: true,
+            brightness: true,
+            media: true,
+        }
+    }
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        Self {
+            strategy: "auto".to_string(),
+            terminal: String::new(),
         }
     }
 }
@@ -145,7 +377,7 @@ impl Default for IntervalsConfig {
             brightness: 5,
             connectivity: 5,
             media: 2,
-            app_tracker: 2,
+            privacy: 3,
         }
     }
 }
@@ -330,3 +562,15 @@ impl Config {
         None
     }
 }
+
+/// State crowbar writes for itself, like launch history and learned audio
+/// preferences, as opposed to the config the user edits.
+pub fn state_dir() -> PathBuf {
+    match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("crowbar"),
+        _ => {
+            let home = std::env::var("HOME").unwrap_or_default();
+            PathBuf::from(format!("{home}/.local/state/crowbar"))
+        }
+    }
+}
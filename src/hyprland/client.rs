@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
@@ -23,6 +24,10 @@ pub struct Window {
     pub pid: i32,
     #[serde(default)]
     pub workspace: WorkspaceRef,
+    #[serde(default)]
+    pub at: [i32; 2],
+    #[serde(default)]
+    pub size: [i32; 2],
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -32,6 +37,18 @@ pub struct WorkspaceRef {
     pub name: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Monitor {
+    pub id: i32,
+    pub name: String,
+    #[serde(default, rename = "activeWorkspace")]
+    pub active_workspace: WorkspaceRef,
+    #[serde(default)]
+    pub focused: bool,
+}
+
+#[derive(Clone)]
 pub struct HyprlandClient {
     socket_path: PathBuf,
 }
@@ -91,6 +108,10 @@ impl HyprlandClient {
         self.json_command("clients")
     }
 
+    pub fn monitors(&self) -> Result<Vec<Monitor>, String> {
+        self.json_command("monitors")
+    }
+
     pub fn switch_workspace(&self, id: i32) -> Result<(), String> {
         self.send_command(&format!("dispatch workspace {id}"))?;
         Ok(())
@@ -123,15 +144,12 @@ impl HyprlandClient {
         Ok(())
     }
 
-    pub fn kill_window(pid: i32) -> Result<(), String> {
-        let status = std::process::Command::new("kill")
-            .args(["-9", &pid.to_string()])
-            .status()
-            .map_err(|e| format!("Failed to kill process {pid}: {e}"))?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(format!("kill -9 {pid} failed"))
-        }
+    /// Current pid of every window, by address.
+    pub fn window_pids(&self) -> Result<HashMap<String, i32>, String> {
+        Ok(self
+            .clients()?
+            .into_iter()
+            .map(|c| (c.address, c.pid))
+            .collect())
     }
 }
@@ -3,17 +3,44 @@ use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+// Payloads are the raw event data after `>>`; window addresses come without
+// the `0x` prefix that `j/clients` uses.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub enum HyprEvent {
     Workspace(String),
+    // "CLASS,TITLE"
     ActiveWindow(String),
+    // "ADDRESS" (empty when nothing is focused)
+    ActiveWindowV2(String),
+    // "ADDRESS,WORKSPACENAME,CLASS,TITLE"
     OpenWindow(String),
+    // "ADDRESS"
     CloseWindow(String),
+    // "ADDRESS,WORKSPACEID,WORKSPACENAME"
+    MoveWindow(String),
+    // "ADDRESS,TITLE"
     WindowTitle(String),
+    // "ADDRESS,0|1" - a client asked to be (un)minimized
+    Minimized(String),
+    // "ADDRESS" - a window requested attention
+    Urgent(String),
     CreateWorkspace(String),
     DestroyWorkspace(String),
+    // The event socket was lost and re-established; state may be stale
+    Reconnected,
+}
+
+/// Converts an event address to the `0x`-prefixed form used by `j/clients`.
+pub fn normalize_address(address: &str) -> String {
+    let address = address.trim();
+    if address.is_empty() || address.starts_with("0x") {
+        address.to_string()
+    } else {
+        format!("0x{address}")
+    }
 }
 
 pub struct EventListener {
@@ -48,45 +75,28 @@ impl EventListener {
         self.running.store(true, Ordering::SeqCst);
         let running = self.running.clone();
 
+        let socket_path = self.socket_path.clone();
+
         std::thread::spawn(move || {
-            let reader = std::io::BufReader::new(stream);
-            for line in reader.lines() {
-                if !running.load(Ordering::SeqCst) {
-                    break;
+            let mut stream = stream;
+            loop {
+                if !read_events(stream, &running, &sender) {
+                    return;
                 }
 
-                let Ok(line) = line else {
-                    break;
-                };
-
-                let line = line.trim().to_string();
-                let Some((event_type, data)) = line.split_once(">>") else {
-                    continue;
-                };
-
-                let event = match event_type {
-                    "workspace" | "workspacev2" => {
-                        HyprEvent::Workspace(data.to_string())
-                    }
-                    "activewindow" | "activewindowv2" => {
-                        HyprEvent::ActiveWindow(data.to_string())
-                    }
-                    "openwindow" => HyprEvent::OpenWindow(data.to_string()),
-                    "closewindow" => HyprEvent::CloseWindow(data.to_string()),
-                    "windowtitle" | "windowtitlev2" => {
-                        HyprEvent::WindowTitle(data.to_string())
-                    }
-                    "createworkspace" | "createworkspacev2" => {
-                        HyprEvent::CreateWorkspace(data.to_string())
+                // Socket closed (e.g. Hyprland reloaded) — reconnect until stopped
+                stream = loop {
+                    if !running.load(Ordering::SeqCst) {
+                        return;
                     }
-                    "destroyworkspace" | "destroyworkspacev2" => {
-                        HyprEvent::DestroyWorkspace(data.to_string())
+                    std::thread::sleep(Duration::from_secs(1));
+                    if let Ok(s) = UnixStream::connect(&socket_path) {
+                        break s;
                     }
-                    _ => continue,
                 };
 
-                if sender.send_blocking(event).is_err() {
-                    break;
+                if sender.send_blocking(HyprEvent::Reconnected).is_err() {
+                    return;
                 }
             }
         });
@@ -98,3 +108,52 @@ impl EventListener {
         self.running.store(false, Ordering::SeqCst);
     }
 }
+
+/// Forwards events until the socket closes. Returns false when the listener
+/// should shut down instead of reconnecting.
+fn read_events(
+    stream: UnixStream,
+    running: &AtomicBool,
+    sender: &async_channel::Sender<HyprEvent>,
+) -> bool {
+    let reader = std::io::BufReader::new(stream);
+    for line in reader.lines() {
+        if !running.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let Ok(line) = line else {
+            break;
+        };
+
+        let line = line.trim().to_string();
+        let Some((event_type, data)) = line.split_once(">>") else {
+            continue;
+        };
+
+        let event = match event_type {
+            "workspace" | "workspacev2" => HyprEvent::Workspace(data.to_string()),
+            "activewindow" => HyprEvent::ActiveWindow(data.to_string()),
+            "activewindowv2" => HyprEvent::ActiveWindowV2(data.to_string()),
+            "openwindow" => HyprEvent::OpenWindow(data.to_string()),
+            "closewindow" => HyprEvent::CloseWindow(data.to_string()),
+            "movewindowv2" => HyprEvent::MoveWindow(data.to_string()),
+            "windowtitlev2" => HyprEvent::WindowTitle(data.to_string()),
+            "minimized" => HyprEvent::Minimized(data.to_string()),
+            "urgent" => HyprEvent::Urgent(data.to_string()),
+            "createworkspace" | "createworkspacev2" => {
+                HyprEvent::CreateWorkspace(data.to_string())
+            }
+            "destroyworkspace" | "destroyworkspacev2" => {
+                HyprEvent::DestroyWorkspace(data.to_string())
+            }
+            _ => continue,
+        };
+
+        if sender.send_blocking(event).is_err() {
+            return false;
+        }
+    }
+
+    running.load(Ordering::SeqCst)
+}
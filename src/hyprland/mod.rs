@@ -2,4 +2,4 @@ pub mod client;
 pub mod events;
 
 pub use client::HyprlandClient;
-pub use events::{EventListener, HyprEvent};
+pub use events::{normalize_address, EventListener, HyprEvent};
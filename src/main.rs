@@ -10,11 +10,53 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 fn main() {
+    // `crowbar msg <command>` talks to the running bar instead of starting one
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("msg") {
+        match system::ipc::send(&args[1..].join(" ")) {
+            Ok(reply) => println!("{reply}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let app = gtk4::Application::new(Some("com.github.javanhut.crowbar"), Default::default());
 
     let bars: Rc<RefCell<Vec<bar::Bar>>> = Rc::new(RefCell::new(Vec::new()));
+    let bar_events: Rc<RefCell<Option<bar::BarEvents>>> = Rc::new(RefCell::new(None));
+    let services: Rc<RefCell<Option<bar::Services>>> = Rc::new(RefCell::new(None));
+    let osd: Rc<RefCell<Option<modules::osd::Osd>>> = Rc::new(RefCell::new(None));
+    let audio_profiles: Rc<RefCell<Option<modules::audio::AudioProfiles>>> =
+        Rc::new(RefCell::new(None));
+
+    // Only the primary instance gets here; later launches just activate it
+    app.connect_startup(|app| {
+        let (sender, receiver) = async_channel::unbounded::<system::ipc::Request>();
+        if let Err(e) = system::ipc::listen(sender) {
+            eprintln!("Warning: IPC socket disabled: {e}");
+            return;
+        }
+        app.connect_shutdown(|_| system::ipc::remove_socket());
+        gtk4::glib::spawn_future_local(async move {
+            while let Ok(request) = receiver.recv().await {
+                let result = if system::privacy::run_command(&request.command) {
+                    Ok(String::new())
+                } else {
+                    Err(format!("Unknown command: {}", request.command))
+                };
+                request.respond(result);
+            }
+        });
+    });
 
     let bars_activate = bars.clone();
+    let bar_events_activate = bar_events.clone();
+    let services_activate = services.clone();
+    let osd_activate = osd.clone();
+    let audio_profiles_activate = audio_profiles.clone();
     app.connect_activate(move |app| {
         // Load config
         let config = config::Config::load();
@@ -54,8 +96,30 @@ fn main() {
         let shared_config = Rc::new(RefCell::new(config.clone()));
 
         // Create bars (multi-monitor support)
-        let new_bars = bar::create_bars(app, client, &config, shared_config);
+        // Run once and shared by the bars on every monitor
+        let new_services = bar::Services::new(&config);
+        let new_bars = bar::create_bars(app, client, &config, shared_config, &new_services);
+        *bar_events_activate.borrow_mut() = Some(bar::setup_events(&new_bars));
         *bars_activate.borrow_mut() = new_bars;
+
+        // One OSD for all monitors; the compositor puts it on the focused one
+        if config.osd.enabled {
+            let o = modules::osd::Osd::new(
+                app,
+                &config.osd,
+                &config.media,
+                new_services.media.as_deref(),
+            );
+            *osd_activate.borrow_mut() = Some(o);
+        }
+        *services_activate.borrow_mut() = Some(new_services);
+
+        // Device rules act on the audio server itself, so one set for all
+        // bars; safety limits hold even without an audio module on screen
+        if bar::shows_module(&config, "audio") || !config.audio.limits.is_empty() {
+            let profiles = modules::audio::AudioProfiles::new(&config.audio);
+            *audio_profiles_activate.borrow_mut() = Some(profiles);
+        }
     });
 
     let bars_shutdown = bars.clone();
@@ -63,6 +127,18 @@ fn main() {
         for bar in bars_shutdown.borrow_mut().iter_mut() {
             bar.stop();
         }
+        if let Some(events) = bar_events.borrow().as_ref() {
+            events.stop();
+        }
+        if let Some(services) = services.borrow().as_ref() {
+            services.stop();
+        }
+        if let Some(osd) = osd.borrow().as_ref() {
+            osd.stop();
+        }
+        if let Some(profiles) = audio_profiles.borrow().as_ref() {
+            profiles.stop();
+        }
     });
 
     app.run_with_args::<String>(&[]);
@@ -0,0 +1,157 @@
+use crate::config::ModuleActions;
+use crate::system::launcher;
+use gtk4::prelude::*;
+use gtk4::{gdk, glib};
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Touchpads scroll in pixels; this far counts as one wheel notch
+const PIXELS_PER_STEP: f64 = 40.0;
+
+// Runs an action name `steps` times over
+type Runner = Rc<dyn Fn(&str, u32)>;
+
+/// `actions` with its empty fields taken from `defaults`.
+pub fn with_defaults(actions: Option<&ModuleActions>, defaults: ModuleActions) -> ModuleActions {
+    let Some(actions) = actions else {
+        return defaults;
+    };
+    let pick = |value: &String, default: String| {
+        if value.is_empty() {
+            default
+        } else {
+            value.clone()
+        }
+    };
+    ModuleActions {
+        on_click: pick(&actions.on_click, defaults.on_click),
+        on_middle_click: pick(&actions.on_middle_click, defaults.on_middle_click),
+        on_right_click: pick(&actions.on_right_click, defaults.on_right_click),
+        on_scroll_up: pick(&actions.on_scroll_up, defaults.on_scroll_up),
+        on_scroll_down: pick(&actions.on_scroll_down, defaults.on_scroll_down),
+    }
+}
+
+/// Runs the configured click and scroll actions on `widget`. `builtin`
+/// performs the module's own named actions `steps` times over and returns
+/// false for names it doesn't know, which then run as shell commands.
+/// Buttons without an action are left to the widget, e.g. a left click
+/// still opens the popover.
+pub fn attach(
+    widget: &impl IsA<gtk4::Widget>,
+    actions: &ModuleActions,
+    builtin: impl Fn(&str, u32) -> bool + 'static,
+) {
+    let widget = widget.as_ref().clone();
+    let run: Runner = {
+        let widget = widget.clone();
+        Rc::new(move |action, steps| match action {
+            "none" => {}
+            "popover" => toggle_popover(&widget),
+            _ if builtin(action, steps) => {}
+            command => {
+                for _ in 0..steps {
+                    if let Err(e) = launcher::run_shell(command) {
+                        eprintln!("Warning: Could not run \"{command}\": {e}");
+                    }
+                }
+            }
+        })
+    };
+
+    let clicks = [
+        actions.on_click.clone(),
+        actions.on_middle_click.clone(),
+        actions.on_right_click.clone(),
+    ];
+    if clicks.iter().any(|a| !a.is_empty()) {
+        let gesture = gtk4::GestureClick::new();
+        gesture.set_button(0);
+        // Ahead of the module's own buttons, so a configured left click
+        // replaces opening the popover
+        gesture.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let run = run.clone();
+        let widget_c = widget.clone();
+        gesture.connect_pressed(move |gesture, _, x, y| {
+            // Presses inside the module's open popover arrive here too
+            if !widget_c.contains(x, y) {
+                return;
+            }
+            let [click, middle, right] = &clicks;
+            let action = match gesture.current_button() {
+                gdk::BUTTON_PRIMARY => click,
+                gdk::BUTTON_MIDDLE => middle,
+                gdk::BUTTON_SECONDARY => right,
+                _ => return,
+            };
+            if action.is_empty() {
+                return;
+            }
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            run(action, 1);
+        });
+        widget.add_controller(gesture);
+    }
+
+    let (up, down) = (actions.on_scroll_up.clone(), actions.on_scroll_down.clone());
+    if up.is_empty() && down.is_empty() {
+        return;
+    }
+    let scroll = gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::VERTICAL);
+    // Partial steps from smooth scrolling, carried over between events
+    let pending = Rc::new(Cell::new(0.0));
+    let pending_c = pending.clone();
+    scroll.connect_scroll_begin(move |_| pending_c.set(0.0));
+    let widget_c = widget.clone();
+    scroll.connect_scroll(move |controller, _dx, dy| {
+        // Let sliders and lists in the open popover have it
+        if popover_open(&widget_c) {
+            return glib::Propagation::Proceed;
+        }
+        let delta = match controller.unit() {
+            gdk::ScrollUnit::Surface => dy / PIXELS_PER_STEP,
+            _ => dy,
+        };
+        let total = pending.get() + delta;
+        let steps = total.trunc();
+        pending.set(total - steps);
+
+        let action = if steps < 0.0 { &up } else { &down };
+        if steps != 0.0 && !action.is_empty() {
+            run(action, steps.abs() as u32);
+        }
+        glib::Propagation::Stop
+    });
+    widget.add_controller(scroll);
+}
+
+fn popover_open(widget: &gtk4::Widget) -> bool {
+    find_menu_button(widget)
+        .and_then(|button| button.popover())
+        .is_some_and(|popover| popover.is_visible())
+}
+
+fn toggle_popover(widget: &gtk4::Widget) {
+    let Some(button) = find_menu_button(widget) else {
+        return;
+    };
+    if popover_open(widget) {
+        button.popdown();
+    } else {
+        button.popup();
+    }
+}
+
+fn find_menu_button(widget: &gtk4::Widget) -> Option<gtk4::MenuButton> {
+    if let Some(button) = widget.downcast_ref::<gtk4::MenuButton>() {
+        return Some(button.clone());
+    }
+    let mut child = widget.first_child();
+    while let Some(c) = child {
+        if let Some(button) = find_menu_button(&c) {
+            return Some(button);
+        }
+        child = c.next_sibling();
+    }
+    None
+}
@@ -1,4 +1,10 @@
-use crate::system::app_finder;
+use crate::config::{AppFinderConfig, LaunchConfig};
+use crate::hyprland::client::Window;
+use crate::hyprland::HyprlandClient;
+use crate::system::app_finder::{self, LaunchOptions};
+use crate::system::desktop_index;
+use crate::system::finder_providers::{self, FinderAction, FinderResult, Provider, SearchContext};
+use crate::system::launch_history::LaunchHistory;
 use gtk4::gdk;
 use gtk4::glib;
 use gtk4::prelude::*;
@@ -7,15 +13,60 @@ use std::rc::Rc;
 
 pub struct AppFinder {
     pub widget: gtk4::Box,
-    _entries: Rc<RefCell<Vec<app_finder::DesktopEntry>>>,
+    _launcher: Rc<Launcher>,
+}
+
+/// What searching and launching from the finder needs: the configs, the
+/// launch history that ranks results, and Hyprland for windows, workspace
+/// placement and focusing running instances.
+struct Launcher {
+    config: AppFinderConfig,
+    launch: LaunchConfig,
+    providers: Vec<Provider>,
+    history: RefCell<LaunchHistory>,
+    client: Option<Rc<HyprlandClient>>,
+    // Open windows, read once per popover open when first searched
+    windows: RefCell<Option<Vec<Window>>>,
+}
+
+impl Launcher {
+    /// The open windows, empty without Hyprland.
+    fn windows(&self) -> Vec<Window> {
+        let Some(client) = &self.client else {
+            return Vec::new();
+        };
+        self.windows
+            .borrow_mut()
+            .get_or_insert_with(|| client.clients().unwrap_or_default())
+            .clone()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Placement {
+    // Focus a running instance if `focus_running` is set, else launch
+    Default,
+    NewInstance,
+    Workspace(i32),
 }
 
 impl AppFinder {
-    pub fn new() -> Self {
+    pub fn new(
+        config: &AppFinderConfig,
+        launch_config: &LaunchConfig,
+        client: Option<Rc<HyprlandClient>>,
+    ) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
         widget.add_css_class("app-finder");
 
-        let entries = Rc::new(RefCell::new(app_finder::load_desktop_entries()));
+        let launcher = Rc::new(Launcher {
+            config: config.clone(),
+            launch: launch_config.clone(),
+            providers: Provider::from_config(&config.providers),
+            history: RefCell::new(LaunchHistory::load()),
+            client,
+            windows: RefCell::new(None),
+        });
 
         let menu_button = gtk4::MenuButton::new();
         menu_button.add_css_class("app-finder-button");
@@ -68,32 +119,37 @@ impl AppFinder {
         // Selected index for keyboard nav: -1 = search focused, 0+ = row index
         let selected_index: Rc<Cell<i32>> = Rc::new(Cell::new(-1));
         // Current visible results for keyboard launch
-        let visible_results: Rc<RefCell<Vec<app_finder::DesktopEntry>>> =
-            Rc::new(RefCell::new(Vec::new()));
+        let visible_results: Rc<RefCell<Vec<FinderResult>>> = Rc::new(RefCell::new(Vec::new()));
 
         // Populate initial results
-        let entries_clone = entries.clone();
         let results_clone = results_box.clone();
         let popover_clone = popover.clone();
         let sel_init = selected_index.clone();
         let vis_init = visible_results.clone();
-        populate_results(&results_clone, &entries_clone.borrow(), "", &popover_clone, &sel_init, &vis_init);
+        populate_results(
+            &results_clone,
+            "",
+            &popover_clone,
+            &sel_init,
+            &vis_init,
+            &launcher,
+        );
 
         // Search as user types with debounce
         let debounce_id: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
-        let entries_search = entries.clone();
         let results_search = results_box.clone();
         let popover_search = popover.clone();
         let sel_search = selected_index.clone();
         let vis_search = visible_results.clone();
+        let launcher_search = launcher.clone();
         search_entry.connect_changed(move |entry| {
             let query = entry.text().to_string();
-            let entries_c = entries_search.clone();
             let results_c = results_search.clone();
             let popover_c = popover_search.clone();
             let debounce = debounce_id.clone();
             let sel_c = sel_search.clone();
             let vis_c = vis_search.clone();
+            let launcher_c = launcher_search.clone();
 
             // Cancel previous debounce if it hasn't fired yet
             if let Some(id) = debounce.borrow_mut().take() {
@@ -105,26 +161,65 @@ impl AppFinder {
                 std::time::Duration::from_millis(100),
                 move || {
                     debounce_for_timeout.borrow_mut().take();
-                    populate_results(&results_c, &entries_c.borrow(), &query, &popover_c, &sel_c, &vis_c);
+                    populate_results(
+                        &results_c,
+                        &query,
+                        &popover_c,
+                        &sel_c,
+                        &vis_c,
+                        &launcher_c,
+                    );
                 },
             );
             *debounce.borrow_mut() = Some(id);
         });
 
-        // Reload entries when popover opens
-        let entries_show = entries.clone();
+        // Start fresh each time the popover opens
         let results_show = results_box.clone();
         let search_show = search_entry.clone();
         let popover_show = popover.clone();
         let sel_show = selected_index.clone();
         let vis_show = visible_results.clone();
+        let launcher_show = launcher.clone();
         popover.connect_show(move |_| {
-            *entries_show.borrow_mut() = app_finder::load_desktop_entries();
+            launcher_show.windows.borrow_mut().take();
             search_show.set_text("");
-            populate_results(&results_show, &entries_show.borrow(), "", &popover_show, &sel_show, &vis_show);
+            populate_results(
+                &results_show,
+                "",
+                &popover_show,
+                &sel_show,
+                &vis_show,
+                &launcher_show,
+            );
             search_show.grab_focus();
         });
 
+        // The desktop index is built and kept fresh in the background;
+        // refresh open results when it changes
+        let index_updates = desktop_index::subscribe();
+        let results_index = results_box.clone();
+        let search_index = search_entry.clone();
+        let popover_index = popover.clone();
+        let sel_index = selected_index.clone();
+        let vis_index = visible_results.clone();
+        let launcher_index = launcher.clone();
+        glib::spawn_future_local(async move {
+            while index_updates.recv().await.is_ok() {
+                if !popover_index.is_visible() {
+                    continue;
+                }
+                populate_results(
+                    &results_index,
+                    &search_index.text(),
+                    &popover_index,
+                    &sel_index,
+                    &vis_index,
+                    &launcher_index,
+                );
+            }
+        });
+
         // Keyboard navigation controller
         let key_controller = gtk4::EventControllerKey::new();
         let sel_key = selected_index.clone();
@@ -132,7 +227,8 @@ impl AppFinder {
         let results_key = results_box.clone();
         let search_key = search_entry.clone();
         let popover_key = popover.clone();
-        key_controller.connect_key_pressed(move |_, keyval, _, _| {
+        let launcher_key = launcher.clone();
+        key_controller.connect_key_pressed(move |_, keyval, _, state| {
             match keyval {
                 gdk::Key::Down => {
                     let vis = vis_key.borrow();
@@ -161,12 +257,33 @@ impl AppFinder {
                 }
                 gdk::Key::Return | gdk::Key::KP_Enter => {
                     let idx = sel_key.get();
-                    if idx >= 0 {
-                        let vis = vis_key.borrow();
-                        if let Some(entry) = vis.get(idx as usize) {
-                            app_finder::launch_app(entry);
-                            popover_key.popdown();
+                    let vis = vis_key.borrow();
+                    if let Some(result) = usize::try_from(idx).ok().and_then(|i| vis.get(i)) {
+                        // Ctrl+Enter runs apps and commands in a terminal
+                        let terminal = state.contains(gdk::ModifierType::CONTROL_MASK);
+                        activate(&result.action, terminal, &launcher_key);
+                        popover_key.popdown();
+                    }
+                    glib::Propagation::Stop
+                }
+                gdk::Key::Tab => {
+                    let query = search_key.text().to_string();
+                    let selected = usize::try_from(sel_key.get()).ok();
+                    let completion =
+                        finder_providers::complete(&query, &vis_key.borrow(), selected);
+                    match completion {
+                        Some(text) => {
+                            search_key.set_text(&text);
+                            search_key.grab_focus();
+                            search_key.set_position(-1);
+                            glib::Propagation::Stop
                         }
+                        None => glib::Propagation::Proceed,
+                    }
+                }
+                gdk::Key::Right | gdk::Key::Left if sel_key.get() >= 0 => {
+                    if let Some(revealer) = nth_item_revealer(&results_key, sel_key.get()) {
+                        revealer.set_reveal_child(keyval == gdk::Key::Right);
                     }
                     glib::Propagation::Stop
                 }
@@ -180,17 +297,20 @@ impl AppFinder {
 
         widget.append(&menu_button);
 
-        Self { widget, _entries: entries }
+        Self {
+            widget,
+            _launcher: launcher,
+        }
     }
 }
 
 fn populate_results(
     results_box: &gtk4::Box,
-    entries: &[app_finder::DesktopEntry],
     query: &str,
     popover: &gtk4::Popover,
     selected_index: &Rc<Cell<i32>>,
-    visible_results: &Rc<RefCell<Vec<app_finder::DesktopEntry>>>,
+    visible_results: &Rc<RefCell<Vec<FinderResult>>>,
+    launcher: &Rc<Launcher>,
 ) {
     // Clear existing
     while let Some(child) = results_box.first_child() {
@@ -199,58 +319,193 @@ fn populate_results(
 
     selected_index.set(-1);
 
-    let results = app_finder::search_entries(entries, query);
+    let entries = desktop_index::entries();
+    let results = finder_providers::search(
+        query,
+        &SearchContext {
+            entries: &entries,
+            history: &launcher.history.borrow(),
+            windows: &|| launcher.windows(),
+            providers: &launcher.providers,
+        },
+    );
+
     if results.is_empty() {
         visible_results.borrow_mut().clear();
-        let empty = gtk4::Label::new(Some("No applications found"));
+        let empty = gtk4::Label::new(Some("Nothing found in the realms"));
         empty.add_css_class("connectivity-empty");
         results_box.append(&empty);
         return;
     }
 
-    // Store cloned entries for keyboard access
-    *visible_results.borrow_mut() = results
-        .iter()
-        .map(|e| app_finder::DesktopEntry {
-            name: e.name.clone(),
-            exec: e.exec.clone(),
-            icon: e.icon.clone(),
-            comment: e.comment.clone(),
-            categories: e.categories.clone(),
-            no_display: e.no_display,
-        })
-        .collect();
-
-    for entry in results {
-        let row = create_app_row(entry, popover);
-        results_box.append(&row);
+    for result in &results {
+        let item = create_result_row(result, popover, launcher);
+        results_box.append(&item);
     }
+
+    // Kept for keyboard access
+    *visible_results.borrow_mut() = results;
 }
 
-fn update_selection(results_box: &gtk4::Box, index: i32) {
-    let mut i = 0;
+fn result_items(results_box: &gtk4::Box) -> Vec<gtk4::Widget> {
+    let mut items = Vec::new();
     let mut child = results_box.first_child();
     while let Some(widget) = child {
-        if widget.css_classes().iter().any(|c| c == "app-finder-row") {
-            if i == index {
-                widget.add_css_class("app-finder-row-selected");
+        if widget.css_classes().iter().any(|c| c == "app-finder-item") {
+            items.push(widget.clone());
+        }
+        child = widget.next_sibling();
+    }
+    items
+}
+
+fn update_selection(results_box: &gtk4::Box, index: i32) {
+    for (i, item) in result_items(results_box).iter().enumerate() {
+        // The row is the item's first child, above its actions revealer
+        let Some(row) = item.first_child() else { continue };
+        if i as i32 == index {
+            row.add_css_class("app-finder-row-selected");
+        } else {
+            row.remove_css_class("app-finder-row-selected");
+        }
+    }
+}
+
+fn nth_item_revealer(results_box: &gtk4::Box, index: i32) -> Option<gtk4::Revealer> {
+    result_items(results_box)
+        .get(usize::try_from(index).ok()?)?
+        .last_child()?
+        .downcast::<gtk4::Revealer>()
+        .ok()
+}
+
+/// Runs a result's action. `terminal` (Ctrl+Enter) runs apps and shell
+/// commands in a terminal.
+fn activate(action: &FinderAction, terminal: bool, launcher: &Launcher) {
+    let result = match action {
+        FinderAction::Launch(entry) => {
+            let options = LaunchOptions {
+                terminal,
+                ..Default::default()
+            };
+            let placement = if terminal {
+                Placement::NewInstance
             } else {
-                widget.remove_css_class("app-finder-row-selected");
+                Placement::Default
+            };
+            launch(entry, &options, placement, launcher);
+            Ok(())
+        }
+        FinderAction::FocusWindow { address, minimized } => match &launcher.client {
+            Some(client) if *minimized => client.restore_window(address),
+            Some(client) => client.focus_window(address),
+            None => Ok(()),
+        },
+        FinderAction::Copy(text) => {
+            if let Some(display) = gdk::Display::default() {
+                display.clipboard().set_text(text);
             }
-            i += 1;
+            Ok(())
         }
-        child = widget.next_sibling();
+        FinderAction::RunCommand(command) => {
+            finder_providers::run_command(command, terminal, &launcher.launch)
+        }
+        FinderAction::Open(path) => finder_providers::open_path(path, &launcher.launch),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Warning: App finder action failed: {e}");
+    }
+}
+
+fn launch(
+    entry: &app_finder::DesktopEntry,
+    options: &LaunchOptions,
+    placement: Placement,
+    launcher: &Launcher,
+) {
+    if placement == Placement::Default
+        && launcher.config.focus_running
+        && focus_running(entry, launcher)
+    {
+        return;
+    }
+
+    let result = match (placement, &launcher.client) {
+        (Placement::Workspace(id), Some(client)) => {
+            // Workspace placement needs Hyprland's exec rules, whatever the
+            // configured launch strategy
+            app_finder::launch_command(entry, options, &launcher.launch)
+                .and_then(|c| client.dispatch(&format!("exec [workspace {id}] {}", c.shell_line())))
+        }
+        _ => app_finder::launch_app(entry, options, &launcher.launch),
+    };
+
+    match result {
+        Ok(()) => launcher.history.borrow_mut().record(&entry.id),
+        Err(e) => eprintln!("Warning: Failed to launch {}: {e}", entry.name),
+    }
+}
+
+/// Focuses (or restores) an open window of `entry`. Returns false if none is open.
+fn focus_running(entry: &app_finder::DesktopEntry, launcher: &Launcher) -> bool {
+    let Some(client) = &launcher.client else {
+        return false;
+    };
+    let Ok(clients) = client.clients() else {
+        return false;
+    };
+    let Some(window) = clients.iter().find(|c| entry.matches_class(&c.class)) else {
+        return false;
+    };
+
+    if window.workspace.name.starts_with("special:minimized") {
+        let _ = client.restore_window(&window.address);
+    } else {
+        let _ = client.focus_window(&window.address);
+    }
+    true
+}
+
+/// Pango markup for `text` with the chars at `positions` emphasized.
+fn highlight_markup(text: &str, positions: &[usize]) -> String {
+    let mut markup = String::new();
+    let mut in_match = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != in_match {
+            markup.push_str(if matched {
+                "<span weight=\"bold\" underline=\"single\">"
+            } else {
+                "</span>"
+            });
+            in_match = matched;
+        }
+        markup.push_str(&glib::markup_escape_text(&c.to_string()));
+    }
+    if in_match {
+        markup.push_str("</span>");
     }
+    markup
 }
 
-fn create_app_row(entry: &app_finder::DesktopEntry, popover: &gtk4::Popover) -> gtk4::Box {
+/// A result: the clickable row, plus for apps a revealer with the entry's
+/// desktop actions and launch options, built on first expand.
+fn create_result_row(
+    result: &FinderResult,
+    popover: &gtk4::Popover,
+    launcher: &Rc<Launcher>,
+) -> gtk4::Box {
+    let item = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    item.add_css_class("app-finder-item");
+
     let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
     row.add_css_class("app-finder-row");
     row.set_margin_top(2);
     row.set_margin_bottom(2);
 
     // Icon
-    if let Some(icon_name) = &entry.icon {
+    if let Some(icon_name) = &result.icon {
         let icon = gtk4::Image::from_icon_name(icon_name);
         icon.set_pixel_size(32);
         icon.add_css_class("app-finder-icon");
@@ -266,14 +521,15 @@ fn create_app_row(entry: &app_finder::DesktopEntry, popover: &gtk4::Popover) ->
     let info_box = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
     info_box.set_hexpand(true);
 
-    let name_label = gtk4::Label::new(Some(&entry.name));
+    let name_label = gtk4::Label::new(None);
+    name_label.set_markup(&highlight_markup(&result.title, &result.title_matches));
     name_label.add_css_class("app-finder-name");
     name_label.set_halign(gtk4::Align::Start);
     name_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
     info_box.append(&name_label);
 
-    if let Some(comment) = &entry.comment {
-        let comment_label = gtk4::Label::new(Some(comment));
+    if let Some(subtitle) = &result.subtitle {
+        let comment_label = gtk4::Label::new(Some(subtitle));
         comment_label.add_css_class("app-finder-comment");
         comment_label.set_halign(gtk4::Align::Start);
         comment_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
@@ -282,23 +538,29 @@ fn create_app_row(entry: &app_finder::DesktopEntry, popover: &gtk4::Popover) ->
 
     row.append(&info_box);
 
+    let revealer = match &result.action {
+        FinderAction::Launch(entry) => {
+            Some(create_options_revealer(&row, entry, popover, launcher))
+        }
+        _ => None,
+    };
+
     // Make the row clickable via a GestureClick
     let gesture = gtk4::GestureClick::new();
-    let exec = entry.exec.clone();
-    let name = entry.name.clone();
-    let icon = entry.icon.clone();
-    let comment = entry.comment.clone();
+    let action = result.action.clone();
     let popover_clone = popover.clone();
-    gesture.connect_released(move |_, _, _, _| {
-        let entry = app_finder::DesktopEntry {
-            name: name.clone(),
-            exec: exec.clone(),
-            icon: icon.clone(),
-            comment: comment.clone(),
-            categories: Vec::new(),
-            no_display: false,
-        };
-        app_finder::launch_app(&entry);
+    let launcher = launcher.clone();
+    let row_ref = row.clone();
+    gesture.connect_released(move |_, _, x, y| {
+        // Leave clicks on the expand button to the button
+        let on_button = row_ref
+            .pick(x, y, gtk4::PickFlags::DEFAULT)
+            .and_then(|w| w.ancestor(gtk4::Button::static_type()))
+            .is_some();
+        if on_button {
+            return;
+        }
+        activate(&action, false, &launcher);
         popover_clone.popdown();
     });
     row.add_controller(gesture);
@@ -315,5 +577,147 @@ fn create_app_row(entry: &app_finder::DesktopEntry, popover: &gtk4::Popover) ->
     });
     row.add_controller(motion);
 
-    row
+    item.append(&row);
+    if let Some(revealer) = revealer {
+        item.append(&revealer);
+    }
+    item
+}
+
+/// Adds the expand button to an app's `row` and returns the revealer it
+/// toggles, which holds the launch options.
+fn create_options_revealer(
+    row: &gtk4::Box,
+    entry: &app_finder::DesktopEntry,
+    popover: &gtk4::Popover,
+    launcher: &Rc<Launcher>,
+) -> gtk4::Revealer {
+    let revealer = gtk4::Revealer::new();
+    revealer.set_transition_type(gtk4::RevealerTransitionType::SlideDown);
+
+    let expand_btn = gtk4::Button::with_label("\u{25BE}");
+    expand_btn.add_css_class("app-finder-expand");
+    expand_btn.set_valign(gtk4::Align::Center);
+    expand_btn.set_tooltip_text(Some("Actions and launch options"));
+    let revealer_c = revealer.clone();
+    expand_btn.connect_clicked(move |_| {
+        revealer_c.set_reveal_child(!revealer_c.reveals_child());
+    });
+    row.append(&expand_btn);
+
+    let entry = entry.clone();
+    let popover = popover.clone();
+    let launcher = launcher.clone();
+    revealer.connect_reveal_child_notify(move |revealer| {
+        if revealer.reveals_child() && revealer.child().is_none() {
+            revealer.set_child(Some(&build_launch_options(&entry, &popover, &launcher)));
+        }
+    });
+
+    revealer
+}
+
+fn build_launch_options(
+    entry: &app_finder::DesktopEntry,
+    popover: &gtk4::Popover,
+    launcher: &Rc<Launcher>,
+) -> gtk4::Box {
+    let options_box = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    options_box.add_css_class("app-finder-options");
+
+    let add_option = |icon: Option<&str>, label: &str, options: LaunchOptions, placement: Placement| {
+        let btn = gtk4::Button::new();
+        btn.add_css_class("app-finder-option");
+
+        let content = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+        let image = gtk4::Image::from_icon_name(icon.unwrap_or("system-run-symbolic"));
+        image.set_pixel_size(16);
+        content.append(&image);
+        let text = gtk4::Label::new(Some(label));
+        text.set_halign(gtk4::Align::Start);
+        text.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        content.append(&text);
+        btn.set_child(Some(&content));
+
+        let entry = entry.clone();
+        let popover = popover.clone();
+        let launcher = launcher.clone();
+        btn.connect_clicked(move |_| {
+            launch(&entry, &options, placement, &launcher);
+            popover.popdown();
+        });
+        options_box.append(&btn);
+    };
+
+    // [Desktop Action ...] entries, e.g. "New Private Window"
+    for action in &entry.actions {
+        let options = LaunchOptions {
+            action: Some(action.id.clone()),
+            ..Default::default()
+        };
+        add_option(
+            action.icon.as_deref().or(entry.icon.as_deref()),
+            &action.name,
+            options,
+            Placement::NewInstance,
+        );
+    }
+
+    add_option(
+        Some("window-new-symbolic"),
+        "New Instance",
+        LaunchOptions::default(),
+        Placement::NewInstance,
+    );
+
+    if !entry.terminal {
+        let options = LaunchOptions {
+            terminal: true,
+            ..Default::default()
+        };
+        add_option(
+            Some("utilities-terminal-symbolic"),
+            "Run in Terminal (Ctrl+Enter)",
+            options,
+            Placement::NewInstance,
+        );
+    }
+
+    if let Some(client) = &launcher.client {
+        let mut ids: Vec<i32> = client
+            .workspaces()
+            .map(|list| list.iter().map(|ws| ws.id).filter(|id| *id > 0).collect())
+            .unwrap_or_default();
+        ids.sort_unstable();
+        // Offer the next unused workspace too
+        ids.push(ids.last().copied().unwrap_or(0) + 1);
+
+        let ws_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+        ws_row.add_css_class("app-finder-workspaces");
+        let ws_label = gtk4::Label::new(Some("Open on"));
+        ws_label.add_css_class("app-finder-comment");
+        ws_row.append(&ws_label);
+
+        for id in ids {
+            let btn = gtk4::Button::with_label(&id.to_string());
+            btn.add_css_class("app-finder-workspace-btn");
+            btn.set_tooltip_text(Some(&format!("Open on workspace {id}")));
+            let entry = entry.clone();
+            let popover = popover.clone();
+            let launcher = launcher.clone();
+            btn.connect_clicked(move |_| {
+                launch(
+                    &entry,
+                    &LaunchOptions::default(),
+                    Placement::Workspace(id),
+                    &launcher,
+                );
+                popover.popdown();
+            });
+            ws_row.append(&btn);
+        }
+        options_box.append(&ws_row);
+    }
+
+    options_box
 }
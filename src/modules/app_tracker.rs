@@ -1,10 +1,37 @@
-use crate::hyprland::HyprlandClient;
+use crate::config::{AppTrackerConfig, LaunchConfig};
+use crate::hyprland::client::Window;
+use crate::hyprland::{normalize_address, HyprEvent, HyprlandClient};
+use crate::modules::icons::{self, IconSource};
+use crate::system::app_finder::{self, LaunchOptions};
+use crate::system::desktop_index;
+use crate::system::launcher::{self, LaunchCommand};
+use crate::system::process;
+use crate::system::screenshot::{self, Region, ScreenshotBackend};
 use gtk4::gdk;
 use gtk4::glib;
 use gtk4::prelude::*;
 use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const HOVER_OPEN_DELAY_MS: u64 = 500;
+const HOVER_CLOSE_DELAY_MS: u64 = 250;
+const QUIT_POLL_MS: u64 = 250;
+
+/// A client as tracked from socket2 events. `seq` preserves open order so
+/// windows of an app keep a stable order across updates.
+struct TrackedWindow {
+    class: String,
+    title: String,
+    pid: i32,
+    workspace_id: i32,
+    workspace_name: String,
+    at: [i32; 2],
+    size: [i32; 2],
+    seq: u64,
+}
 
 struct AppInfo {
     class: String,
@@ -12,25 +39,83 @@ struct AppInfo {
     windows: Vec<WindowInfo>,
     focused: bool,
     all_minimized: bool,
+    urgent: bool,
 }
 
 struct WindowInfo {
     address: String,
     pid: i32,
     minimized: bool,
+    urgent: bool,
+    title: String,
+    workspace_id: i32,
+    workspace_name: String,
+    at: [i32; 2],
+    size: [i32; 2],
+}
+
+struct AppButton {
+    button: gtk4::Button,
+    icon: gtk4::Image,
+    count_label: gtk4::Label,
+}
+
+struct TrackerState {
+    client: Rc<HyprlandClient>,
+    widget: gtk4::Box,
+    config: AppTrackerConfig,
+    launch: LaunchConfig,
+    // Keyed by address in `j/clients` form (with `0x`)
+    windows: RefCell<HashMap<String, TrackedWindow>>,
+    active_address: RefCell<String>,
+    // Windows that requested attention and haven't been focused since
+    urgent: RefCell<HashSet<String>>,
+    next_seq: Cell<u64>,
+    // Derived from `windows`, keyed by lowercase class
+    apps: RefCell<HashMap<String, AppInfo>>,
+    buttons: RefCell<HashMap<String, AppButton>>,
+    menu_open: Cell<bool>,
+}
+
+impl TrackerState {
+    fn take_seq(&self) -> u64 {
+        let seq = self.next_seq.get();
+        self.next_seq.set(seq + 1);
+        seq
+    }
+}
+
+/// Hover preview state shared between a button's motion controller and its popover.
+#[derive(Clone, Default)]
+struct HoverState {
+    timer: Rc<RefCell<Option<glib::SourceId>>>,
+    popover: Rc<RefCell<Option<gtk4::Popover>>>,
+    pointer_inside: Rc<Cell<bool>>,
+}
+
+impl HoverState {
+    fn cancel_timer(&self) {
+        if let Some(id) = self.timer.borrow_mut().take() {
+            id.remove();
+        }
+    }
+
+    fn close(&self) {
+        self.cancel_timer();
+        let popover = self.popover.borrow_mut().take();
+        if let Some(popover) = popover {
+            popover.popdown();
+        }
+    }
 }
 
 pub struct AppTracker {
     pub widget: gtk4::Box,
-    client: Rc<HyprlandClient>,
-    apps: Rc<RefCell<HashMap<String, AppInfo>>>,
-    buttons: Rc<RefCell<HashMap<String, gtk4::Button>>>,
-    menu_open: Rc<Cell<bool>>,
-    source_id: RefCell<Option<glib::SourceId>>,
+    state: Rc<TrackerState>,
 }
 
 impl AppTracker {
-    pub fn new(client: Rc<HyprlandClient>, interval_secs: u32) -> Self {
+    pub fn new(client: Rc<HyprlandClient>, config: &AppTrackerConfig, launch: &LaunchConfig) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
         widget.add_css_class("app-tracker");
 
@@ -40,255 +125,776 @@ impl AppTracker {
         rune.set_tooltip_text(Some("\u{16D7} Mannaz - Running Apps"));
         widget.append(&rune);
 
-        let apps = Rc::new(RefCell::new(HashMap::new()));
-        let buttons = Rc::new(RefCell::new(HashMap::new()));
-        let menu_open = Rc::new(Cell::new(false));
-
-        let tracker = Self {
-            widget,
+        let state = Rc::new(TrackerState {
             client,
-            apps,
-            buttons,
-            menu_open,
-            source_id: RefCell::new(None),
-        };
+            widget: widget.clone(),
+            config: config.clone(),
+            launch: launch.clone(),
+            windows: RefCell::new(HashMap::new()),
+            active_address: RefCell::new(String::new()),
+            urgent: RefCell::new(HashSet::new()),
+            next_seq: Cell::new(0),
+            apps: RefCell::new(HashMap::new()),
+            buttons: RefCell::new(HashMap::new()),
+            menu_open: Cell::new(false),
+        });
 
-        tracker.refresh();
-        tracker.start_updates(interval_secs);
-        tracker
+        resync(&state);
+        watch_desktop_index(&state);
+        Self { widget, state }
     }
 
-    fn start_updates(&self, interval_secs: u32) {
-        let client = self.client.clone();
-        let widget = self.widget.clone();
-        let apps = self.apps.clone();
-        let buttons = self.buttons.clone();
-        let menu_open = self.menu_open.clone();
-
-        *self.source_id.borrow_mut() = Some(glib::timeout_add_seconds_local(interval_secs, move || {
-            do_refresh(&client, &widget, &apps, &buttons, &menu_open);
-            glib::ControlFlow::Continue
-        }));
+    /// Applies a socket2 event to the client model.
+    pub fn handle_event(&self, event: &HyprEvent) {
+        handle_event(&self.state, event);
     }
 
-    pub fn refresh(&self) {
-        do_refresh(&self.client, &self.widget, &self.apps, &self.buttons, &self.menu_open);
+    /// Acts on a `minimized` event's "ADDRESS,0|1" payload. Unlike
+    /// `handle_event` this dispatches to the compositor, so only one tracker
+    /// should be given each request.
+    pub fn handle_minimize_request(&self, data: &str) {
+        // Clients asking to be minimized go to the same special workspace
+        // the context menu uses; the resulting move event updates the model.
+        let Some((address, minimize)) = data.split_once(',') else {
+            return;
+        };
+        let address = normalize_address(address);
+        let minimized = self
+            .state
+            .windows
+            .borrow()
+            .get(&address)
+            .map(|w| is_minimized_workspace(&w.workspace_name));
+        match (minimize.trim(), minimized) {
+            ("1", Some(false)) => {
+                let _ = self.state.client.minimize_window(&address);
+            }
+            ("0", Some(true)) => {
+                let _ = self.state.client.restore_window(&address);
+            }
+            _ => {}
+        }
     }
+}
 
-    pub fn stop(&self) {
-        if let Some(id) = self.source_id.borrow_mut().take() {
-            id.remove();
+fn is_minimized_workspace(name: &str) -> bool {
+    name.starts_with("special:minimized")
+}
+
+fn handle_event(state: &Rc<TrackerState>, event: &HyprEvent) {
+    match event {
+        HyprEvent::OpenWindow(data) => {
+            let mut parts = data.splitn(4, ',');
+            let (Some(address), Some(workspace_name), Some(class)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return;
+            };
+            let title = parts.next().unwrap_or_default();
+            let window = TrackedWindow {
+                class: class.to_string(),
+                title: title.to_string(),
+                // Filled in from `j/clients` when a menu or preview needs them
+                pid: 0,
+                workspace_id: known_workspace_id(state, workspace_name),
+                workspace_name: workspace_name.to_string(),
+                at: [0, 0],
+                size: [0, 0],
+                seq: state.take_seq(),
+            };
+            state
+                .windows
+                .borrow_mut()
+                .insert(normalize_address(address), window);
         }
+        HyprEvent::CloseWindow(address) => {
+            let address = normalize_address(address);
+            state.windows.borrow_mut().remove(&address);
+            state.urgent.borrow_mut().remove(&address);
+            screenshot::remove_preview(&address);
+            let mut active = state.active_address.borrow_mut();
+            if *active == address {
+                active.clear();
+            }
+        }
+        HyprEvent::ActiveWindowV2(address) => {
+            let address = normalize_address(address);
+            state.urgent.borrow_mut().remove(&address);
+            *state.active_address.borrow_mut() = address;
+        }
+        HyprEvent::Urgent(address) => {
+            let address = normalize_address(address);
+            if !state.windows.borrow().contains_key(&address)
+                || *state.active_address.borrow() == address
+            {
+                return;
+            }
+            state.urgent.borrow_mut().insert(address);
+        }
+        HyprEvent::MoveWindow(data) => {
+            let mut parts = data.splitn(3, ',');
+            let (Some(address), Some(id), Some(name)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return;
+            };
+            let mut windows = state.windows.borrow_mut();
+            let Some(window) = windows.get_mut(&normalize_address(address)) else {
+                return;
+            };
+            window.workspace_id = id.parse().unwrap_or(window.workspace_id);
+            window.workspace_name = name.to_string();
+        }
+        HyprEvent::WindowTitle(data) => {
+            let Some((address, title)) = data.split_once(',') else {
+                return;
+            };
+            if let Some(window) = state.windows.borrow_mut().get_mut(&normalize_address(address)) {
+                window.title = title.to_string();
+            }
+        }
+        HyprEvent::Reconnected => {
+            resync(state);
+            return;
+        }
+        _ => return,
     }
+
+    sync_buttons(state);
 }
 
-fn do_refresh(
-    client: &Rc<HyprlandClient>,
-    widget: &gtk4::Box,
-    apps_cell: &Rc<RefCell<HashMap<String, AppInfo>>>,
-    buttons_cell: &Rc<RefCell<HashMap<String, gtk4::Button>>>,
-    menu_open: &Rc<Cell<bool>>,
-) {
-    // Skip rebuild while a context menu is open to prevent destroying its parent
-    if menu_open.get() {
-        return;
+/// The id of a workspace from its name alone, without an IPC round trip:
+/// numbered workspaces are named after their id, and named ones are looked
+/// up among the windows already on them. Anything else waits for
+/// `refresh_window_details`.
+fn known_workspace_id(state: &TrackerState, name: &str) -> i32 {
+    if let Ok(id) = name.parse() {
+        return id;
     }
+    state
+        .windows
+        .borrow()
+        .values()
+        .find(|w| w.workspace_name == name)
+        .map(|w| w.workspace_id)
+        .unwrap_or_default()
+}
 
-    let Ok(clients) = client.clients() else {
+/// Rebuilds the client model from `j/clients`. Only needed at startup and
+/// after the event socket reconnects, since events may have been missed.
+fn resync(state: &Rc<TrackerState>) {
+    let Ok(clients) = state.client.clients() else {
         return;
     };
-    let active_class = client
+
+    {
+        let mut windows = state.windows.borrow_mut();
+        let previous = std::mem::take(&mut *windows);
+        for c in clients {
+            let seq = previous
+                .get(&c.address)
+                .map(|w| w.seq)
+                .unwrap_or_else(|| state.take_seq());
+            windows.insert(
+                c.address,
+                TrackedWindow {
+                    class: c.class,
+                    title: c.title,
+                    pid: c.pid,
+                    workspace_id: c.workspace.id,
+                    workspace_name: c.workspace.name,
+                    at: c.at,
+                    size: c.size,
+                    seq,
+                },
+            );
+        }
+    }
+
+    *state.active_address.borrow_mut() = state
+        .client
         .active_window()
-        .map(|w| w.class.to_lowercase())
+        .map(|w| w.address)
         .unwrap_or_default();
 
-    let mut new_apps: HashMap<String, AppInfo> = HashMap::new();
+    sync_buttons(state);
+}
+
+/// Pulls pid, geometry and workspace ids, which socket2 events don't carry
+/// (or only by name), for the windows already in the model. `j/clients` is
+/// read on a worker thread; `then` runs once the model is up to date.
+fn refresh_window_details(
+    state: &Rc<TrackerState>,
+    then: impl FnOnce(&Rc<TrackerState>) + 'static,
+) {
+    let client = (*state.client).clone();
+    let (sender, receiver) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        let _ = sender.send_blocking(client.clients());
+    });
+
+    let weak = Rc::downgrade(state);
+    glib::spawn_future_local(async move {
+        let Ok(result) = receiver.recv().await else {
+            return;
+        };
+        let Some(state) = weak.upgrade() else {
+            return;
+        };
+        if let Ok(clients) = result {
+            apply_window_details(&state, clients);
+        }
+        then(&state);
+    });
+}
+
+fn apply_window_details(state: &TrackerState, clients: Vec<Window>) {
+    {
+        let mut windows = state.windows.borrow_mut();
+        for c in clients {
+            if let Some(window) = windows.get_mut(&c.address) {
+                window.pid = c.pid;
+                window.workspace_id = c.workspace.id;
+                window.workspace_name = c.workspace.name;
+                window.at = c.at;
+                window.size = c.size;
+            }
+        }
+    }
+
+    *state.apps.borrow_mut() = collect_apps(state);
+}
+
+fn collect_apps(state: &TrackerState) -> HashMap<String, AppInfo> {
+    let windows = state.windows.borrow();
+    let active_address = state.active_address.borrow();
+    let urgent = state.urgent.borrow();
+
+    let mut ordered: Vec<(&String, &TrackedWindow)> = windows.iter().collect();
+    ordered.sort_by_key(|(_, w)| w.seq);
+
+    let mut apps: HashMap<String, AppInfo> = HashMap::new();
 
-    for c in &clients {
-        let class = c.class.to_lowercase();
+    for (address, w) in ordered {
+        let class = w.class.to_lowercase();
         if class.is_empty() {
             continue;
         }
 
-        let is_minimized = c.workspace.name.starts_with("special:minimized");
         let win_info = WindowInfo {
-            address: c.address.clone(),
-            pid: c.pid,
-            minimized: is_minimized,
+            address: address.clone(),
+            pid: w.pid,
+            minimized: is_minimized_workspace(&w.workspace_name),
+            urgent: urgent.contains(address),
+            title: w.title.clone(),
+            workspace_id: w.workspace_id,
+            workspace_name: w.workspace_name.clone(),
+            at: w.at,
+            size: w.size,
         };
-
-        if let Some(app) = new_apps.get_mut(&class) {
-            app.windows.push(win_info);
-            if class == active_class {
-                app.focused = true;
-            }
-        } else {
-            new_apps.insert(
-                class.clone(),
-                AppInfo {
-                    class: class.clone(),
-                    title: c.class.clone(),
-                    windows: vec![win_info],
-                    focused: class == active_class,
-                    all_minimized: false,
-                },
-            );
-        }
+        let focused = *address == *active_address;
+
+        let app = apps.entry(class.clone()).or_insert_with(|| AppInfo {
+            class,
+            title: w.class.clone(),
+            windows: Vec::new(),
+            focused: false,
+            all_minimized: false,
+            urgent: false,
+        });
+        app.urgent |= win_info.urgent;
+        app.windows.push(win_info);
+        app.focused |= focused;
     }
 
-    for app in new_apps.values_mut() {
+    for app in apps.values_mut() {
         app.all_minimized = app.windows.iter().all(|w| w.minimized);
     }
 
-    // Rebuild UI
+    apps
+}
+
+/// Brings the buttons in line with the model: existing buttons are updated in
+/// place, and buttons are only added or removed while no menu is open so a
+/// popover never loses its parent.
+fn sync_buttons(state: &Rc<TrackerState>) {
+    let new_apps = collect_apps(state);
+    let structural = !state.menu_open.get();
+
     {
-        let mut buttons = buttons_cell.borrow_mut();
-        for btn in buttons.values() {
-            widget.remove(btn);
+        let mut buttons = state.buttons.borrow_mut();
+
+        if structural {
+            let gone: Vec<String> = buttons
+                .keys()
+                .filter(|class| !new_apps.contains_key(*class))
+                .cloned()
+                .collect();
+            for class in gone {
+                if let Some(entry) = buttons.remove(&class) {
+                    state.widget.remove(&entry.button);
+                }
+            }
         }
-        buttons.clear();
-    }
 
-    let mut classes: Vec<String> = new_apps.keys().cloned().collect();
-    classes.sort();
-
-    for class in &classes {
-        let app = &new_apps[class];
-        let btn = create_app_button(client, app, apps_cell, widget, menu_open);
-        buttons_cell.borrow_mut().insert(class.clone(), btn.clone());
-        widget.append(&btn);
+        let mut classes: Vec<&String> = new_apps.keys().collect();
+        classes.sort();
+
+        // Buttons follow the rune label in class order
+        let mut previous = state.widget.first_child();
+        for class in classes {
+            let app = &new_apps[class];
+            if let Some(entry) = buttons.get(class) {
+                update_app_button(entry, app);
+                previous = Some(entry.button.clone().upcast());
+            } else if structural {
+                let entry = create_app_button(state, app);
+                update_app_button(&entry, app);
+                state
+                    .widget
+                    .insert_child_after(&entry.button, previous.as_ref());
+                previous = Some(entry.button.clone().upcast());
+                buttons.insert(class.clone(), entry);
+            }
+        }
     }
 
-    *apps_cell.borrow_mut() = new_apps;
+    *state.apps.borrow_mut() = new_apps;
 }
 
-fn create_app_button(
-    client: &Rc<HyprlandClient>,
-    app: &AppInfo,
-    apps_cell: &Rc<RefCell<HashMap<String, AppInfo>>>,
-    tracker_widget: &gtk4::Box,
-    menu_open: &Rc<Cell<bool>>,
-) -> gtk4::Button {
+fn create_app_button(state: &Rc<TrackerState>, app: &AppInfo) -> AppButton {
     let btn = gtk4::Button::new();
     btn.add_css_class("app-button");
 
     let content = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
 
-    let icon = get_app_icon(&app.class);
+    let icon = get_app_icon(&app.class, &state.config);
     icon.add_css_class("app-icon");
     content.append(&icon);
 
-    if app.windows.len() > 1 {
-        let count_label = gtk4::Label::new(Some(&app.windows.len().to_string()));
-        count_label.add_css_class("app-count");
-        content.append(&count_label);
-    }
+    let count_label = gtk4::Label::new(None);
+    count_label.add_css_class("app-count");
+    content.append(&count_label);
 
     btn.set_child(Some(&content));
 
+    // Left click - focus/cycle/restore
+    let state_c = state.clone();
+    let class = app.class.clone();
+    btn.connect_clicked(move |_| {
+        on_app_clicked(&state_c, &class);
+    });
+
+    // Right click - context menu
+    let gesture = gtk4::GestureClick::new();
+    gesture.set_button(gdk::BUTTON_SECONDARY);
+    let state_c = state.clone();
+    let class = app.class.clone();
+    let btn_ref = btn.clone();
+    let hover = HoverState::default();
+    let hover_ref = hover.clone();
+    gesture.connect_pressed(move |_, _n, _x, _y| {
+        hover_ref.close();
+        // Pids and geometry aren't carried by events
+        let class = class.clone();
+        let btn_ref = btn_ref.clone();
+        refresh_window_details(&state_c, move |state| {
+            if !state.menu_open.get() {
+                show_context_menu(state, &class, &btn_ref);
+            }
+        });
+    });
+    btn.add_controller(gesture);
+
+    if state.config.hover_preview {
+        attach_hover_preview(&btn, state, &app.class, &hover);
+    }
+
+    AppButton {
+        button: btn,
+        icon,
+        count_label,
+    }
+}
+
+fn update_app_button(entry: &AppButton, app: &AppInfo) {
+    let count = app.windows.len();
+    entry.count_label.set_visible(count > 1);
+    entry.count_label.set_text(&count.to_string());
+
+    let btn = &entry.button;
     if app.focused {
         btn.add_css_class("focused");
+    } else {
+        btn.remove_css_class("focused");
     }
     if app.all_minimized {
         btn.add_css_class("minimized");
+    } else {
+        btn.remove_css_class("minimized");
+    }
+    if app.urgent {
+        btn.add_css_class("urgent");
+    } else {
+        btn.remove_css_class("urgent");
     }
 
     let minimized_count = app.windows.iter().filter(|w| w.minimized).count();
     let tooltip = if minimized_count > 0 {
         format!(
             "{}\n{} window(s) ({} minimized)\nClick: Focus/Restore | Right-click: Options",
-            app.title,
-            app.windows.len(),
-            minimized_count
+            app.title, count, minimized_count
         )
     } else {
         format!(
             "{}\n{} window(s)\nClick: Focus | Right-click: Options",
-            app.title,
-            app.windows.len()
+            app.title, count
         )
     };
     btn.set_tooltip_text(Some(&tooltip));
+}
 
-    // Left click - focus/cycle/restore
-    let client_clone = client.clone();
-    let class = app.class.clone();
-    let apps_ref = apps_cell.clone();
-    btn.connect_clicked(move |_| {
-        on_app_clicked(&client_clone, &class, &apps_ref);
-    });
+fn attach_hover_preview(
+    btn: &gtk4::Button,
+    state: &Rc<TrackerState>,
+    class: &str,
+    hover: &HoverState,
+) {
+    let motion = gtk4::EventControllerMotion::new();
 
-    // Right click - context menu
-    let gesture = gtk4::GestureClick::new();
-    gesture.set_button(gdk::BUTTON_SECONDARY);
-    let client_clone = client.clone();
-    let class = app.class.clone();
-    let apps_ref = apps_cell.clone();
+    let state = state.clone();
+    let class = class.to_string();
     let btn_ref = btn.clone();
-    let tracker_ref = tracker_widget.clone();
-    let menu_flag = menu_open.clone();
-    gesture.connect_pressed(move |_, _n, _x, _y| {
-        show_context_menu(&client_clone, &class, &apps_ref, &btn_ref, &tracker_ref, &menu_flag);
+    let hover_enter = hover.clone();
+    motion.connect_enter(move |motion, _, _| {
+        hover_enter.cancel_timer();
+        // Don't stack a preview on top of an open context menu
+        if state.menu_open.get() || hover_enter.popover.borrow().is_some() {
+            return;
+        }
+
+        let state = state.clone();
+        let class = class.clone();
+        let btn_ref = btn_ref.clone();
+        let hover_open = hover_enter.clone();
+        let motion = motion.clone();
+        let id = glib::timeout_add_local_once(
+            Duration::from_millis(HOVER_OPEN_DELAY_MS),
+            move || {
+                hover_open.timer.borrow_mut().take();
+                if state.menu_open.get() {
+                    return;
+                }
+                refresh_window_details(&state, move |state| {
+                    // The pointer may have moved on while details were fetched
+                    if state.menu_open.get()
+                        || hover_open.popover.borrow().is_some()
+                        || !motion.contains_pointer()
+                    {
+                        return;
+                    }
+                    if let Some(popover) = show_window_preview(state, &class, &btn_ref, &hover_open)
+                    {
+                        *hover_open.popover.borrow_mut() = Some(popover);
+                    }
+                });
+            },
+        );
+        *hover_enter.timer.borrow_mut() = Some(id);
     });
-    btn.add_controller(gesture);
 
-    btn
+    let hover_leave = hover.clone();
+    motion.connect_leave(move |_| {
+        hover_leave.cancel_timer();
+        schedule_hover_close(&hover_leave);
+    });
+
+    btn.add_controller(motion);
 }
 
-fn get_app_icon(class: &str) -> gtk4::Image {
-    let icon_mappings: HashMap<&str, &str> = HashMap::from([
-        ("firefox", "firefox"),
-        ("chromium", "chromium"),
-        ("google-chrome", "google-chrome"),
-        ("code", "visual-studio-code"),
-        ("code-oss", "code-oss"),
-        ("discord", "discord"),
-        ("spotify", "spotify"),
-        ("steam", "steam"),
-        ("telegram-desktop", "telegram"),
-        ("thunar", "thunar"),
-        ("nautilus", "nautilus"),
-        ("kitty", "kitty"),
-        ("alacritty", "Alacritty"),
-        ("foot", "foot"),
-        ("wezterm", "wezterm"),
-        ("obsidian", "obsidian"),
-        ("vlc", "vlc"),
-        ("mpv", "mpv"),
-        ("gimp", "gimp"),
-        ("inkscape", "inkscape"),
-        ("blender", "blender"),
-        ("libreoffice", "libreoffice-startcenter"),
-    ]);
-
-    let icon_name = icon_mappings
-        .get(class.to_lowercase().as_str())
-        .copied()
-        .unwrap_or(class);
-
-    let display = gdk::Display::default().unwrap();
-    let icon_theme = gtk4::IconTheme::for_display(&display);
-
-    if icon_theme.has_icon(icon_name) {
-        gtk4::Image::from_icon_name(icon_name)
-    } else if icon_theme.has_icon(&class.to_lowercase()) {
-        gtk4::Image::from_icon_name(&class.to_lowercase())
+fn schedule_hover_close(hover: &HoverState) {
+    let hover_close = hover.clone();
+    glib::timeout_add_local_once(Duration::from_millis(HOVER_CLOSE_DELAY_MS), move || {
+        if !hover_close.pointer_inside.get() {
+            hover_close.close();
+        }
+    });
+}
+
+fn show_window_preview(
+    state: &Rc<TrackerState>,
+    class: &str,
+    btn: &gtk4::Button,
+    hover: &HoverState,
+) -> Option<gtk4::Popover> {
+    let apps = state.apps.borrow();
+    let app = apps.get(class)?;
+
+    // Hold off adding/removing buttons so the preview's parent survives
+    state.menu_open.set(true);
+
+    let popover = gtk4::Popover::new();
+    popover.add_css_class("app-menu");
+    popover.add_css_class("app-preview");
+    popover.set_parent(btn);
+    popover.set_position(gtk4::PositionType::Bottom);
+    popover.set_autohide(false);
+    popover.set_has_arrow(false);
+
+    let content = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+    content.set_margin_top(8);
+    content.set_margin_bottom(8);
+    content.set_margin_start(8);
+    content.set_margin_end(8);
+
+    let header = gtk4::Label::new(Some(&app.title));
+    header.add_css_class("app-menu-header");
+    content.append(&header);
+
+    content.append(&build_window_list(state, app, &popover));
+    popover.set_child(Some(&content));
+
+    // Keep the preview open while the pointer is over it
+    let motion = gtk4::EventControllerMotion::new();
+    let hover_enter = hover.clone();
+    motion.connect_enter(move |_, _, _| {
+        hover_enter.pointer_inside.set(true);
+    });
+    let hover_leave = hover.clone();
+    motion.connect_leave(move |_| {
+        hover_leave.pointer_inside.set(false);
+        schedule_hover_close(&hover_leave);
+    });
+    popover.add_controller(motion);
+
+    let state_c = state.clone();
+    let hover_closed = hover.clone();
+    popover.connect_closed(move |p| {
+        state_c.menu_open.set(false);
+        hover_closed.pointer_inside.set(false);
+        hover_closed.popover.borrow_mut().take();
+        p.unparent();
+        // Apply any structural changes deferred while open
+        sync_buttons(&state_c);
+    });
+
+    popover.popup();
+    Some(popover)
+}
+
+/// Builds one clickable row per window of `app`: thumbnail, title and workspace.
+fn build_window_list(
+    state: &Rc<TrackerState>,
+    app: &AppInfo,
+    popover: &gtk4::Popover,
+) -> gtk4::Box {
+    let client = &state.client;
+    let config = &state.config;
+
+    let list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    list.add_css_class("app-window-list");
+
+    let backend = if config.previews {
+        ScreenshotBackend::from_config(&config.preview_backend)
+    } else {
+        ScreenshotBackend::None
+    };
+
+    // Only windows on a workspace shown on some monitor can be captured
+    let visible_workspaces: HashSet<i32> = if backend == ScreenshotBackend::None {
+        HashSet::new()
     } else {
-        gtk4::Image::from_icon_name("application-x-executable")
+        client
+            .monitors()
+            .map(|monitors| monitors.iter().map(|m| m.active_workspace.id).collect())
+            .unwrap_or_default()
+    };
+
+    let active_address = state.active_address.borrow().clone();
+
+    for win in &app.windows {
+        let row_btn = gtk4::Button::new();
+        row_btn.add_css_class("app-menu-item");
+        row_btn.add_css_class("app-window-row");
+        row_btn.set_focusable(true);
+        if win.address == active_address {
+            row_btn.add_css_class("focused");
+        }
+        if win.minimized {
+            row_btn.add_css_class("minimized");
+        }
+        if win.urgent {
+            row_btn.add_css_class("urgent");
+        }
+
+        let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+
+        if backend != ScreenshotBackend::None {
+            let capturable = !win.minimized && visible_workspaces.contains(&win.workspace_id);
+            row.append(&build_thumbnail(&app.class, win, &backend, capturable, config));
+        }
+
+        let info = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+        info.set_valign(gtk4::Align::Center);
+
+        let title = if win.title.is_empty() { &app.title } else { &win.title };
+        let title_label = gtk4::Label::new(Some(title));
+        title_label.add_css_class("app-menu-label");
+        title_label.set_halign(gtk4::Align::Start);
+        title_label.set_max_width_chars(28);
+        title_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        info.append(&title_label);
+
+        let location = if win.minimized {
+            "Minimized".to_string()
+        } else if win.workspace_name.is_empty() || win.workspace_name == win.workspace_id.to_string() {
+            format!("Workspace {}", win.workspace_id)
+        } else {
+            format!("Workspace {}", win.workspace_name)
+        };
+        let location_label = gtk4::Label::new(Some(&location));
+        location_label.add_css_class("app-window-workspace");
+        location_label.set_halign(gtk4::Align::Start);
+        info.append(&location_label);
+
+        row.append(&info);
+        row_btn.set_child(Some(&row));
+        row_btn.set_tooltip_text(Some(title));
+
+        let client_c = client.clone();
+        let address = win.address.clone();
+        let minimized = win.minimized;
+        let popover_c = popover.clone();
+        row_btn.connect_clicked(move |_| {
+            if minimized {
+                let _ = client_c.restore_window(&address);
+            } else {
+                let _ = client_c.focus_window(&address);
+            }
+            popover_c.popdown();
+        });
+
+        list.append(&row_btn);
     }
+
+    list
 }
 
-fn on_app_clicked(
-    client: &HyprlandClient,
+fn build_thumbnail(
     class: &str,
-    apps_cell: &Rc<RefCell<HashMap<String, AppInfo>>>,
-) {
-    let apps = apps_cell.borrow();
+    win: &WindowInfo,
+    backend: &ScreenshotBackend,
+    capturable: bool,
+    config: &AppTrackerConfig,
+) -> gtk4::Box {
+    let width = config.preview_width.max(48);
+    let height = if win.size[0] > 0 && win.size[1] > 0 {
+        (width * win.size[1] / win.size[0]).clamp(24, width)
+    } else {
+        width * 9 / 16
+    };
+
+    let frame = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    frame.add_css_class("app-window-thumbnail");
+    frame.set_size_request(width, height);
+
+    // App icon placeholder until (or instead of) the capture
+    let placeholder = get_app_icon(class, config);
+    placeholder.set_pixel_size(32);
+    placeholder.set_vexpand(true);
+    placeholder.set_valign(gtk4::Align::Center);
+    frame.append(&placeholder);
+
+    if !capturable {
+        return frame;
+    }
+    // No runtime dir to keep the capture in
+    let Some(output) = screenshot::preview_path(&win.address) else {
+        return frame;
+    };
+
+    let region = Region {
+        x: win.at[0],
+        y: win.at[1],
+        width: win.size[0],
+        height: win.size[1],
+    };
+    let scale = width as f64 / win.size[0].max(1) as f64;
+    let backend = backend.clone();
+
+    let (sender, receiver) = async_channel::bounded::<Option<PathBuf>>(1);
+    std::thread::spawn(move || {
+        let result = screenshot::capture_region(&backend, &region, scale, &output)
+            .ok()
+            .map(|_| output);
+        let _ = sender.send_blocking(result);
+    });
+
+    let frame_ref = frame.clone();
+    glib::spawn_future_local(async move {
+        if let Ok(Some(path)) = receiver.recv().await {
+            frame_ref.remove(&placeholder);
+            let picture = gtk4::Picture::for_filename(&path);
+            picture.set_can_shrink(true);
+            picture.set_content_fit(gtk4::ContentFit::Contain);
+            picture.set_vexpand(true);
+            frame_ref.append(&picture);
+        }
+    });
+
+    frame
+}
+
+/// Re-resolves button icons when the desktop index updates, so apps started
+/// before the first scan finished (or installed since) get their icons.
+fn watch_desktop_index(state: &Rc<TrackerState>) {
+    let receiver = icons::subscribe();
+    let weak = Rc::downgrade(state);
+    glib::spawn_future_local(async move {
+        while receiver.recv().await.is_ok() {
+            let Some(state) = weak.upgrade() else { break };
+            for (class, app_button) in state.buttons.borrow().iter() {
+                icons::set_icon(&app_button.icon, &app_icon_source(class, &state.config));
+            }
+        }
+    });
+}
+
+fn get_app_icon(class: &str, config: &AppTrackerConfig) -> gtk4::Image {
+    let image = gtk4::Image::new();
+    icons::set_icon(&image, &app_icon_source(class, config));
+    image
+}
+
+fn app_icon_source(class: &str, config: &AppTrackerConfig) -> IconSource {
+    let class = class.to_lowercase();
+
+    // Overrides are checked before the cache so config reloads apply
+    config
+        .icon_overrides
+        .iter()
+        .find(|(key, _)| key.to_lowercase() == class)
+        .map(|(_, value)| IconSource::from_value(value))
+        .unwrap_or_else(|| icons::app_icon(&class))
+}
+
+fn on_app_clicked(state: &TrackerState, class: &str) {
+    let client = &state.client;
+    let apps = state.apps.borrow();
     let Some(app) = apps.get(class) else { return };
     if app.windows.is_empty() {
         return;
     }
 
+    // Jump straight to a window that asked for attention
+    if let Some(win) = app.windows.iter().find(|w| w.urgent) {
+        if win.minimized {
+            let _ = client.restore_window(&win.address);
+        } else {
+            let _ = client.focus_window(&win.address);
+        }
+        return;
+    }
+
     if app.all_minimized {
         let _ = client.restore_window(&app.windows[0].address);
         return;
@@ -302,27 +908,20 @@ fn on_app_clicked(
     if visible.len() == 1 {
         let _ = client.focus_window(&visible[0].address);
     } else {
-        let active = client.active_window().ok();
-        let current_addr = active.map(|w| w.address).unwrap_or_default();
-        let current_idx = visible.iter().position(|w| w.address == current_addr);
+        let current_addr = state.active_address.borrow();
+        let current_idx = visible.iter().position(|w| w.address == *current_addr);
         let next_idx = current_idx.map(|i| (i + 1) % visible.len()).unwrap_or(0);
         let _ = client.focus_window(&visible[next_idx].address);
     }
 }
 
-fn show_context_menu(
-    client: &Rc<HyprlandClient>,
-    class: &str,
-    apps_cell: &Rc<RefCell<HashMap<String, AppInfo>>>,
-    btn: &gtk4::Button,
-    _tracker_widget: &gtk4::Box,
-    menu_open: &Rc<Cell<bool>>,
-) {
-    let apps = apps_cell.borrow();
+fn show_context_menu(state: &Rc<TrackerState>, class: &str, btn: &gtk4::Button) {
+    let client = &state.client;
+    let apps = state.apps.borrow();
     let Some(app) = apps.get(class) else { return };
 
-    // Block refreshes while the menu is open
-    menu_open.set(true);
+    // Hold off adding/removing buttons while the menu is open
+    state.menu_open.set(true);
 
     let popover = gtk4::Popover::new();
     popover.add_css_class("app-menu");
@@ -343,6 +942,12 @@ fn show_context_menu(
     let sep = gtk4::Separator::new(gtk4::Orientation::Horizontal);
     content.append(&sep);
 
+    // Per-window list: focus a specific window of this class
+    content.append(&build_window_list(state, app, &popover));
+
+    let sep_windows = gtk4::Separator::new(gtk4::Orientation::Horizontal);
+    content.append(&sep_windows);
+
     let visible_count = app.windows.iter().filter(|w| !w.minimized).count();
     let minimized_count = app.windows.iter().filter(|w| w.minimized).count();
 
@@ -425,46 +1030,48 @@ fn show_context_menu(
         content.append(&close_all_btn);
     }
 
-    // Force close separator
+    // Force quit separator
     let sep_force = gtk4::Separator::new(gtk4::Orientation::Horizontal);
     content.append(&sep_force);
 
-    // Force Close (ᛏ Tiwaz - Justice/Force)
+    let quit_status = QuitStatus::new();
+
+    // Force Quit (ᛏ Tiwaz - Justice/Force)
     if let Some(first) = app.windows.first() {
-        let pid = first.pid;
-        let popover_c = popover.clone();
-        let force_btn = create_menu_item("\u{16CF}", "Force Close", move || {
-            let _ = crate::hyprland::HyprlandClient::kill_window(pid);
-            popover_c.popdown();
-        });
-        force_btn.add_css_class("app-menu-danger");
-        content.append(&force_btn);
+        let targets = vec![QuitTarget::from(first)];
+        content.append(&build_force_quit_item(
+            state,
+            "Force Quit",
+            targets,
+            &popover,
+            &quit_status,
+        ));
     }
 
-    // Force Close All
+    // Force Quit All
     if app.windows.len() > 1 {
-        let pids: Vec<i32> = app.windows.iter().map(|w| w.pid).collect();
-        let label = format!("Force Close All ({})", pids.len());
-        let popover_c = popover.clone();
-        let force_all_btn = create_menu_item("\u{16CF}", &label, move || {
-            for pid in &pids {
-                let _ = crate::hyprland::HyprlandClient::kill_window(*pid);
-            }
-            popover_c.popdown();
-        });
-        force_all_btn.add_css_class("app-menu-danger");
-        content.append(&force_all_btn);
+        let targets: Vec<QuitTarget> = app.windows.iter().map(QuitTarget::from).collect();
+        let label = format!("Force Quit All ({})", targets.len());
+        content.append(&build_force_quit_item(
+            state,
+            &label,
+            targets,
+            &popover,
+            &quit_status,
+        ));
     }
 
+    content.append(&quit_status.container);
+
     // New instance
     let sep3 = gtk4::Separator::new(gtk4::Orientation::Horizontal);
     content.append(&sep3);
 
     let class_name = class.to_string();
-    let client_c = client.clone();
+    let state_c = state.clone();
     let popover_c = popover.clone();
     let new_btn = create_menu_item("\u{16A0}", "New Instance", move || {
-        let _ = client_c.dispatch(&format!("exec {class_name}"));
+        launch_new_instance(&state_c, &class_name);
         popover_c.popdown();
     });
     content.append(&new_btn);
@@ -497,11 +1104,12 @@ fn show_context_menu(
     });
     popover.add_controller(key_controller);
 
-    // Unblock refreshes and clean up when the popover closes
-    let menu_flag = menu_open.clone();
+    // Apply deferred changes and clean up when the popover closes
+    let state_c = state.clone();
     popover.connect_closed(move |p| {
-        menu_flag.set(false);
+        state_c.menu_open.set(false);
         p.unparent();
+        sync_buttons(&state_c);
     });
 
     popover.popup();
@@ -510,6 +1118,210 @@ fn show_context_menu(
     focus_first_menu_item(&content);
 }
 
+#[derive(Clone)]
+struct QuitTarget {
+    address: String,
+    pid: i32,
+}
+
+impl From<&WindowInfo> for QuitTarget {
+    fn from(win: &WindowInfo) -> Self {
+        Self {
+            address: win.address.clone(),
+            pid: win.pid,
+        }
+    }
+}
+
+/// Progress row shown in the context menu while a force quit waits out its
+/// grace period.
+#[derive(Clone)]
+struct QuitStatus {
+    container: gtk4::Box,
+    label: gtk4::Label,
+    progress: gtk4::ProgressBar,
+    running: Rc<Cell<bool>>,
+}
+
+impl QuitStatus {
+    fn new() -> Self {
+        let container = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+        container.add_css_class("app-menu-progress");
+        container.set_visible(false);
+
+        let label = gtk4::Label::new(None);
+        label.add_css_class("app-menu-label");
+        label.set_halign(gtk4::Align::Start);
+        container.append(&label);
+
+        let progress = gtk4::ProgressBar::new();
+        container.append(&progress);
+
+        Self {
+            container,
+            label,
+            progress,
+            running: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+/// A force quit menu item, followed by an inline confirmation row when
+/// `confirm_force_quit` is set.
+fn build_force_quit_item(
+    state: &Rc<TrackerState>,
+    label: &str,
+    targets: Vec<QuitTarget>,
+    popover: &gtk4::Popover,
+    status: &QuitStatus,
+) -> gtk4::Box {
+    let container = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+
+    let state_c = state.clone();
+    let popover_c = popover.clone();
+    let status_c = status.clone();
+    let start: Rc<dyn Fn()> = Rc::new(move || {
+        start_force_quit(&state_c, &targets, &status_c, &popover_c);
+    });
+
+    let confirm = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    confirm.add_css_class("app-menu-confirm");
+    confirm.set_visible(false);
+
+    let prompt = gtk4::Label::new(Some("Unsaved work may be lost"));
+    prompt.add_css_class("app-menu-label");
+    prompt.set_halign(gtk4::Align::Start);
+    confirm.append(&prompt);
+
+    let start_c = start.clone();
+    let confirm_c = confirm.clone();
+    let yes_btn = create_menu_item("\u{16CF}", "Quit", move || {
+        confirm_c.set_visible(false);
+        start_c();
+    });
+    yes_btn.add_css_class("app-menu-danger");
+    confirm.append(&yes_btn);
+
+    let confirm_c = confirm.clone();
+    let no_btn = create_menu_item("\u{16C1}", "Cancel", move || {
+        confirm_c.set_visible(false);
+    });
+    confirm.append(&no_btn);
+
+    let needs_confirm = state.config.confirm_force_quit;
+    let confirm_c = confirm.clone();
+    let yes_c = yes_btn.clone();
+    let item = create_menu_item("\u{16CF}", label, move || {
+        if needs_confirm {
+            confirm_c.set_visible(true);
+            yes_c.grab_focus();
+        } else {
+            start();
+        }
+    });
+    item.add_css_class("app-menu-danger");
+
+    container.append(&item);
+    container.append(&confirm);
+    container
+}
+
+/// Sends SIGTERM to each target, then escalates to SIGKILL for any process
+/// that still owns its window once the grace period runs out.
+fn start_force_quit(
+    state: &Rc<TrackerState>,
+    targets: &[QuitTarget],
+    status: &QuitStatus,
+    popover: &gtk4::Popover,
+) {
+    if status.running.replace(true) {
+        return;
+    }
+
+    let client = state.client.clone();
+
+    // Only signal a pid that still belongs to the window it was listed for,
+    // once per process (several windows can share one)
+    let pids = client.window_pids().unwrap_or_default();
+    let mut pending: Vec<QuitTarget> = Vec::new();
+    for target in targets {
+        if pending.iter().any(|p| p.pid == target.pid)
+            || pids.get(&target.address) != Some(&target.pid)
+        {
+            continue;
+        }
+        match process::terminate(target.pid) {
+            Ok(()) => pending.push(target.clone()),
+            Err(e) => eprintln!("Warning: {e}"),
+        }
+    }
+
+    if pending.is_empty() {
+        status.running.set(false);
+        popover.popdown();
+        return;
+    }
+
+    let grace = Duration::from_secs(state.config.kill_grace_secs.into());
+    let started = Instant::now();
+    status.container.set_visible(true);
+    status.progress.set_fraction(0.0);
+    status.label.set_text("Asking to quit\u{2026}");
+
+    let status = status.clone();
+    let popover = popover.clone();
+    glib::timeout_add_local(Duration::from_millis(QUIT_POLL_MS), move || {
+        // Done with a target once its process exits or it closes its window;
+        // one `j/clients` read covers every target
+        let pids = client.window_pids().unwrap_or_default();
+        pending.retain(|t| process::is_running(t.pid) && pids.get(&t.address) == Some(&t.pid));
+
+        let elapsed = started.elapsed();
+        if pending.is_empty() || elapsed >= grace {
+            for target in &pending {
+                if let Err(e) = process::kill(target.pid) {
+                    eprintln!("Warning: {e}");
+                }
+            }
+            status.running.set(false);
+            popover.popdown();
+            return glib::ControlFlow::Break;
+        }
+
+        let remaining = (grace - elapsed).as_secs_f64().ceil();
+        status
+            .progress
+            .set_fraction(elapsed.as_secs_f64() / grace.as_secs_f64());
+        status
+            .label
+            .set_text(&format!("Waiting for exit\u{2026} killing in {remaining:.0}s"));
+        glib::ControlFlow::Continue
+    });
+}
+
+fn launch_new_instance(state: &TrackerState, class: &str) {
+    let entry = app_finder::find_entry_for_class(&desktop_index::entries(), class).cloned();
+
+    match entry {
+        Some(entry) => {
+            if let Err(e) = app_finder::launch_app(&entry, &LaunchOptions::default(), &state.launch)
+            {
+                eprintln!("Warning: Failed to launch {}: {e}", entry.name);
+            }
+        }
+        None => {
+            // Without a desktop file, the class is usually the binary name
+            let command = LaunchCommand {
+                args: vec![class.to_string()],
+                working_dir: None,
+            };
+            if let Err(e) = launcher::launch(&command, class, &state.launch) {
+                eprintln!("Warning: Failed to launch {class}: {e}");
+            }
+        }
+    }
+}
+
 fn create_menu_item(rune: &str, label: &str, on_click: impl Fn() + 'static) -> gtk4::Button {
     let btn = gtk4::Button::new();
     btn.add_css_class("app-menu-item");
@@ -535,10 +1347,18 @@ fn collect_menu_buttons(container: &gtk4::Box) -> Vec<gtk4::Button> {
     let mut buttons = Vec::new();
     let mut child = container.first_child();
     while let Some(widget) = child {
+        // Skip collapsed rows such as an unopened confirmation
+        if !widget.is_visible() {
+            child = widget.next_sibling();
+            continue;
+        }
         if let Ok(btn) = widget.clone().downcast::<gtk4::Button>() {
             if btn.css_classes().iter().any(|c| c == "app-menu-item") {
                 buttons.push(btn);
             }
+        } else if let Ok(nested) = widget.clone().downcast::<gtk4::Box>() {
+            // Window list rows live in a nested box
+            buttons.extend(collect_menu_buttons(&nested));
         }
         child = widget.next_sibling();
     }
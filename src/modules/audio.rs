@@ -1,14 +1,16 @@
+use crate::config::{AudioConfig, ModuleActions};
+use crate::modules::{actions, icons};
 use crate::system::audio;
+use crate::system::audio_profiles::{self, DeviceRules, StreamMemory};
 use gtk4::glib;
 use gtk4::prelude::*;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct Audio {
     pub widget: gtk4::Box,
-    label: gtk4::Label,
-    slider: gtk4::Scale,
-    mute_btn: gtk4::Button,
+    output: OutputControls,
     source_slider: gtk4::Scale,
     source_mute_btn: gtk4::Button,
     source_label: gtk4::Label,
@@ -17,17 +19,22 @@ pub struct Audio {
     card_profiles_list: gtk4::Box,
     sink_list: gtk4::Box,
     source_list: gtk4::Box,
-    updating: Rc<Cell<bool>>,
-    event_listener: Option<audio::AudioEventListener>,
+    // For naming the rule the default devices follow; `AudioProfiles`
+    // applies them
+    sink_priority: Rc<Vec<String>>,
+    source_priority: Rc<Vec<String>>,
+    rule_label: gtk4::Label,
 }
 
 impl Audio {
-    pub fn new() -> Self {
+    pub fn new(config: &AudioConfig, module_actions: Option<&ModuleActions>) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
         widget.add_css_class("audio");
 
         let label = gtk4::Label::new(Some("--"));
         let updating = Rc::new(Cell::new(false));
+        let max_volume = config.max_volume.clamp(1, audio::MAX_VOLUME);
+        let cap = Rc::new(Cell::new(max_volume));
         let source_updating = Rc::new(Cell::new(false));
 
         let menu_button = gtk4::MenuButton::new();
@@ -64,23 +71,50 @@ impl Audio {
         header.append(&header_label);
         popover_content.append(&header);
 
-        let slider = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, 0.0, 100.0, 1.0);
+        let slider =
+            gtk4::Scale::with_range(gtk4::Orientation::Horizontal, 0.0, max_volume as f64, 1.0);
         slider.add_css_class("audio-slider");
         slider.set_draw_value(true);
         slider.set_value_pos(gtk4::PositionType::Right);
         slider.set_hexpand(true);
         slider.set_size_request(200, -1);
+        if max_volume > 100 {
+            // Past here is over-amplified
+            slider.add_mark(100.0, gtk4::PositionType::Bottom, None);
+        }
+        // A device limit shows as the fill level the knob can't pass
+        slider.set_restrict_to_fill_level(true);
 
         let updating_clone = updating.clone();
+        let cap_clone = cap.clone();
         slider.connect_value_changed(move |scale| {
             if updating_clone.get() {
                 return;
             }
-            let vol = scale.value() as i32;
+            let vol = (scale.value() as i32).min(cap_clone.get());
             audio::set_volume(vol);
         });
         popover_content.append(&slider);
 
+        // Balance, for sinks with a left and a right
+        let balance = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, -100.0, 100.0, 1.0);
+        balance.add_css_class("audio-balance-slider");
+        balance.set_draw_value(false);
+        balance.set_hexpand(true);
+        balance.add_mark(-100.0, gtk4::PositionType::Bottom, Some("L"));
+        balance.add_mark(0.0, gtk4::PositionType::Bottom, None);
+        balance.add_mark(100.0, gtk4::PositionType::Bottom, Some("R"));
+        balance.set_visible(false);
+
+        let updating_clone = updating.clone();
+        balance.connect_value_changed(move |scale| {
+            if updating_clone.get() {
+                return;
+            }
+            audio::set_balance(scale.value() / 100.0);
+        });
+        popover_content.append(&balance);
+
         // Mute button
         let mute_btn = gtk4::Button::new();
         mute_btn.add_css_class("audio-mute-btn");
@@ -94,20 +128,22 @@ impl Audio {
         mute_box.set_halign(gtk4::Align::Center);
         mute_btn.set_child(Some(&mute_box));
 
-        let label_clone = label.clone();
-        let widget_clone = widget.clone();
-        let slider_clone = slider.clone();
-        let mute_btn_clone = mute_btn.clone();
-        let updating_clone2 = updating.clone();
+        let output = OutputControls {
+            label,
+            widget: widget.clone(),
+            slider,
+            balance,
+            mute_btn: mute_btn.clone(),
+            updating,
+            cap,
+            max_volume,
+            limits: Rc::new(config.limits.clone()),
+        };
+
+        let output_clone = output.clone();
         mute_btn.connect_clicked(move |_| {
             audio::toggle_mute();
-            refresh_audio(
-                &label_clone,
-                &widget_clone,
-                &slider_clone,
-                &mute_btn_clone,
-                &updating_clone2,
-            );
+            refresh_audio(&output_clone);
         });
         popover_content.append(&mute_btn);
 
@@ -167,6 +203,7 @@ impl Audio {
         let source_mute_btn_clone = source_mute_btn.clone();
         let source_updating_clone2 = source_updating.clone();
         let source_label_clone = source_label.clone();
+        let widget_clone = widget.clone();
         source_mute_btn.connect_clicked(move |_| {
             audio::toggle_source_mute();
             refresh_source_audio(
@@ -174,6 +211,7 @@ impl Audio {
                 &source_mute_btn_clone,
                 &source_updating_clone2,
                 &source_label_clone,
+                &widget_clone,
             );
         });
         popover_content.append(&source_mute_btn);
@@ -219,6 +257,14 @@ impl Audio {
         );
         popover_content.append(&source_revealer);
 
+        // Which configured device preference is in effect
+        let rule_label = gtk4::Label::new(None);
+        rule_label.add_css_class("audio-rule-label");
+        rule_label.set_halign(gtk4::Align::Start);
+        rule_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        rule_label.set_visible(false);
+        popover_content.append(&rule_label);
+
         // === Section 6: Audio Profiles (NEW) ===
         let sep_profiles = gtk4::Separator::new(gtk4::Orientation::Horizontal);
         sep_profiles.add_css_class("audio-separator");
@@ -245,6 +291,7 @@ impl Audio {
         let source_mute_ref = source_mute_btn.clone();
         let source_updating_ref = source_updating.clone();
         let source_label_ref = source_label.clone();
+        let widget_ref = widget.clone();
         popover.connect_show(move |_| {
             rebuild_device_lists(&sink_list_ref, &source_list_ref);
             rebuild_app_streams(&app_streams_ref);
@@ -254,6 +301,7 @@ impl Audio {
                 &source_mute_ref,
                 &source_updating_ref,
                 &source_label_ref,
+                &widget_ref,
             );
         });
 
@@ -261,11 +309,56 @@ impl Audio {
 
         widget.append(&menu_button);
 
+        let defaults = ModuleActions {
+            on_middle_click: "toggle-mute".to_string(),
+            on_scroll_up: "volume-up".to_string(),
+            on_scroll_down: "volume-down".to_string(),
+            ..Default::default()
+        };
+        let step = config.scroll_step;
+        let output_c = output.clone();
+        let source_slider_c = source_slider.clone();
+        let source_mute_c = source_mute_btn.clone();
+        let source_updating_c = source_updating.clone();
+        let source_label_c = source_label.clone();
+        actions::attach(
+            &widget,
+            &actions::with_defaults(module_actions, defaults),
+            move |action, steps| {
+                let delta = step * steps as i32;
+                match action {
+                    "volume-up" => {
+                        // Scrolling never pushes past 100%, nor pulls down from above it,
+                        // and stops at the device's limit
+                        let volume = audio::get_info().volume;
+                        let target = (volume + delta).min(volume.max(100));
+                        audio::set_volume(target.min(output_c.cap.get()));
+                    }
+                    "volume-down" => audio::set_volume(audio::get_info().volume - delta),
+                    "toggle-mute" => audio::toggle_mute(),
+                    "mic-up" => {
+                        let volume = audio::get_source_info().volume;
+                        audio::set_source_volume((volume + delta).min(volume.max(100)));
+                    }
+                    "mic-down" => audio::set_source_volume(audio::get_source_info().volume - delta),
+                    "toggle-mic-mute" => audio::toggle_source_mute(),
+                    _ => return false,
+                }
+                refresh_audio(&output_c);
+                refresh_source_audio(
+                    &source_slider_c,
+                    &source_mute_c,
+                    &source_updating_c,
+                    &source_label_c,
+                    &output_c.widget,
+                );
+                true
+            },
+        );
+
         let module = Self {
             widget,
-            label,
-            slider,
-            mute_btn,
+            output,
             source_slider,
             source_mute_btn,
             source_label,
@@ -274,8 +367,9 @@ impl Audio {
             card_profiles_list,
             sink_list,
             source_list,
-            updating,
-            event_listener: None,
+            sink_priority: Rc::new(config.sink_priority.clone()),
+            source_priority: Rc::new(config.source_priority.clone()),
+            rule_label,
         };
 
         module.refresh();
@@ -283,12 +377,9 @@ impl Audio {
         module
     }
 
-    pub fn setup_events(&mut self) {
-        let label = self.label.clone();
-        let widget = self.widget.clone();
-        let slider = self.slider.clone();
-        let mute_btn = self.mute_btn.clone();
-        let updating = self.updating.clone();
+    /// Refreshes on each message from the app's audio event listener.
+    pub fn setup_events(&self, receiver: async_channel::Receiver<()>) {
+        let output = self.output.clone();
         let sink_list = self.sink_list.clone();
         let source_list = self.source_list.clone();
         let source_slider = self.source_slider.clone();
@@ -297,52 +388,78 @@ impl Audio {
         let source_label = self.source_label.clone();
         let app_streams_list = self.app_streams_list.clone();
         let card_profiles_list = self.card_profiles_list.clone();
-
-        let (sender, receiver) = async_channel::unbounded::<()>();
+        let sink_priority = self.sink_priority.clone();
+        let source_priority = self.source_priority.clone();
+        let rule_label = self.rule_label.clone();
 
         glib::spawn_future_local(async move {
             while receiver.recv().await.is_ok() {
-                refresh_audio(&label, &widget, &slider, &mute_btn, &updating);
+                refresh_rule_label(&rule_label, &sink_priority, &source_priority);
+                refresh_audio(&output);
                 refresh_source_audio(
                     &source_slider,
                     &source_mute_btn,
                     &source_updating,
                     &source_label,
+                    &output.widget,
                 );
                 rebuild_device_lists(&sink_list, &source_list);
                 rebuild_app_streams(&app_streams_list);
                 rebuild_card_profiles(&card_profiles_list);
             }
         });
-
-        let listener = audio::AudioEventListener::new();
-        listener.start(sender);
-        self.event_listener = Some(listener);
     }
 
     fn refresh(&self) {
-        refresh_audio(
-            &self.label,
-            &self.widget,
-            &self.slider,
-            &self.mute_btn,
-            &self.updating,
-        );
+        refresh_rule_label(&self.rule_label, &self.sink_priority, &self.source_priority);
+        refresh_audio(&self.output);
         refresh_source_audio(
             &self.source_slider,
             &self.source_mute_btn,
             &self.source_updating,
             &self.source_label,
+            &self.widget,
         );
         rebuild_device_lists(&self.sink_list, &self.source_list);
         rebuild_app_streams(&self.app_streams_list);
         rebuild_card_profiles(&self.card_profiles_list);
     }
+}
+
+/// Device rules and remembered stream settings, run once for the whole app
+/// rather than by each bar's audio module, so every audio event is acted
+/// on (and saved) once.
+pub struct AudioProfiles {
+    listener: audio::AudioEventListener,
+}
+
+impl AudioProfiles {
+    pub fn new(config: &AudioConfig) -> Self {
+        let mut rules = DeviceRules::new(config);
+        let mut stream_memory = config.remember_streams.then(StreamMemory::new);
+
+        let (sender, receiver) = async_channel::unbounded::<()>();
+        glib::spawn_future_local(async move {
+            // The listener's first message covers startup
+            while receiver.recv().await.is_ok() {
+                // Events come in bursts; one look once they settle
+                while receiver.try_recv().is_ok() {}
+                // Devices may have come or gone
+                rules.apply();
+                // Streams may have started
+                if let Some(memory) = &mut stream_memory {
+                    memory.apply();
+                }
+            }
+        });
+
+        let listener = audio::AudioEventListener::new();
+        listener.start(sender);
+        Self { listener }
+    }
 
     pub fn stop(&self) {
-        if let Some(listener) = &self.event_listener {
-            listener.stop();
-        }
+        self.listener.stop();
     }
 }
 
@@ -411,41 +528,89 @@ fn create_device_section(
     container
 }
 
-fn refresh_audio(
-    label: &gtk4::Label,
-    widget: &gtk4::Box,
-    slider: &gtk4::Scale,
-    mute_btn: &gtk4::Button,
-    updating: &Rc<Cell<bool>>,
-) {
+/// The bar label and the popover's output controls, all showing the
+/// default sink.
+#[derive(Clone)]
+struct OutputControls {
+    label: gtk4::Label,
+    widget: gtk4::Box,
+    slider: gtk4::Scale,
+    balance: gtk4::Scale,
+    mute_btn: gtk4::Button,
+    updating: Rc<Cell<bool>>,
+    // The most the current sink may be set to
+    cap: Rc<Cell<i32>>,
+    max_volume: i32,
+    limits: Rc<HashMap<String, i32>>,
+}
+
+fn refresh_audio(out: &OutputControls) {
     let info = audio::get_info();
     if !info.available {
-        label.set_text("--");
+        out.label.set_text("--");
         return;
     }
 
-    widget.remove_css_class("muted");
+    // `AudioProfiles` pulls the volume back down; this only stops the
+    // slider going past the limit
+    let limit = audio_profiles::device_limit(&out.limits, &info);
+    out.cap.set(limit.map_or(out.max_volume, |l| l.min(out.max_volume)));
+
+    out.widget.remove_css_class("muted");
     if info.muted {
-        label.set_text("Muted");
-        widget.add_css_class("muted");
+        out.label.set_text("Muted");
+        out.widget.add_css_class("muted");
     } else {
-        label.set_text(&format!("{}%", info.volume));
+        out.label.set_text(&format!("{}%", info.volume));
     }
 
-    updating.set(true);
-    slider.set_value(info.volume as f64);
-    updating.set(false);
+    out.updating.set(true);
+    out.slider.set_show_fill_level(out.cap.get() < out.max_volume);
+    out.slider.set_fill_level(out.cap.get() as f64);
+    out.slider.set_value(info.volume as f64);
+    out.balance.set_visible(info.has_balance());
+    out.balance.set_value(info.balance() * 100.0);
+    out.updating.set(false);
 
     if info.muted {
-        mute_btn.add_css_class("muted");
+        out.mute_btn.add_css_class("muted");
     } else {
-        mute_btn.remove_css_class("muted");
+        out.mute_btn.remove_css_class("muted");
+    }
+
+    let mut tooltip = format!("Volume: {}%", info.volume);
+    if info.balance() != 0.0 {
+        let side = |side| {
+            info.channels
+                .iter()
+                .filter(|(s, _)| *s == side)
+                .map(|(_, v)| *v)
+                .max()
+                .unwrap_or(0)
+        };
+        tooltip.push_str(&format!(
+            " (L {}% / R {}%)",
+            side(audio::ChannelSide::Left),
+            side(audio::ChannelSide::Right)
+        ));
     }
+    if let Some(limit) = limit {
+        tooltip.push_str(&format!("\nLimited to {limit}%"));
+    }
+    tooltip.push_str("\nClick to adjust");
+    out.widget.set_tooltip_text(Some(&tooltip));
+}
 
-    widget.set_tooltip_text(Some(&format!(
-        "Volume: {}%\nClick to adjust",
-        info.volume
-    )));
+fn refresh_rule_label(label: &gtk4::Label, sink_priority: &[String], source_priority: &[String]) {
+    let lines: Vec<String> = [
+        ("Output", audio_profiles::active_sink_rule(sink_priority)),
+        ("Input", audio_profiles::active_source_rule(source_priority)),
+    ]
+    .into_iter()
+    .filter_map(|(kind, rule)| Some(format!("{kind} follows rule \"{}\"", rule?)))
+    .collect();
+    label.set_text(&lines.join("\n"));
+    label.set_visible(!lines.is_empty());
 }
 
 fn refresh_source_audio(
@@ -453,6 +618,7 @@ fn refresh_source_audio(
     mute_btn: &gtk4::Button,
     updating: &Rc<Cell<bool>>,
     label: &gtk4::Label,
+    widget: &gtk4::Box,
 ) {
     let info = audio::get_source_info();
     if !info.available {
@@ -460,6 +626,16 @@ fn refresh_source_audio(
         return;
     }
 
+    // Lets themes show the microphone's state on the bar itself
+    let recording = !audio::list_source_outputs().is_empty();
+    for (class, on) in [("mic-muted", info.muted), ("mic-in-use", recording)] {
+        if on {
+            widget.add_css_class(class);
+        } else {
+            widget.remove_css_class(class);
+        }
+    }
+
     updating.set(true);
     slider.set_value(info.volume as f64);
     updating.set(false);
@@ -558,25 +734,59 @@ fn rebuild_app_streams(app_streams_list: &gtk4::Box) {
     let sinks = audio::list_sinks();
     let has_multiple_sinks = sinks.len() > 1;
 
+    // One row per application, in order of its first stream
+    let mut groups: Vec<Vec<&audio::SinkInput>> = Vec::new();
     for input in &sink_inputs {
-        let row = build_app_stream_row(input, &sinks, has_multiple_sinks, app_streams_list);
+        match groups.iter_mut().find(|g| g[0].app_id == input.app_id) {
+            Some(group) => group.push(input),
+            None => groups.push(vec![input]),
+        }
+    }
+
+    for group in &groups {
+        let row = build_app_stream_row(group, &sinks, has_multiple_sinks, app_streams_list);
         app_streams_list.append(&row);
     }
 }
 
+/// The app's own icon if the theme has it, else one found from its binary.
+fn stream_icon(input: &audio::SinkInput) -> gtk4::Image {
+    let image = gtk4::Image::new();
+    image.add_css_class("audio-app-icon");
+    let has_own = !input.icon_name.is_empty()
+        && gtk4::gdk::Display::default()
+            .is_some_and(|d| gtk4::IconTheme::for_display(&d).has_icon(&input.icon_name));
+    if has_own {
+        image.set_icon_name(Some(&input.icon_name));
+    } else {
+        icons::set_icon(&image, &icons::app_icon(&input.app_id));
+    }
+    image
+}
+
+/// A row controlling all of one application's streams together.
 fn build_app_stream_row(
-    input: &audio::SinkInput,
+    group: &[&audio::SinkInput],
     sinks: &[audio::AudioDevice],
     has_multiple_sinks: bool,
     app_streams_list: &gtk4::Box,
 ) -> gtk4::Box {
+    let first = group[0];
+    let indices: Vec<u32> = group.iter().map(|i| i.index).collect();
+
     let row = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
     row.add_css_class("audio-app-row");
 
-    // Top line: app name + mute toggle
+    // Top line: icon + app name + mute toggle
     let top_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+    top_box.append(&stream_icon(first));
 
-    let name_label = gtk4::Label::new(Some(&input.name));
+    let name = if group.len() > 1 {
+        format!("{} ({})", first.name, group.len())
+    } else {
+        first.name.clone()
+    };
+    let name_label = gtk4::Label::new(Some(&name));
     name_label.add_css_class("audio-app-name");
     name_label.set_halign(gtk4::Align::Start);
     name_label.set_hexpand(true);
@@ -584,21 +794,23 @@ fn build_app_stream_row(
     name_label.set_max_width_chars(20);
     top_box.append(&name_label);
 
+    let muted = group.iter().all(|i| i.muted);
     let mute_toggle = gtk4::Button::new();
     mute_toggle.add_css_class("audio-app-mute");
-    if input.muted {
+    if muted {
         mute_toggle.set_label("\u{16C1}"); // Isa rune for muted
         mute_toggle.add_css_class("muted");
     } else {
         mute_toggle.set_label("\u{16A8}"); // Ansuz rune for unmuted
     }
 
-    let idx = input.index;
-    let muted = input.muted;
+    let idxs = indices.clone();
     let streams_ref = app_streams_list.clone();
     mute_toggle.connect_clicked(move |_| {
         let toggle_val = if muted { "0" } else { "1" };
-        audio::set_sink_input_mute(idx, toggle_val);
+        for &idx in &idxs {
+            audio::set_sink_input_mute(idx, toggle_val);
+        }
         rebuild_app_streams(&streams_ref);
     });
     top_box.append(&mute_toggle);
@@ -606,19 +818,26 @@ fn build_app_stream_row(
     row.append(&top_box);
 
     // Volume slider
-    let vol_slider =
-        gtk4::Scale::with_range(gtk4::Orientation::Horizontal, 0.0, 150.0, 1.0);
+    let vol_slider = gtk4::Scale::with_range(
+        gtk4::Orientation::Horizontal,
+        0.0,
+        audio::MAX_VOLUME as f64,
+        1.0,
+    );
     vol_slider.add_css_class("audio-slider");
     vol_slider.add_css_class("audio-app-slider");
     vol_slider.set_draw_value(true);
     vol_slider.set_value_pos(gtk4::PositionType::Right);
     vol_slider.set_hexpand(true);
-    vol_slider.set_value(input.volume as f64);
+    let volume = group.iter().map(|i| i.volume).max().unwrap_or(0);
+    vol_slider.set_value(volume as f64);
 
-    let idx = input.index;
+    let idxs = indices.clone();
     vol_slider.connect_value_changed(move |scale| {
         let vol = scale.value() as i32;
-        audio::set_sink_input_volume(idx, vol);
+        for &idx in &idxs {
+            audio::set_sink_input_volume(idx, vol);
+        }
     });
     row.append(&vol_slider);
 
@@ -632,17 +851,18 @@ fn build_app_stream_row(
         // Find current sink index
         let current_pos = sinks
             .iter()
-            .position(|s| s.name == input.sink_name)
+            .position(|s| s.name == first.sink_name)
             .unwrap_or(0);
         dropdown.set_selected(current_pos as u32);
 
         let sink_names: Vec<String> = sinks.iter().map(|s| s.name.clone()).collect();
-        let idx = input.index;
         let streams_ref = app_streams_list.clone();
         dropdown.connect_selected_notify(move |dd| {
             let selected = dd.selected() as usize;
             if selected < sink_names.len() {
-                audio::move_sink_input(idx, &sink_names[selected]);
+                for &idx in &indices {
+                    audio::move_sink_input(idx, &sink_names[selected]);
+                }
                 rebuild_app_streams(&streams_ref);
             }
         });
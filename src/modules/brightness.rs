@@ -1,3 +1,5 @@
+use crate::config::{BrightnessConfig, ModuleActions};
+use crate::modules::actions;
 use crate::system::brightness;
 use gtk4::glib;
 use gtk4::prelude::*;
@@ -15,7 +17,11 @@ pub struct Brightness {
 }
 
 impl Brightness {
-    pub fn new(interval_secs: u32) -> Self {
+    pub fn new(
+        interval_secs: u32,
+        config: &BrightnessConfig,
+        module_actions: Option<&ModuleActions>,
+    ) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
         widget.add_css_class("brightness");
 
@@ -93,6 +99,33 @@ impl Brightness {
 
         widget.append(&menu_button);
 
+        let defaults = ModuleActions {
+            on_scroll_up: "brightness-up".to_string(),
+            on_scroll_down: "brightness-down".to_string(),
+            ..Default::default()
+        };
+        let step = config.scroll_step;
+        let label_c = label.clone();
+        let slider_c = slider.clone();
+        let widget_c = widget.clone();
+        let device_c = device.clone();
+        let updating_c = updating.clone();
+        actions::attach(
+            &widget,
+            &actions::with_defaults(module_actions, defaults),
+            move |action, steps| {
+                let delta = step * steps as i32;
+                let current = brightness::get_info(&device_c).map_or(0, |info| info.percent);
+                match action {
+                    "brightness-up" => brightness::set_brightness(&device_c, current + delta),
+                    "brightness-down" => brightness::set_brightness(&device_c, current - delta),
+                    _ => return false,
+                }
+                refresh_brightness(&label_c, &slider_c, &widget_c, &device_c, &updating_c);
+                true
+            },
+        );
+
         if !available {
             widget.set_visible(false);
         }
@@ -0,0 +1,100 @@
+use crate::system::app_finder;
+use crate::system::desktop_index;
+use gtk4::gdk;
+use gtk4::glib;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub enum IconSource {
+    Name(String),
+    File(PathBuf),
+}
+
+impl IconSource {
+    /// An absolute path is a file, anything else a themed icon name.
+    pub fn from_value(value: &str) -> Self {
+        if value.starts_with('/') {
+            Self::File(PathBuf::from(value))
+        } else {
+            Self::Name(value.to_string())
+        }
+    }
+}
+
+thread_local! {
+    // Shared by every module, cleared when the desktop index changes
+    static ICON_CACHE: RefCell<HashMap<String, IconSource>> = RefCell::new(HashMap::new());
+    static SUBSCRIBERS: RefCell<Vec<async_channel::Sender<()>>> = const { RefCell::new(Vec::new()) };
+    static WATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Notified after the desktop index updates and the icon cache has been
+/// cleared, so icons looked up from the receiver are fresh.
+pub fn subscribe() -> async_channel::Receiver<()> {
+    watch_desktop_index();
+    let (sender, receiver) = async_channel::unbounded();
+    SUBSCRIBERS.with(|subscribers| subscribers.borrow_mut().push(sender));
+    receiver
+}
+
+fn watch_desktop_index() {
+    if WATCHING.with(|watching| watching.replace(true)) {
+        return;
+    }
+    let receiver = desktop_index::subscribe();
+    glib::spawn_future_local(async move {
+        while receiver.recv().await.is_ok() {
+            ICON_CACHE.with(|cache| cache.borrow_mut().clear());
+            SUBSCRIBERS.with(|subscribers| {
+                subscribers
+                    .borrow_mut()
+                    .retain(|sender| sender.try_send(()).is_ok());
+            });
+        }
+    });
+}
+
+pub fn set_icon(image: &gtk4::Image, source: &IconSource) {
+    match source {
+        IconSource::Name(name) => image.set_icon_name(Some(name)),
+        IconSource::File(path) => image.set_from_file(Some(path)),
+    }
+}
+
+/// The icon for an app class or binary name, resolved once per desktop
+/// index update.
+pub fn app_icon(class: &str) -> IconSource {
+    watch_desktop_index();
+    let class = class.to_lowercase();
+    ICON_CACHE.with(|cache| {
+        cache
+            .borrow_mut()
+            .entry(class.clone())
+            .or_insert_with(|| resolve_app_icon(&class))
+            .clone()
+    })
+}
+
+fn resolve_app_icon(class: &str) -> IconSource {
+    let display = gdk::Display::default().unwrap();
+    let icon_theme = gtk4::IconTheme::for_display(&display);
+
+    let entry_icon = app_finder::find_entry_for_class(&desktop_index::entries(), class)
+        .and_then(|e| e.icon.clone());
+
+    if let Some(icon) = entry_icon {
+        match IconSource::from_value(&icon) {
+            IconSource::File(path) if path.exists() => return IconSource::File(path),
+            IconSource::Name(name) if icon_theme.has_icon(&name) => return IconSource::Name(name),
+            _ => {}
+        }
+    }
+
+    if icon_theme.has_icon(class) {
+        IconSource::Name(class.to_string())
+    } else {
+        IconSource::Name("application-x-executable".to_string())
+    }
+}
@@ -1,10 +1,86 @@
-use crate::system::media::{self, PlaybackStatus};
+use crate::config::{MediaConfig, VisualizerConfig};
+use crate::modules::visualizer::Spectrum;
+use crate::system::media::{self, MediaCommand, MediaEvent, PlaybackStatus, Player, Players};
 use gtk4::glib;
 use gtk4::pango;
 use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The app's one MPRIS client. Every subscriber gets every event, starting
+/// with each player as it last reported, and commands from any of them go
+/// out over the same connection.
+pub struct MediaClient {
+    commands: async_channel::Sender<MediaCommand>,
+    subscribers: Rc<RefCell<Vec<async_channel::Sender<MediaEvent>>>>,
+    // Every player on the bus, for subscribers that join late
+    players: Rc<RefCell<HashMap<String, Player>>>,
+}
+
+impl MediaClient {
+    pub fn new() -> Self {
+        let (commands, command_receiver) = async_channel::unbounded::<MediaCommand>();
+        let (sender, receiver) = async_channel::unbounded::<MediaEvent>();
+        media::start(command_receiver, sender);
+
+        let subscribers: Rc<RefCell<Vec<async_channel::Sender<MediaEvent>>>> = Rc::default();
+        let players: Rc<RefCell<HashMap<String, Player>>> = Rc::default();
+        let subscribers_c = subscribers.clone();
+        let players_c = players.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                match &event {
+                    MediaEvent::Updated(player) => {
+                        players_c
+                            .borrow_mut()
+                            .insert(player.bus_name.clone(), player.clone());
+                    }
+                    MediaEvent::Removed(bus_name) => {
+                        players_c.borrow_mut().remove(bus_name);
+                    }
+                }
+                subscribers_c
+                    .borrow_mut()
+                    .retain(|subscriber| subscriber.try_send(event.clone()).is_ok());
+            }
+        });
+
+        Self {
+            commands,
+            subscribers,
+            players,
+        }
+    }
+
+    pub fn subscribe(&self) -> async_channel::Receiver<MediaEvent> {
+        let (sender, receiver) = async_channel::unbounded();
+        for player in self.players.borrow().values() {
+            let _ = sender.try_send(MediaEvent::Updated(player.clone()));
+        }
+        self.subscribers.borrow_mut().push(sender);
+        receiver
+    }
+
+    pub fn commands(&self) -> async_channel::Sender<MediaCommand> {
+        self.commands.clone()
+    }
+
+    pub fn stop(&self) {
+        // Ends the D-Bus thread
+        self.commands.close();
+    }
+}
 
 pub struct Media {
     pub widget: gtk4::Box,
+    view: MediaView,
+    source_id: Option<glib::SourceId>,
+}
+
+#[derive(Clone)]
+struct MediaView {
+    widget: gtk4::Box,
     status_icon: gtk4::Image,
     title_label: gtk4::Label,
     track_title: gtk4::Label,
@@ -12,12 +88,25 @@ pub struct Media {
     play_pause_icon: gtk4::Image,
     progress_bar: gtk4::ProgressBar,
     position_label: gtk4::Label,
-    available: bool,
-    source_id: Option<glib::SourceId>,
+    spectrum: Option<Rc<Spectrum>>,
+    // This module's areas of the shared spectrum
+    spectrum_areas: Vec<gtk4::DrawingArea>,
+    player_list: gtk4::Box,
+    player_rows: Rc<RefCell<HashMap<String, PlayerRow>>>,
+    prev_btn: gtk4::Button,
+    next_btn: gtk4::Button,
+    players: Rc<RefCell<Players>>,
+    commands: async_channel::Sender<MediaCommand>,
 }
 
 impl Media {
-    pub fn new(interval_secs: u32) -> Self {
+    pub fn new(
+        interval_secs: u32,
+        config: &MediaConfig,
+        visualizer: &VisualizerConfig,
+        client: &MediaClient,
+        spectrum: Option<Rc<Spectrum>>,
+    ) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
         widget.add_css_class("media");
 
@@ -42,6 +131,14 @@ impl Media {
         title_label.set_ellipsize(pango::EllipsizeMode::End);
         btn_content.append(&title_label);
 
+        let mut spectrum_areas = Vec::new();
+        if let Some(spectrum) = spectrum.as_ref().filter(|_| visualizer.bar) {
+            let bars = visualizer.bars as i32;
+            let area = spectrum.area("media-visualizer", bars * 4, 14);
+            btn_content.append(&area);
+            spectrum_areas.push(area);
+        }
+
         menu_button.set_child(Some(&btn_content));
 
         // Popover
@@ -66,6 +163,12 @@ impl Media {
         header.append(&header_label);
         content.append(&header);
 
+        // Switcher, shown once there's more than one player
+        let player_list = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+        player_list.add_css_class("media-players");
+        player_list.set_visible(false);
+        content.append(&player_list);
+
         // Track info
         let track_info = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
         track_info.add_css_class("media-track-info");
@@ -86,6 +189,12 @@ impl Media {
 
         content.append(&track_info);
 
+        if let Some(spectrum) = spectrum.as_ref().filter(|_| visualizer.popover) {
+            let area = spectrum.area("media-popover-visualizer", 260, 48);
+            content.append(&area);
+            spectrum_areas.push(area);
+        }
+
         // Progress
         let progress_box = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
         let progress_bar = gtk4::ProgressBar::new();
@@ -99,12 +208,26 @@ impl Media {
         content.append(&progress_box);
 
         // Controls
+        let commands = client.commands();
+        let players = Rc::new(RefCell::new(Players::new(config)));
+        // Controls act on the player followed
+        let send = |command: fn(String) -> MediaCommand| {
+            let commands = commands.clone();
+            let players = players.clone();
+            move || {
+                let focused = players.borrow().focused().map(|p| p.bus_name.clone());
+                if let Some(bus_name) = focused {
+                    let _ = commands.send_blocking(command(bus_name));
+                }
+            }
+        };
+
         let controls = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
         controls.set_halign(gtk4::Align::Center);
         controls.add_css_class("media-controls");
 
         // Previous - ᚱ Raidho
-        let prev_btn = create_control_button("\u{16B1}", "Previous", || media::previous());
+        let prev_btn = create_control_button("\u{16B1}", "Previous", send(MediaCommand::Previous));
         prev_btn.add_css_class("media-prev");
         controls.append(&prev_btn);
 
@@ -116,11 +239,12 @@ impl Media {
         play_pause_icon.add_css_class("media-control-icon");
         pp_box.append(&play_pause_icon);
         play_pause_btn.set_child(Some(&pp_box));
-        play_pause_btn.connect_clicked(|_| media::play_pause());
+        let play_pause = send(MediaCommand::PlayPause);
+        play_pause_btn.connect_clicked(move |_| play_pause());
         controls.append(&play_pause_btn);
 
         // Next - ᚠ Fehu
-        let next_btn = create_control_button("\u{16A0}", "Next", || media::next());
+        let next_btn = create_control_button("\u{16A0}", "Next", send(MediaCommand::Next));
         next_btn.add_css_class("media-next");
         controls.append(&next_btn);
 
@@ -132,8 +256,8 @@ impl Media {
         widget.append(&menu_button);
         widget.set_visible(false);
 
-        let mut module = Self {
-            widget,
+        let view = MediaView {
+            widget: widget.clone(),
             status_icon,
             title_label,
             track_title,
@@ -141,54 +265,54 @@ impl Media {
             play_pause_icon,
             progress_bar,
             position_label,
-            available: false,
+            spectrum,
+            spectrum_areas,
+            player_list,
+            player_rows: Rc::default(),
+            prev_btn,
+            next_btn,
+            players,
+            commands,
+        };
+
+        let mut module = Self {
+            widget,
+            view,
             source_id: None,
         };
 
-        module.refresh();
+        module.start_dbus_listener(client.subscribe());
         module.start_updates(interval_secs);
         module
     }
 
+    fn start_dbus_listener(&self, receiver: async_channel::Receiver<MediaEvent>) {
+        let view = self.view.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                view.players.borrow_mut().apply(event);
+                refresh_media(&view);
+            }
+        });
+    }
+
+    /// Moves the position on while something plays; everything else
+    /// changes with the player's own signals.
     fn start_updates(&mut self, interval_secs: u32) {
-        let widget = self.widget.clone();
-        let status_icon = self.status_icon.clone();
-        let title_label = self.title_label.clone();
-        let track_title = self.track_title.clone();
-        let track_artist = self.track_artist.clone();
-        let play_pause_icon = self.play_pause_icon.clone();
-        let progress_bar = self.progress_bar.clone();
-        let position_label = self.position_label.clone();
-
-        self.source_id = Some(glib::timeout_add_seconds_local(interval_secs, move || {
-            refresh_media(
-                &widget,
-                &status_icon,
-                &title_label,
-                &track_title,
-                &track_artist,
-                &play_pause_icon,
-                &progress_bar,
-                &position_label,
-            );
+        let view = self.view.clone();
+        let interval = interval_secs.max(1);
+
+        self.source_id = Some(glib::timeout_add_seconds_local(interval, move || {
+            let players = view.players.borrow();
+            if let Some(player) = players.focused() {
+                if player.status == PlaybackStatus::Playing {
+                    refresh_progress(&view, player);
+                }
+            }
             glib::ControlFlow::Continue
         }));
     }
 
-    fn refresh(&mut self) {
-        let visible = refresh_media(
-            &self.widget,
-            &self.status_icon,
-            &self.title_label,
-            &self.track_title,
-            &self.track_artist,
-            &self.play_pause_icon,
-            &self.progress_bar,
-            &self.position_label,
-        );
-        self.available = visible;
-    }
-
     pub fn stop(&mut self) {
         if let Some(id) = self.source_id.take() {
             id.remove();
@@ -212,28 +336,32 @@ fn create_control_button(
     btn
 }
 
-#[allow(clippy::too_many_arguments)]
-fn refresh_media(
-    widget: &gtk4::Box,
-    status_icon: &gtk4::Image,
-    title_label: &gtk4::Label,
-    track_title: &gtk4::Label,
-    track_artist: &gtk4::Label,
-    play_pause_icon: &gtk4::Image,
-    progress_bar: &gtk4::ProgressBar,
-    position_label: &gtk4::Label,
-) -> bool {
-    let info = media::get_media_info();
-
-    let should_show = info.available
-        && (info.status == PlaybackStatus::Playing || info.status == PlaybackStatus::Paused)
-        && !info.title.is_empty();
-
-    widget.set_visible(should_show);
-    if !should_show {
-        return false;
+/// Shows the player followed, or hides the module when there's none.
+fn refresh_media(view: &MediaView) {
+    let players = view.players.borrow();
+    let player = players.focused();
+    refresh_player_list(view, &players.list(), player);
+    if let Some(spectrum) = &view.spectrum {
+        let playing = player.is_some_and(|p| p.status == PlaybackStatus::Playing);
+        spectrum.set_active(&view.spectrum_areas, playing);
     }
 
+    view.widget.set_visible(player.is_some());
+    let Some(info) = player else {
+        return;
+    };
+    let MediaView {
+        widget,
+        status_icon,
+        title_label,
+        track_title,
+        track_artist,
+        play_pause_icon,
+        prev_btn,
+        next_btn,
+        ..
+    } = view;
+
     // Status icon
     let icon_name = match info.status {
         PlaybackStatus::Playing => "media-playback-start-symbolic",
@@ -275,26 +403,151 @@ fn refresh_media(
         "media-playback-start-symbolic"
     }));
 
-    // Progress
+    prev_btn.set_sensitive(info.can_go_previous);
+    next_btn.set_sensitive(info.can_go_next);
+
+    refresh_progress(view, info);
+
+    // Tooltip
+    let mut tooltip = format!("{}\n{}", info.title, info.artist);
+    tooltip += &format!("\nPlayer: {}", info.display_name());
+    widget.set_tooltip_text(Some(&tooltip));
+}
+
+fn refresh_progress(view: &MediaView, info: &Player) {
+    let MediaView {
+        progress_bar,
+        position_label,
+        ..
+    } = view;
+    let position = info.position();
     if info.length > 0 {
-        let progress = info.position as f64 / info.length as f64;
+        let progress = position as f64 / info.length as f64;
         progress_bar.set_fraction(progress);
         position_label.set_text(&format!(
             "{} / {}",
-            media::format_duration(info.position),
+            media::format_duration(position),
             media::format_duration(info.length)
         ));
     } else {
         progress_bar.set_fraction(0.0);
         position_label.set_text("0:00 / 0:00");
     }
+}
 
-    // Tooltip
-    let mut tooltip = format!("{}\n{}", info.title, info.artist);
-    if !info.player.is_empty() {
-        tooltip += &format!("\nPlayer: {}", info.player);
+/// A switcher row, kept across updates so a click never lands on a row
+/// being rebuilt.
+struct PlayerRow {
+    row: gtk4::Box,
+    select: gtk4::Button,
+    name: gtk4::Label,
+    track: gtk4::Label,
+    play_pause: gtk4::Button,
+}
+
+/// One row per player with a track, the one followed marked active; hidden
+/// while there's only one to choose from. Rows are updated in place, keyed
+/// by bus name.
+fn refresh_player_list(view: &MediaView, players: &[&Player], focused: Option<&Player>) {
+    view.player_list.set_visible(players.len() > 1);
+
+    let mut rows = view.player_rows.borrow_mut();
+    rows.retain(|bus_name, entry| {
+        let keep = players.iter().any(|p| &p.bus_name == bus_name);
+        if !keep {
+            view.player_list.remove(&entry.row);
+        }
+        keep
+    });
+
+    // Rows follow the list's order
+    let mut previous: Option<gtk4::Widget> = None;
+    for player in players {
+        let entry = rows
+            .entry(player.bus_name.clone())
+            .or_insert_with(|| build_player_row(view, &player.bus_name));
+        if entry.row.parent().is_none() {
+            view.player_list
+                .insert_child_after(&entry.row, previous.as_ref());
+        } else {
+            view.player_list
+                .reorder_child_after(&entry.row, previous.as_ref());
+        }
+        let is_focused = focused.is_some_and(|f| f.bus_name == player.bus_name);
+        update_player_row(entry, player, is_focused);
+        previous = Some(entry.row.clone().upcast());
     }
-    widget.set_tooltip_text(Some(&tooltip));
+}
+
+fn build_player_row(view: &MediaView, bus_name: &str) -> PlayerRow {
+    let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    row.add_css_class("media-player-row");
+
+    let select = gtk4::Button::new();
+    select.add_css_class("media-player-select");
+    select.set_has_frame(false);
+    select.set_hexpand(true);
+
+    let label_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    let name = gtk4::Label::new(None);
+    name.add_css_class("media-player-name");
+    label_box.append(&name);
+
+    let track = gtk4::Label::new(None);
+    track.add_css_class("media-player-track");
+    track.set_halign(gtk4::Align::Start);
+    track.set_hexpand(true);
+    track.set_max_width_chars(20);
+    track.set_ellipsize(pango::EllipsizeMode::End);
+    label_box.append(&track);
+    select.set_child(Some(&label_box));
+
+    let bus_name_c = bus_name.to_string();
+    let view_c = view.clone();
+    select.connect_clicked(move |_| {
+        view_c.players.borrow_mut().focus(&bus_name_c);
+        refresh_media(&view_c);
+    });
+    row.append(&select);
+
+    let play_pause = gtk4::Button::new();
+    play_pause.add_css_class("media-player-play-pause");
+    play_pause.set_has_frame(false);
+    let bus_name_c = bus_name.to_string();
+    let commands = view.commands.clone();
+    play_pause.connect_clicked(move |_| {
+        let _ = commands.send_blocking(MediaCommand::PlayPause(bus_name_c.clone()));
+    });
+    row.append(&play_pause);
+
+    PlayerRow {
+        row,
+        select,
+        name,
+        track,
+        play_pause,
+    }
+}
 
-    true
+fn update_player_row(entry: &PlayerRow, player: &Player, focused: bool) {
+    if focused {
+        entry.row.add_css_class("active");
+    } else {
+        entry.row.remove_css_class("active");
+    }
+    entry
+        .select
+        .set_tooltip_text(Some(&format!("Show {}", player.display_name())));
+    entry.name.set_text(player.display_name());
+    entry.track.set_text(&player.title);
+
+    let playing = player.status == PlaybackStatus::Playing;
+    entry.play_pause.set_icon_name(if playing {
+        "media-playback-pause-symbolic"
+    } else {
+        "media-playback-start-symbolic"
+    });
+    entry
+        .play_pause
+        .set_tooltip_text(Some(if playing { "Pause" } else { "Play" }));
 }
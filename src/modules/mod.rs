@@ -1,3 +1,4 @@
+pub mod actions;
 pub mod app_finder;
 pub mod app_tracker;
 pub mod audio;
@@ -5,9 +6,13 @@ pub mod battery;
 pub mod brightness;
 pub mod clock;
 pub mod connectivity;
+pub mod icons;
 pub mod media;
+pub mod osd;
 pub mod power;
 pub mod power_menu;
+pub mod privacy;
 pub mod settings;
 pub mod systray;
+pub mod visualizer;
 pub mod workspaces;
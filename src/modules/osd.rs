@@ -0,0 +1,295 @@
+use crate::config::{MediaConfig, OsdConfig};
+use crate::modules::media::MediaClient;
+use crate::system::media::{MediaEvent, PlaybackStatus, Players};
+use crate::system::{audio, brightness};
+use gtk4::glib;
+use gtk4::pango;
+use gtk4::prelude::*;
+use gtk4_layer_shell::{Edge, LayerShell};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A transient overlay showing volume, microphone, brightness and track
+/// changes, whatever made them.
+pub struct Osd {
+    audio_listener: Option<audio::AudioEventListener>,
+}
+
+impl Osd {
+    pub fn new(
+        app: &gtk4::Application,
+        config: &OsdConfig,
+        media_config: &MediaConfig,
+        media_client: Option<&MediaClient>,
+    ) -> Self {
+        let view = Rc::new(OsdView::new(app, config));
+
+        let mut audio_listener = None;
+        if config.volume || config.microphone {
+            audio_listener = Some(watch_audio(&view, config.volume, config.microphone));
+        }
+        if config.brightness {
+            watch_brightness(&view);
+        }
+        if let Some(client) = media_client.filter(|_| config.media) {
+            watch_media(&view, media_config, client);
+        }
+
+        Self { audio_listener }
+    }
+
+    pub fn stop(&self) {
+        if let Some(listener) = &self.audio_listener {
+            listener.stop();
+        }
+    }
+}
+
+struct OsdView {
+    window: gtk4::Window,
+    content: gtk4::Box,
+    rune: gtk4::Label,
+    title: gtk4::Label,
+    subtitle: gtk4::Label,
+    level_row: gtk4::Box,
+    level: gtk4::LevelBar,
+    value: gtk4::Label,
+    timeout_ms: u32,
+    hide_source: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl OsdView {
+    fn new(app: &gtk4::Application, config: &OsdConfig) -> Self {
+        let window = gtk4::Window::new();
+        window.set_title(Some("CrowBar OSD"));
+        window.add_css_class("osd-window");
+        app.add_window(&window);
+
+        if gtk4_layer_shell::is_supported() {
+            window.init_layer_shell();
+            window.set_layer(gtk4_layer_shell::Layer::Overlay);
+            window.set_namespace(Some("crowbar-osd"));
+            window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
+            let edge = match config.position.as_str() {
+                "top" => Some(Edge::Top),
+                "bottom" => Some(Edge::Bottom),
+                "left" => Some(Edge::Left),
+                "right" => Some(Edge::Right),
+                _ => None,
+            };
+            if let Some(edge) = edge {
+                window.set_anchor(edge, true);
+                window.set_margin(edge, config.margin);
+            }
+        }
+
+        // Never take clicks from what's underneath
+        window.connect_realize(|window| {
+            if let Some(surface) = window.surface() {
+                surface.set_input_region(&gtk4::cairo::Region::create());
+            }
+        });
+
+        let content = gtk4::Box::new(gtk4::Orientation::Horizontal, 14);
+        content.add_css_class("osd");
+
+        let rune = gtk4::Label::new(None);
+        rune.add_css_class("osd-rune");
+        content.append(&rune);
+
+        let text = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+        text.set_valign(gtk4::Align::Center);
+        text.set_hexpand(true);
+
+        let title = gtk4::Label::new(None);
+        title.add_css_class("osd-title");
+        title.set_halign(gtk4::Align::Start);
+        title.set_ellipsize(pango::EllipsizeMode::End);
+        title.set_max_width_chars(32);
+        text.append(&title);
+
+        let subtitle = gtk4::Label::new(None);
+        subtitle.add_css_class("osd-subtitle");
+        subtitle.set_halign(gtk4::Align::Start);
+        subtitle.set_ellipsize(pango::EllipsizeMode::End);
+        subtitle.set_max_width_chars(32);
+        text.append(&subtitle);
+
+        let level_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 10);
+        let level = gtk4::LevelBar::for_interval(0.0, 100.0);
+        level.add_css_class("osd-level");
+        level.set_hexpand(true);
+        level.set_valign(gtk4::Align::Center);
+        // Plain bar: no low/high/full colour offsets
+        for offset in ["low", "high", "full"] {
+            level.remove_offset_value(Some(offset));
+        }
+        level_row.append(&level);
+
+        let value = gtk4::Label::new(None);
+        value.add_css_class("osd-value");
+        level_row.append(&value);
+        text.append(&level_row);
+
+        content.append(&text);
+        window.set_child(Some(&content));
+
+        Self {
+            window,
+            content,
+            rune,
+            title,
+            subtitle,
+            level_row,
+            level,
+            value,
+            timeout_ms: config.timeout_ms,
+            hide_source: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn show_level(&self, kind: &str, rune: &str, title: &str, percent: i32, muted: bool) {
+        self.rune.set_text(rune);
+        self.title.set_text(title);
+        self.subtitle.set_visible(false);
+        self.level_row.set_visible(true);
+        // Over-amplified volumes stretch the bar rather than overflow it
+        self.level.set_max_value(percent.max(100) as f64);
+        self.level.set_value(percent.max(0) as f64);
+        self.value.set_text(&if muted {
+            "Muted".to_string()
+        } else {
+            format!("{percent}%")
+        });
+        self.present(kind, muted);
+    }
+
+    fn show_track(&self, title: &str, artist: &str) {
+        self.rune.set_text("\u{16DA}"); // Laguz
+        self.title.set_text(title);
+        self.subtitle.set_text(artist);
+        self.subtitle.set_visible(!artist.is_empty());
+        self.level_row.set_visible(false);
+        self.present("media", false);
+    }
+
+    fn present(&self, kind: &str, muted: bool) {
+        for class in ["volume", "microphone", "brightness", "media"] {
+            self.content.remove_css_class(&format!("osd-{class}"));
+        }
+        self.content.add_css_class(&format!("osd-{kind}"));
+        if muted {
+            self.content.add_css_class("muted");
+        } else {
+            self.content.remove_css_class("muted");
+        }
+        self.window.set_visible(true);
+
+        // Restart the countdown on every change
+        if let Some(id) = self.hide_source.borrow_mut().take() {
+            id.remove();
+        }
+        let window = self.window.clone();
+        let hide_source = self.hide_source.clone();
+        let id = glib::timeout_add_local_once(
+            Duration::from_millis(self.timeout_ms as u64),
+            move || {
+                // Spent; removing it again would panic
+                hide_source.borrow_mut().take();
+                window.set_visible(false);
+            },
+        );
+        *self.hide_source.borrow_mut() = Some(id);
+    }
+}
+
+fn watch_audio(view: &Rc<OsdView>, volume: bool, microphone: bool) -> audio::AudioEventListener {
+    let (sender, receiver) = async_channel::unbounded::<()>();
+    let listener = audio::AudioEventListener::new();
+    listener.start(sender);
+
+    let view = view.clone();
+    glib::spawn_future_local(async move {
+        // Unknown until the first event, which only sets the baseline
+        let mut last_sink = None;
+        let mut last_source = None;
+        while receiver.recv().await.is_ok() {
+            // Events come in bursts; look at the state they leave behind
+            while receiver.try_recv().is_ok() {}
+
+            if volume {
+                let info = audio::get_info();
+                let state = info.available.then_some((info.volume, info.muted));
+                if let (Some(previous), Some((percent, muted))) = (last_sink, state) {
+                    if previous != (percent, muted) {
+                        let rune = if muted { "\u{16C1}" } else { "\u{16A8}" }; // Isa / Ansuz
+                        view.show_level("volume", rune, "Volume", percent, muted);
+                    }
+                }
+                last_sink = state;
+            }
+
+            if microphone {
+                let info = audio::get_source_info();
+                let state = info.available.then_some((info.volume, info.muted));
+                if let (Some(previous), Some((percent, muted))) = (last_source, state) {
+                    if previous != (percent, muted) {
+                        let rune = if muted { "\u{16C1}" } else { "\u{16D7}" }; // Isa / Mannaz
+                        view.show_level("microphone", rune, "Microphone", percent, muted);
+                    }
+                }
+                last_source = state;
+            }
+        }
+    });
+
+    listener
+}
+
+fn watch_brightness(view: &Rc<OsdView>) {
+    let Some(device) = brightness::find_backlights().into_iter().next() else {
+        return;
+    };
+    let (sender, receiver) = async_channel::unbounded::<i32>();
+    brightness::watch(&device, sender);
+
+    let view = view.clone();
+    glib::spawn_future_local(async move {
+        while let Ok(percent) = receiver.recv().await {
+            // ᛊ Sowilo - Sun
+            view.show_level("brightness", "\u{16CA}", "Brightness", percent, false);
+        }
+    });
+}
+
+fn watch_media(view: &Rc<OsdView>, config: &MediaConfig, client: &MediaClient) {
+    let receiver = client.subscribe();
+
+    // Only for its ignore list
+    let players = Players::new(config);
+    let view = view.clone();
+    glib::spawn_future_local(async move {
+        // Last track seen per player; a player's first report isn't news
+        let mut tracks: HashMap<String, (String, String)> = HashMap::new();
+        while let Ok(event) = receiver.recv().await {
+            match event {
+                MediaEvent::Updated(player) if players.is_ignored(&player) => {}
+                MediaEvent::Updated(player) => {
+                    let track = (player.title.clone(), player.artist.clone());
+                    let last = tracks.insert(player.bus_name.clone(), track.clone());
+                    if last.is_some_and(|last| last != track)
+                        && player.status == PlaybackStatus::Playing
+                        && !track.0.is_empty()
+                    {
+                        view.show_track(&track.0, &track.1);
+                    }
+                }
+                MediaEvent::Removed(bus_name) => {
+                    tracks.remove(&bus_name);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,185 @@
+use crate::config::{ModuleActions, PrivacyConfig};
+use crate::modules::actions;
+use crate::system::{audio, privacy};
+use gtk4::glib;
+use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Shows when a microphone or camera is in use and by whom.
+pub struct Privacy {
+    pub widget: gtk4::Box,
+    audio_listener: Option<audio::AudioEventListener>,
+    camera_running: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct Users {
+    microphone: Vec<String>,
+    camera: Vec<String>,
+    muted: bool,
+}
+
+impl Privacy {
+    pub fn new(
+        interval_secs: u32,
+        config: &PrivacyConfig,
+        module_actions: Option<&ModuleActions>,
+    ) -> Self {
+        let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        widget.add_css_class("privacy");
+
+        // ᛗ Mannaz - Voice of mankind
+        let mic = gtk4::Label::new(Some("\u{16D7}"));
+        mic.add_css_class("module-rune");
+        mic.add_css_class("privacy-mic");
+        mic.set_visible(config.microphone);
+        widget.append(&mic);
+
+        // ᛈ Perthro - The unseen, seen
+        let camera = gtk4::Label::new(Some("\u{16C8}"));
+        camera.add_css_class("module-rune");
+        camera.add_css_class("privacy-camera");
+        camera.set_visible(false);
+        widget.append(&camera);
+
+        let users = Rc::new(RefCell::new(Users::default()));
+
+        let defaults = ModuleActions {
+            on_click: "toggle-mute-all-sources".to_string(),
+            ..Default::default()
+        };
+        let widget_c = widget.clone();
+        let users_c = users.clone();
+        actions::attach(
+            &widget,
+            &actions::with_defaults(module_actions, defaults),
+            move |action, _steps| {
+                if !privacy::run_command(action) {
+                    return false;
+                }
+                refresh_microphone(&users_c);
+                show(&widget_c, &users_c.borrow());
+                true
+            },
+        );
+
+        let mut module = Self {
+            widget,
+            audio_listener: None,
+            camera_running: Arc::new(AtomicBool::new(false)),
+        };
+
+        if config.microphone {
+            module.watch_microphone(&users);
+        }
+        if config.camera {
+            module.watch_camera(&users, &camera, interval_secs);
+        }
+        show(&module.widget, &users.borrow());
+
+        module
+    }
+
+    fn watch_microphone(&mut self, users: &Rc<RefCell<Users>>) {
+        refresh_microphone(users);
+
+        let (sender, receiver) = async_channel::unbounded::<()>();
+        let listener = audio::AudioEventListener::new();
+        listener.start(sender);
+        self.audio_listener = Some(listener);
+
+        let widget = self.widget.clone();
+        let users = users.clone();
+        glib::spawn_future_local(async move {
+            while receiver.recv().await.is_ok() {
+                // Streams come and go in bursts; look once they settle
+                while receiver.try_recv().is_ok() {}
+                refresh_microphone(&users);
+                show(&widget, &users.borrow());
+            }
+        });
+    }
+
+    fn watch_camera(&self, users: &Rc<RefCell<Users>>, camera: &gtk4::Label, interval_secs: u32) {
+        let (sender, receiver) = async_channel::unbounded::<Vec<String>>();
+        let interval = Duration::from_secs(interval_secs.max(1) as u64);
+        let running = self.camera_running.clone();
+        running.store(true, Ordering::SeqCst);
+
+        // Scanning every process's fds is too slow for the main thread
+        std::thread::spawn(move || {
+            let mut last = None;
+            while running.load(Ordering::SeqCst) {
+                let current = privacy::camera_users();
+                if last.as_ref() != Some(&current) {
+                    if sender.send_blocking(current.clone()).is_err() {
+                        return;
+                    }
+                    last = Some(current);
+                }
+                std::thread::sleep(interval);
+            }
+        });
+
+        let widget = self.widget.clone();
+        let camera = camera.clone();
+        let users = users.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(current) = receiver.recv().await {
+                camera.set_visible(!current.is_empty());
+                users.borrow_mut().camera = current;
+                show(&widget, &users.borrow());
+            }
+        });
+    }
+
+    pub fn stop(&self) {
+        if let Some(listener) = &self.audio_listener {
+            listener.stop();
+        }
+        self.camera_running.store(false, Ordering::SeqCst);
+    }
+}
+
+fn refresh_microphone(users: &Rc<RefCell<Users>>) {
+    let mut users = users.borrow_mut();
+    users.microphone = privacy::microphone_users();
+    users.muted = audio::all_sources_muted();
+}
+
+fn show(widget: &gtk4::Box, users: &Users) {
+    let classes = [
+        ("mic-active", !users.microphone.is_empty()),
+        ("mic-muted", users.muted),
+        ("camera-active", !users.camera.is_empty()),
+    ];
+    for (class, on) in classes {
+        if on {
+            widget.add_css_class(class);
+        } else {
+            widget.remove_css_class(class);
+        }
+    }
+
+    let mut lines = Vec::new();
+    if users.muted {
+        lines.push("All microphones muted".to_string());
+    }
+    if !users.microphone.is_empty() {
+        lines.push(format!(
+            "Microphone in use by {}",
+            users.microphone.join(", ")
+        ));
+    }
+    if !users.camera.is_empty() {
+        lines.push(format!("Camera in use by {}", users.camera.join(", ")));
+    }
+    if lines.is_empty() {
+        lines.push("No microphone or camera in use".to_string());
+    }
+    widget.set_tooltip_text(Some(&lines.join("\n")));
+}
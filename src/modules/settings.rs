@@ -16,6 +16,7 @@ const MODULE_DISPLAY: &[(&str, &str)] = &[
     ("connectivity", "Connectivity"),
     ("audio", "Audio"),
     ("brightness", "Brightness"),
+    ("privacy", "Privacy"),
     ("power", "Power"),
     ("battery", "Battery"),
     ("clock", "Clock"),
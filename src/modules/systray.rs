@@ -1,106 +1,120 @@
+use crate::config::SystrayConfig;
+use crate::system::dbusmenu::{MenuItem, ToggleType};
+use crate::system::systray::{
+    self, ItemAction, ItemStatus, Pixmap, TrayCommand, TrayEvent, TrayItem,
+};
+use gtk4::gdk;
 use gtk4::glib;
+use gtk4::pango;
 use gtk4::prelude::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const FALLBACK_ICON: &str = "application-x-executable-symbolic";
 
-#[derive(Debug, Clone)]
-struct TrayItem {
-    service: String,
-    path: String,
-    icon_name: String,
-    title: String,
+/// An item's icon with its overlay emblem in the bottom-right corner.
+struct TrayIcon {
+    widget: gtk4::Overlay,
+    image: gtk4::Image,
+    overlay: gtk4::Image,
 }
 
-enum TrayEvent {
-    Added(TrayItem),
-    Removed(String),
+/// Where icons go: the bar itself, or the overflow drawer behind a button.
+struct TrayView {
+    config: SystrayConfig,
+    widget: gtk4::Box,
+    bar: gtk4::Box,
+    drawer: gtk4::Box,
+    overflow: gtk4::MenuButton,
+    icons: RefCell<HashMap<String, TrayIcon>>,
+    // Key of the item whose menu this bar asked for, so only it shows
+    // the menu when it arrives
+    menu_requested: Rc<RefCell<Option<String>>>,
 }
 
-// ── StatusNotifierWatcher D-Bus service ──
+// ── Tray client ──
 
-struct WatcherService {
-    items: Arc<tokio::sync::Mutex<Vec<String>>>,
-    sender: async_channel::Sender<TrayEvent>,
-    connection: zbus::Connection,
+/// The app's one tray backend. Every subscriber gets every event, starting
+/// with the items already registered, and commands from any of them go out
+/// over the same connection.
+pub struct TrayClient {
+    commands: async_channel::Sender<TrayCommand>,
+    subscribers: Rc<RefCell<Vec<async_channel::Sender<TrayEvent>>>>,
+    // Registration order, for subscribers that join late
+    items: Rc<RefCell<Vec<TrayItem>>>,
 }
 
-#[zbus::interface(name = "org.kde.StatusNotifierWatcher")]
-impl WatcherService {
-    async fn register_status_notifier_item(
-        &self,
-        service: &str,
-        #[zbus(header)] header: zbus::message::Header<'_>,
-    ) {
-        let full_service = if service.starts_with('/') {
-            let sender_name = header
-                .sender()
-                .map(|s| s.to_string())
-                .unwrap_or_default();
-            format!("{}{}", sender_name, service)
-        } else {
-            service.to_string()
-        };
-
-        let mut items = self.items.lock().await;
-        if items.contains(&full_service) {
-            return;
-        }
-        items.push(full_service.clone());
-        drop(items);
-
-        let item = fetch_item_properties(&self.connection, &full_service).await;
-        let _ = self.sender.send(TrayEvent::Added(item)).await;
-    }
+impl TrayClient {
+    pub fn new() -> Self {
+        let (commands, command_receiver) = async_channel::unbounded::<TrayCommand>();
+        let (sender, receiver) = async_channel::unbounded::<TrayEvent>();
+        systray::start(command_receiver, sender);
 
-    fn register_status_notifier_host(&self, _service: &str) {}
+        let subscribers: Rc<RefCell<Vec<async_channel::Sender<TrayEvent>>>> = Rc::default();
+        let items: Rc<RefCell<Vec<TrayItem>>> = Rc::default();
+        let subscribers_c = subscribers.clone();
+        let items_c = items.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                match &event {
+                    TrayEvent::Added(item) => {
+                        let mut items = items_c.borrow_mut();
+                        if !items.iter().any(|i| i.key() == item.key()) {
+                            items.push(item.clone());
+                        }
+                    }
+                    TrayEvent::Updated(item) => {
+                        let key = item.key();
+                        if let Some(slot) = items_c.borrow_mut().iter_mut().find(|i| i.key() == key)
+                        {
+                            *slot = item.clone();
+                        }
+                    }
+                    TrayEvent::Removed(key) => {
+                        items_c.borrow_mut().retain(|i| &i.key() != key);
+                    }
+                    TrayEvent::Menu(..) => {}
+                }
+                subscribers_c
+                    .borrow_mut()
+                    .retain(|subscriber| subscriber.try_send(event.clone()).is_ok());
+            }
+        });
 
-    #[zbus(property)]
-    async fn registered_status_notifier_items(&self) -> Vec<String> {
-        self.items.lock().await.clone()
+        Self {
+            commands,
+            subscribers,
+            items,
+        }
     }
 
-    #[zbus(property)]
-    fn is_status_notifier_host_registered(&self) -> bool {
-        true
+    pub fn subscribe(&self) -> async_channel::Receiver<TrayEvent> {
+        let (sender, receiver) = async_channel::unbounded();
+        for item in self.items.borrow().iter() {
+            let _ = sender.try_send(TrayEvent::Added(item.clone()));
+        }
+        self.subscribers.borrow_mut().push(sender);
+        receiver
     }
 
-    #[zbus(property)]
-    fn protocol_version(&self) -> i32 {
-        0
+    pub fn commands(&self) -> async_channel::Sender<TrayCommand> {
+        self.commands.clone()
     }
-
-    #[zbus(signal)]
-    async fn status_notifier_item_registered(
-        signal_ctxt: &zbus::object_server::SignalContext<'_>,
-        service: &str,
-    ) -> zbus::Result<()>;
-
-    #[zbus(signal)]
-    async fn status_notifier_item_unregistered(
-        signal_ctxt: &zbus::object_server::SignalContext<'_>,
-        service: &str,
-    ) -> zbus::Result<()>;
-
-    #[zbus(signal)]
-    async fn status_notifier_host_registered(
-        signal_ctxt: &zbus::object_server::SignalContext<'_>,
-    ) -> zbus::Result<()>;
 }
 
 // ── Systray module ──
 
 pub struct Systray {
     pub widget: gtk4::Box,
-    icons: Rc<RefCell<HashMap<String, gtk4::Image>>>,
+    view: Rc<TrayView>,
     items: Arc<Mutex<Vec<TrayItem>>>,
+    commands: async_channel::Sender<TrayCommand>,
 }
 
 impl Systray {
-    pub fn new() -> Self {
+    pub fn new(config: &SystrayConfig, client: &TrayClient) -> Self {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
         widget.add_css_class("systray");
 
@@ -110,96 +124,98 @@ impl Systray {
         rune.set_tooltip_text(Some("\u{16C9} Algiz - System Tray"));
         widget.append(&rune);
 
+        let bar = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+        widget.append(&bar);
+
+        // ᛜ Ingwaz - The seed, kept in store
+        let overflow = gtk4::MenuButton::new();
+        overflow.add_css_class("systray-overflow");
+        overflow.set_has_frame(false);
+        overflow.set_child(Some(&gtk4::Label::new(Some("\u{16DC}"))));
+        overflow.set_tooltip_text(Some("\u{16DC} Ingwaz - More Items"));
+        overflow.set_visible(false);
+
+        let drawer = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+        drawer.set_margin_top(6);
+        drawer.set_margin_bottom(6);
+        drawer.set_margin_start(6);
+        drawer.set_margin_end(6);
+
+        let popover = gtk4::Popover::new();
+        popover.add_css_class("systray-drawer");
+        popover.set_autohide(true);
+        popover.set_child(Some(&drawer));
+        overflow.set_popover(Some(&popover));
+        widget.append(&overflow);
+
+        let view = Rc::new(TrayView {
+            config: config.clone(),
+            widget: widget.clone(),
+            bar,
+            drawer,
+            overflow,
+            icons: RefCell::new(HashMap::new()),
+            menu_requested: Rc::default(),
+        });
+
         let items = Arc::new(Mutex::new(Vec::new()));
 
-        let mut systray = Self {
+        let systray = Self {
             widget,
-            icons: Rc::new(RefCell::new(HashMap::new())),
+            view,
             items,
+            commands: client.commands(),
         };
 
-        systray.start_dbus_listener();
+        systray.start_dbus_listener(client.subscribe());
         systray
     }
 
-    fn start_dbus_listener(&mut self) {
-        let (sender, receiver) = async_channel::unbounded::<TrayEvent>();
-
-        std::thread::spawn(move || {
-            let rt = match tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-            {
-                Ok(rt) => rt,
-                Err(e) => {
-                    eprintln!("Warning: Could not create tokio runtime for systray: {e}");
-                    return;
-                }
-            };
-
-            rt.block_on(async move {
-                let Ok(conn) = zbus::Connection::session().await else {
-                    eprintln!("Warning: Could not connect to session bus for systray");
-                    return;
-                };
-
-                let items = Arc::new(tokio::sync::Mutex::new(Vec::<String>::new()));
-
-                let watcher = WatcherService {
-                    items: items.clone(),
-                    sender: sender.clone(),
-                    connection: conn.clone(),
-                };
-
-                if let Err(e) = conn
-                    .object_server()
-                    .at("/StatusNotifierWatcher", watcher)
-                    .await
-                {
-                    eprintln!("Warning: Could not serve StatusNotifierWatcher: {e}");
-                    // Fall back to client mode
-                    run_client_mode(&conn, &sender).await;
-                    return;
-                }
-
-                match conn
-                    .request_name("org.kde.StatusNotifierWatcher")
-                    .await
-                {
-                    Ok(_) => {
-                        // We are the watcher — monitor for app exits
-                        watch_name_changes(&conn, &items, &sender).await;
-                    }
-                    Err(_) => {
-                        // Another watcher is running (e.g. waybar) — use it as client
-                        run_client_mode(&conn, &sender).await;
-                    }
-                }
-            });
-        });
-
-        let widget = self.widget.clone();
-        let icons = self.icons.clone();
+    fn start_dbus_listener(&self, receiver: async_channel::Receiver<TrayEvent>) {
+        let view = self.view.clone();
         let items = self.items.clone();
+        let commands = self.commands.clone();
 
         glib::spawn_future_local(async move {
             while let Ok(event) = receiver.recv().await {
                 match event {
                     TrayEvent::Added(item) => {
-                        let key = format!("{}{}", item.service, item.path);
+                        let key = item.key();
                         let mut current = items.lock().unwrap();
-                        if !current.iter().any(|i| format!("{}{}", i.service, i.path) == key) {
+                        if !current.iter().any(|i| i.key() == key) {
                             current.push(item);
                         }
                         drop(current);
-                        refresh_tray(&widget, &icons, &items);
+                        refresh_tray(&view, &items, &commands);
+                    }
+                    TrayEvent::Updated(item) => {
+                        let key = item.key();
+                        if let Some(icon) = view.icons.borrow().get(&key) {
+                            update_icon(icon, &item, view.config.icon_size);
+                        }
+                        let mut current = items.lock().unwrap();
+                        if let Some(slot) = current.iter_mut().find(|i| i.key() == key) {
+                            *slot = item;
+                        }
+                        drop(current);
+                        // The status may move it in or out of the drawer
+                        layout_tray(&view, &items);
                     }
                     TrayEvent::Removed(key) => {
-                        items
-                            .lock()
-                            .unwrap()
-                            .retain(|i| format!("{}{}", i.service, i.path) != key);
-                        refresh_tray(&widget, &icons, &items);
+                        items.lock().unwrap().retain(|i| i.key() != key);
+                        refresh_tray(&view, &items, &commands);
+                    }
+                    TrayEvent::Menu(key, menu) => {
+                        // Another bar's request
+                        let requested = view.menu_requested.borrow_mut().take_if(|k| *k == key);
+                        if requested.is_none() {
+                            continue;
+                        }
+                        let item = items.lock().unwrap().iter().find(|i| i.key() == key).cloned();
+                        let icon = view.icons.borrow().get(&key).map(|icon| icon.widget.clone());
+                        if let (Some(item), Some(icon)) = (item, icon) {
+                            show_menu(&icon, &item, &menu, &commands);
+                        }
                     }
                 }
             }
@@ -211,221 +227,436 @@ impl Systray {
     }
 }
 
-// ── NameOwnerChanged watcher ──
+// ── GTK refresh ──
 
-async fn watch_name_changes(
-    conn: &zbus::Connection,
-    items: &Arc<tokio::sync::Mutex<Vec<String>>>,
-    sender: &async_channel::Sender<TrayEvent>,
+/// Creates icons for new items and drops those of gone ones.
+fn refresh_tray(
+    view: &TrayView,
+    items: &Arc<Mutex<Vec<TrayItem>>>,
+    commands: &async_channel::Sender<TrayCommand>,
 ) {
-    use futures_util::StreamExt;
-
-    let Ok(dbus_proxy) = zbus::fdo::DBusProxy::new(conn).await else {
-        eprintln!("Warning: Could not create DBus proxy for name watching");
-        // Keep the task alive so the watcher stays registered
-        std::future::pending::<()>().await;
-        return;
-    };
+    let current_items = items.lock().unwrap().clone();
+    let mut present = std::collections::HashSet::new();
+    let mut icons = view.icons.borrow_mut();
+    let size = view.config.icon_size;
 
-    let Ok(mut stream) = dbus_proxy.receive_name_owner_changed().await else {
-        eprintln!("Warning: Could not subscribe to NameOwnerChanged");
-        std::future::pending::<()>().await;
-        return;
-    };
+    for item in &current_items {
+        let key = item.key();
+        present.insert(key.clone());
 
-    while let Some(signal) = stream.next().await {
-        let Ok(args) = signal.args() else {
-            continue;
-        };
+        if !icons.contains_key(&key) {
+            let image = gtk4::Image::new();
+            image.set_pixel_size(size);
+
+            let overlay = gtk4::Image::new();
+            overlay.add_css_class("systray-overlay");
+            overlay.set_pixel_size(size / 2);
+            overlay.set_halign(gtk4::Align::End);
+            overlay.set_valign(gtk4::Align::End);
+            overlay.set_can_target(false);
+
+            let container = gtk4::Overlay::new();
+            container.add_css_class("systray-icon");
+            container.set_child(Some(&image));
+            container.add_overlay(&overlay);
+            attach_item_controllers(&container, &key, items, commands, &view.menu_requested);
+
+            let icon = TrayIcon {
+                widget: container,
+                image,
+                overlay,
+            };
+            update_icon(&icon, item, size);
+            icons.insert(key, icon);
+        }
+    }
 
-        // Only care when a name vanishes (new_owner is empty)
-        let new_owner: &str = args.new_owner().as_deref().unwrap_or("");
-        if !new_owner.is_empty() {
-            continue;
+    // Remove gone items
+    let to_remove: Vec<String> = icons
+        .keys()
+        .filter(|k| !present.contains(*k))
+        .cloned()
+        .collect();
+    for key in to_remove {
+        if let Some(icon) = icons.remove(&key) {
+            detach(&icon.widget);
         }
+    }
+    drop(icons);
+
+    layout_tray(view, items);
+}
 
-        let vanished = args.name().as_str();
-        let mut lock = items.lock().await;
+/// Puts icons in the configured order, leaving out hidden items and
+/// moving passive and excess ones into the overflow drawer.
+fn layout_tray(view: &TrayView, items: &Arc<Mutex<Vec<TrayItem>>>) {
+    let config = &view.config;
+    let items = items.lock().unwrap().clone();
 
-        // Collect items belonging to the vanished service
-        let removed: Vec<String> = lock
+    let mut shown: Vec<&TrayItem> = items
+        .iter()
+        .filter(|item| !config.hidden.iter().any(|name| item.matches(name)))
+        .collect();
+    // Stable, so unlisted items keep registration order after listed ones
+    shown.sort_by_key(|item| {
+        config
+            .order
             .iter()
-            .filter(|item_str| {
-                let svc = if let Some(idx) = item_str.find('/') {
-                    &item_str[..idx]
-                } else {
-                    item_str.as_str()
-                };
-                svc == vanished
-            })
-            .cloned()
-            .collect();
-
-        if removed.is_empty() {
-            continue;
-        }
+            .position(|name| item.matches(name))
+            .unwrap_or(usize::MAX)
+    });
+
+    let (mut in_bar, mut in_drawer): (Vec<&TrayItem>, Vec<&TrayItem>) = shown
+        .into_iter()
+        .partition(|item| !(config.overflow_passive && item.status == ItemStatus::Passive));
+    if config.max_visible > 0 && in_bar.len() > config.max_visible {
+        let excess = in_bar.split_off(config.max_visible);
+        in_drawer.splice(0..0, excess);
+    }
 
-        lock.retain(|item_str| {
-            let svc = if let Some(idx) = item_str.find('/') {
-                &item_str[..idx]
-            } else {
-                item_str.as_str()
+    let icons = view.icons.borrow();
+    let mut placed = std::collections::HashSet::new();
+    for (container, items) in [(&view.bar, &in_bar), (&view.drawer, &in_drawer)] {
+        let mut previous: Option<gtk4::Widget> = None;
+        for item in items {
+            let key = item.key();
+            let Some(icon) = icons.get(&key) else {
+                continue;
             };
-            svc != vanished
-        });
-        drop(lock);
-
-        for svc in &removed {
-            // Reconstruct the key the same way the GTK side does
-            let (service_name, path) = if let Some(idx) = svc.find('/') {
-                (
-                    svc[..idx].to_string(),
-                    format!("/{}", &svc[idx + 1..]),
-                )
-            } else {
-                (svc.to_string(), "/StatusNotifierItem".to_string())
-            };
-            let key = format!("{}{}", service_name, path);
-            let _ = sender.send(TrayEvent::Removed(key)).await;
+            let widget = icon.widget.clone().upcast::<gtk4::Widget>();
+            if widget.parent().as_ref() != Some(container.upcast_ref()) {
+                detach(&widget);
+                container.append(&widget);
+            }
+            container.reorder_child_after(&widget, previous.as_ref());
+            previous = Some(widget);
+            placed.insert(key);
         }
     }
+    for (key, icon) in icons.iter() {
+        if !placed.contains(key) {
+            detach(&icon.widget);
+        }
+    }
+
+    if in_drawer.is_empty() {
+        view.overflow.popdown();
+    }
+    view.overflow.set_visible(!in_drawer.is_empty());
+    view.widget.set_visible(!in_bar.is_empty() || !in_drawer.is_empty());
 }
 
-// ── Client mode fallback ──
+fn detach(widget: &impl IsA<gtk4::Widget>) {
+    if let Some(parent) = widget.parent().and_downcast::<gtk4::Box>() {
+        parent.remove(widget);
+    }
+}
 
-async fn run_client_mode(
-    conn: &zbus::Connection,
-    sender: &async_channel::Sender<TrayEvent>,
-) {
-    let Ok(proxy) = zbus::Proxy::new(
-        conn,
-        "org.kde.StatusNotifierWatcher",
-        "/StatusNotifierWatcher",
-        "org.kde.StatusNotifierWatcher",
-    )
-    .await
-    else {
-        return;
+/// Shows the attention icon while the item needs attention, its overlay
+/// emblem, tooltip and status.
+fn update_icon(icon: &TrayIcon, item: &TrayItem, size: i32) {
+    let attention = item.status == ItemStatus::NeedsAttention;
+    let has_attention_icon =
+        !item.attention_icon_name.is_empty() || !item.attention_pixmaps.is_empty();
+    let (name, pixmaps) = if attention && has_attention_icon {
+        (&item.attention_icon_name, &item.attention_pixmaps)
+    } else {
+        (&item.icon_name, &item.icon_pixmaps)
     };
 
-    // Fetch currently registered items
-    if let Ok(registered) = proxy
-        .get_property::<Vec<String>>("RegisteredStatusNotifierItems")
-        .await
-    {
-        for service_str in registered {
-            let item = fetch_item_properties(conn, &service_str).await;
-            let _ = sender.send(TrayEvent::Added(item)).await;
+    if !item.icon_theme_path.is_empty() {
+        add_icon_search_path(&icon.image, &item.icon_theme_path);
+    }
+    if !set_image(&icon.image, name, pixmaps, size) {
+        icon.image.set_icon_name(Some(FALLBACK_ICON));
+    }
+    let has_overlay = set_image(
+        &icon.overlay,
+        &item.overlay_icon_name,
+        &item.overlay_pixmaps,
+        size / 2,
+    );
+    icon.overlay.set_visible(has_overlay);
+
+    icon.widget.set_tooltip_markup(Some(&tooltip_markup(item)));
+
+    for (class, active) in [
+        ("passive", item.status == ItemStatus::Passive),
+        ("needs-attention", attention),
+    ] {
+        if active {
+            icon.widget.add_css_class(class);
+        } else {
+            icon.widget.remove_css_class(class);
         }
     }
+}
 
-    // Listen for new registrations
-    let Ok(mut stream) = proxy
-        .receive_signal("StatusNotifierItemRegistered")
-        .await
-    else {
-        return;
-    };
-
-    use futures_util::StreamExt;
-    while let Some(signal) = stream.next().await {
-        if let Ok(args) = signal.body().deserialize::<(String,)>() {
-            let item = fetch_item_properties(conn, &args.0).await;
-            let _ = sender.send(TrayEvent::Added(item)).await;
+/// Sets the named icon if the theme (or the file system, for absolute
+/// paths) has it, else the pixmap closest to `size`. Returns false when
+/// neither is usable.
+fn set_image(image: &gtk4::Image, name: &str, pixmaps: &[Pixmap], size: i32) -> bool {
+    if name.starts_with('/') && std::path::Path::new(name).is_file() {
+        image.set_from_file(Some(name));
+        return true;
+    }
+    let theme = gtk4::IconTheme::for_display(&image.display());
+    if !name.is_empty() && theme.has_icon(name) {
+        image.set_icon_name(Some(name));
+        return true;
+    }
+    match Pixmap::best(pixmaps, size) {
+        Some(pixmap) => {
+            image.set_paintable(Some(&pixmap_texture(pixmap)));
+            true
         }
+        None => false,
+    }
+}
+
+fn pixmap_texture(pixmap: &Pixmap) -> gdk::MemoryTexture {
+    let stride = pixmap.width as usize * 4;
+    let len = stride * pixmap.height as usize;
+    // ARGB32 in network byte order is A, R, G, B in memory
+    gdk::MemoryTexture::new(
+        pixmap.width,
+        pixmap.height,
+        gdk::MemoryFormat::A8r8g8b8,
+        &glib::Bytes::from(&pixmap.data[..len]),
+        stride,
+    )
+}
+
+fn add_icon_search_path(image: &gtk4::Image, path: &str) {
+    let theme = gtk4::IconTheme::for_display(&image.display());
+    let path = std::path::PathBuf::from(path);
+    if !theme.search_path().contains(&path) {
+        theme.add_search_path(&path);
     }
 }
 
-// ── Item property fetcher ──
+/// The item's tooltip title in bold over its description, or just its name.
+fn tooltip_markup(item: &TrayItem) -> String {
+    let Some(tooltip) = &item.tooltip else {
+        return glib::markup_escape_text(item.display_name()).to_string();
+    };
+    let title = if tooltip.title.is_empty() {
+        item.display_name()
+    } else {
+        &tooltip.title
+    };
+    let title = glib::markup_escape_text(title);
+    if tooltip.description.is_empty() {
+        return title.to_string();
+    }
 
-async fn fetch_item_properties(conn: &zbus::Connection, service_str: &str) -> TrayItem {
-    let (service_name, path) = if let Some(idx) = service_str.find('/') {
-        (
-            service_str[..idx].to_string(),
-            format!("/{}", &service_str[idx + 1..]),
-        )
+    // Descriptions may use simple markup, but not all apps get it right
+    let description = if pango::parse_markup(&tooltip.description, '\0').is_ok() {
+        tooltip.description.clone()
     } else {
-        (service_str.to_string(), "/StatusNotifierItem".to_string())
+        glib::markup_escape_text(&tooltip.description).to_string()
     };
+    format!("<b>{title}</b>\n{description}")
+}
 
-    let mut item = TrayItem {
-        service: service_name.clone(),
-        path: path.clone(),
-        icon_name: String::new(),
-        title: String::new(),
+/// Left click activates (or opens the menu of menu-only items), middle
+/// click secondary-activates, right click opens the menu and scrolling
+/// is forwarded to the item. Handlers look the item up by `key` so they
+/// see its latest properties.
+fn attach_item_controllers(
+    icon: &gtk4::Overlay,
+    key: &str,
+    items: &Arc<Mutex<Vec<TrayItem>>>,
+    commands: &async_channel::Sender<TrayCommand>,
+    menu_requested: &Rc<RefCell<Option<String>>>,
+) {
+    let current = {
+        let key = key.to_string();
+        let items = items.clone();
+        move || items.lock().unwrap().iter().find(|i| i.key() == key).cloned()
     };
 
-    if let Ok(proxy) = zbus::Proxy::new(
-        conn,
-        service_name.as_str(),
-        path.as_str(),
-        ITEM_INTERFACE,
-    )
-    .await
-    {
-        if let Ok(icon) = proxy.get_property::<String>("IconName").await {
-            item.icon_name = icon;
-        }
-        if let Ok(title) = proxy.get_property::<String>("Title").await {
-            item.title = title;
-        }
-        if item.title.is_empty() {
-            if let Ok(id) = proxy.get_property::<String>("Id").await {
-                item.title = id;
-            }
+    let gesture = gtk4::GestureClick::new();
+    gesture.set_button(0);
+    let current_c = current.clone();
+    let commands_c = commands.clone();
+    let menu_requested = menu_requested.clone();
+    gesture.connect_released(move |gesture, _, _, _| {
+        let Some(item) = current_c() else {
+            return;
+        };
+        let has_menu = item.menu.is_some();
+        let command = match gesture.current_button() {
+            gdk::BUTTON_PRIMARY if item.item_is_menu && has_menu => TrayCommand::OpenMenu(item),
+            gdk::BUTTON_PRIMARY => TrayCommand::Item(item, ItemAction::Activate),
+            gdk::BUTTON_MIDDLE => TrayCommand::Item(item, ItemAction::SecondaryActivate),
+            gdk::BUTTON_SECONDARY if has_menu => TrayCommand::OpenMenu(item),
+            gdk::BUTTON_SECONDARY => TrayCommand::Item(item, ItemAction::ContextMenu),
+            _ => return,
+        };
+        if let TrayCommand::OpenMenu(ref item) = command {
+            *menu_requested.borrow_mut() = Some(item.key());
         }
-    }
-
-    item
+        let _ = commands_c.send_blocking(command);
+    });
+    icon.add_controller(gesture);
+
+    let scroll = gtk4::EventControllerScroll::new(
+        gtk4::EventControllerScrollFlags::BOTH_AXES | gtk4::EventControllerScrollFlags::DISCRETE,
+    );
+    let commands_c = commands.clone();
+    scroll.connect_scroll(move |_, dx, dy| {
+        let Some(item) = current() else {
+            return glib::Propagation::Proceed;
+        };
+        // SNI deltas follow wheel angles: 120 per notch, positive is up/left
+        let (delta, orientation) = if dy != 0.0 {
+            (-dy * 120.0, "vertical")
+        } else {
+            (-dx * 120.0, "horizontal")
+        };
+        let action = ItemAction::Scroll(delta.round() as i32, orientation);
+        let _ = commands_c.send_blocking(TrayCommand::Item(item, action));
+        glib::Propagation::Stop
+    });
+    icon.add_controller(scroll);
 }
 
-// ── GTK refresh ──
-
-fn refresh_tray(
-    widget: &gtk4::Box,
-    icons: &Rc<RefCell<HashMap<String, gtk4::Image>>>,
-    items: &Arc<Mutex<Vec<TrayItem>>>,
+fn show_menu(
+    icon: &gtk4::Overlay,
+    item: &TrayItem,
+    menu: &MenuItem,
+    commands: &async_channel::Sender<TrayCommand>,
 ) {
-    let current_items = items.lock().unwrap().clone();
-    let mut present = std::collections::HashSet::new();
-    let mut icons = icons.borrow_mut();
+    let Some(menu_path) = item.menu.clone() else {
+        return;
+    };
 
-    for item in &current_items {
-        let key = format!("{}{}", item.service, item.path);
-        present.insert(key.clone());
+    let popover = gtk4::Popover::new();
+    popover.add_css_class("app-menu");
+    popover.add_css_class("systray-menu");
+    popover.set_parent(icon);
+    popover.set_position(gtk4::PositionType::Bottom);
+    popover.set_autohide(true);
+    popover.connect_closed(|p| {
+        p.unparent();
+    });
+
+    let target = MenuTarget {
+        service: item.service.clone(),
+        menu: menu_path,
+        commands: commands.clone(),
+        root: popover.clone(),
+    };
+    popover.set_child(Some(&build_menu(&menu.children, &target)));
+    popover.popup();
+}
 
-        if !icons.contains_key(&key) {
-            let icon = if item.icon_name.is_empty() {
-                gtk4::Image::from_icon_name("application-x-executable-symbolic")
-            } else {
-                gtk4::Image::from_icon_name(&item.icon_name)
-            };
-            icon.add_css_class("systray-icon");
-            icon.set_pixel_size(16);
+/// Where menu clicks go, plus the top-level popover they close.
+#[derive(Clone)]
+struct MenuTarget {
+    service: String,
+    menu: String,
+    commands: async_channel::Sender<TrayCommand>,
+    root: gtk4::Popover,
+}
 
-            let tooltip = if item.title.is_empty() {
-                &item.service
-            } else {
-                &item.title
-            };
-            icon.set_tooltip_text(Some(tooltip));
+fn build_menu(items: &[MenuItem], target: &MenuTarget) -> gtk4::Box {
+    let content = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    content.set_margin_top(6);
+    content.set_margin_bottom(6);
+    content.set_margin_start(6);
+    content.set_margin_end(6);
 
-            widget.append(&icon);
-            icons.insert(key, icon);
+    for item in items.iter().filter(|i| i.visible) {
+        if item.separator {
+            content.append(&gtk4::Separator::new(gtk4::Orientation::Horizontal));
+            continue;
         }
+        content.append(&build_menu_row(item, target));
     }
+    content
+}
 
-    // Remove gone items
-    let to_remove: Vec<String> = icons
-        .keys()
-        .filter(|k| !present.contains(*k))
-        .cloned()
-        .collect();
-    for key in to_remove {
-        if let Some(icon) = icons.remove(&key) {
-            widget.remove(&icon);
+fn build_menu_row(item: &MenuItem, target: &MenuTarget) -> gtk4::Button {
+    let btn = gtk4::Button::new();
+    btn.add_css_class("app-menu-item");
+    btn.set_sensitive(item.enabled);
+
+    let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+
+    // Toggle state, else the item's icon, in a fixed-width column
+    let indicator = match item.toggle {
+        ToggleType::Checkmark => Some(if item.toggled { "\u{2713}" } else { "" }),
+        ToggleType::Radio => Some(if item.toggled { "\u{25C9}" } else { "\u{25CB}" }),
+        ToggleType::None => None,
+    };
+    match indicator {
+        Some(mark) => {
+            let mark = gtk4::Label::new(Some(mark));
+            mark.add_css_class("app-menu-rune");
+            row.append(&mark);
         }
+        None => row.append(&menu_icon(item)),
     }
 
-    widget.set_visible(!icons.is_empty());
+    let label = gtk4::Label::new(Some(&item.label));
+    label.add_css_class("app-menu-label");
+    label.set_halign(gtk4::Align::Start);
+    label.set_hexpand(true);
+    row.append(&label);
+
+    if item.is_submenu() {
+        let arrow = gtk4::Label::new(Some("\u{25B8}"));
+        arrow.add_css_class("app-menu-label");
+        row.append(&arrow);
+    }
+    btn.set_child(Some(&row));
+
+    if item.is_submenu() {
+        let children = item.children.clone();
+        let target = target.clone();
+        btn.connect_clicked(move |btn| {
+            let submenu = gtk4::Popover::new();
+            submenu.add_css_class("app-menu");
+            submenu.add_css_class("systray-menu");
+            submenu.set_parent(btn);
+            submenu.set_position(gtk4::PositionType::Right);
+            submenu.set_autohide(true);
+            submenu.connect_closed(|p| {
+                p.unparent();
+            });
+            submenu.set_child(Some(&build_menu(&children, &target)));
+            submenu.popup();
+        });
+    } else {
+        let id = item.id;
+        let target = target.clone();
+        btn.connect_clicked(move |_| {
+            let _ = target.commands.send_blocking(TrayCommand::MenuClicked {
+                service: target.service.clone(),
+                menu: target.menu.clone(),
+                id,
+            });
+            target.root.popdown();
+        });
+    }
+
+    btn
+}
+
+fn menu_icon(item: &MenuItem) -> gtk4::Image {
+    let texture = item
+        .icon_data
+        .as_ref()
+        .and_then(|data| gdk::Texture::from_bytes(&glib::Bytes::from(data)).ok());
+    let icon = match (texture, &item.icon_name) {
+        (Some(texture), _) => gtk4::Image::from_paintable(Some(&texture)),
+        (None, Some(name)) => gtk4::Image::from_icon_name(name),
+        (None, None) => gtk4::Image::new(),
+    };
+    icon.set_pixel_size(16);
+    // Keeps labels aligned whether or not a row has an icon
+    icon.set_size_request(20, -1);
+    icon
 }
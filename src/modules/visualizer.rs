@@ -0,0 +1,125 @@
+use crate::config::VisualizerConfig;
+use crate::system::visualizer::Visualizer;
+use gtk4::glib;
+use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Gap between bars, as a fraction of each bar's slot
+const GAP: f64 = 0.25;
+
+/// Spectrum bars from one capture, drawn in as many areas as asked for.
+/// One is shared by every bar, so there's one capture however many
+/// monitors show it.
+pub struct Spectrum {
+    visualizer: Visualizer,
+    config: VisualizerConfig,
+    levels: Rc<RefCell<Vec<f64>>>,
+    areas: Rc<RefCell<Vec<gtk4::DrawingArea>>>,
+    sender: async_channel::Sender<Vec<f64>>,
+}
+
+impl Spectrum {
+    pub fn new(config: &VisualizerConfig) -> Self {
+        let levels = Rc::new(RefCell::new(vec![0.0; config.bars]));
+        let areas: Rc<RefCell<Vec<gtk4::DrawingArea>>> = Rc::new(RefCell::new(Vec::new()));
+        // Room for a frame in flight; the worker drops the rest
+        let (sender, receiver) = async_channel::bounded::<Vec<f64>>(2);
+
+        let levels_c = levels.clone();
+        let areas_c = areas.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(frame) = receiver.recv().await {
+                *levels_c.borrow_mut() = frame;
+                for area in areas_c.borrow().iter() {
+                    area.queue_draw();
+                }
+            }
+        });
+
+        Self {
+            visualizer: Visualizer::new(),
+            config: config.clone(),
+            levels,
+            areas,
+            sender,
+        }
+    }
+
+    /// A new area drawing the bars, hidden until playback starts.
+    pub fn area(&self, css_class: &str, width: i32, height: i32) -> gtk4::DrawingArea {
+        let area = gtk4::DrawingArea::new();
+        area.add_css_class("visualizer");
+        area.add_css_class(css_class);
+        area.set_content_width(width);
+        area.set_content_height(height);
+        area.set_visible(false);
+
+        let levels = self.levels.clone();
+        area.set_draw_func(move |area, cr, width, height| {
+            draw_spectrum(cr, width, height, &levels.borrow(), &area.color());
+        });
+        self.areas.borrow_mut().push(area.clone());
+        area
+    }
+
+    /// Shows `areas` (some of this spectrum's) while `active` and hides
+    /// them otherwise. Captures while any of its areas is showing.
+    pub fn set_active(&self, areas: &[gtk4::DrawingArea], active: bool) {
+        for area in areas {
+            area.set_visible(active);
+        }
+        if self.areas.borrow().iter().any(|area| area.is_visible()) {
+            self.visualizer.start(
+                self.config.bars,
+                self.config.framerate,
+                self.config.smoothing,
+                self.sender.clone(),
+            );
+        } else if self.visualizer.is_running() {
+            self.visualizer.stop();
+            self.levels.borrow_mut().fill(0.0);
+        }
+    }
+
+    pub fn stop(&self) {
+        self.visualizer.stop();
+    }
+}
+
+fn draw_spectrum(
+    cr: &gtk4::cairo::Context,
+    width: i32,
+    height: i32,
+    levels: &[f64],
+    color: &gtk4::gdk::RGBA,
+) {
+    if levels.is_empty() {
+        return;
+    }
+    let w = width as f64;
+    let h = height as f64;
+    let slot = w / levels.len() as f64;
+    let bar_w = (slot * (1.0 - GAP)).max(1.0);
+
+    // Bars rise from the bottom in the widget's CSS colour, fading towards
+    // the top
+    let gradient = gtk4::cairo::LinearGradient::new(0.0, h, 0.0, 0.0);
+    let (r, g, b, a) = (
+        color.red() as f64,
+        color.green() as f64,
+        color.blue() as f64,
+        color.alpha() as f64,
+    );
+    gradient.add_color_stop_rgba(0.0, r, g, b, a);
+    gradient.add_color_stop_rgba(1.0, r, g, b, a * 0.4);
+    let _ = cr.set_source(&gradient);
+
+    for (i, level) in levels.iter().enumerate() {
+        // Keep a sliver so silence still reads as a row of bars
+        let bar_h = (level.clamp(0.0, 1.0) * h).max(1.0);
+        let x = i as f64 * slot + (slot - bar_w) / 2.0;
+        cr.rectangle(x, h - bar_h, bar_w, bar_h);
+    }
+    let _ = cr.fill();
+}
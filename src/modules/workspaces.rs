@@ -1,10 +1,16 @@
-use crate::hyprland::HyprlandClient;
+use crate::hyprland::{normalize_address, HyprEvent, HyprlandClient};
 use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 
 pub struct Workspaces {
     pub widget: gtk4::Box,
     client: Rc<HyprlandClient>,
+    // Addresses of windows that requested attention and haven't been focused
+    urgent: RefCell<HashSet<String>>,
+    // Urgency from the focused window needs no marking
+    active_address: RefCell<String>,
 }
 
 impl Workspaces {
@@ -12,11 +18,54 @@ impl Workspaces {
         let widget = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
         widget.add_css_class("workspaces");
 
-        let ws = Self { widget, client };
+        let active_address = active_address(&client);
+        let ws = Self {
+            widget,
+            client,
+            urgent: RefCell::new(HashSet::new()),
+            active_address: RefCell::new(active_address),
+        };
         ws.refresh();
         ws
     }
 
+    pub fn handle_event(&self, event: &HyprEvent) {
+        match event {
+            HyprEvent::Workspace(_)
+            | HyprEvent::CreateWorkspace(_)
+            | HyprEvent::DestroyWorkspace(_) => self.refresh(),
+            HyprEvent::Reconnected => {
+                *self.active_address.borrow_mut() = active_address(&self.client);
+                self.refresh();
+            }
+            HyprEvent::Urgent(address) => {
+                let address = normalize_address(address);
+                if *self.active_address.borrow() == address {
+                    return;
+                }
+                self.urgent.borrow_mut().insert(address);
+                self.refresh();
+            }
+            // A window moving to another workspace takes its urgency along
+            HyprEvent::MoveWindow(_) if !self.urgent.borrow().is_empty() => self.refresh(),
+            HyprEvent::ActiveWindowV2(address) => {
+                let address = normalize_address(address);
+                let removed = self.urgent.borrow_mut().remove(&address);
+                *self.active_address.borrow_mut() = address;
+                if removed {
+                    self.refresh();
+                }
+            }
+            HyprEvent::CloseWindow(address) => {
+                let removed = self.urgent.borrow_mut().remove(&normalize_address(address));
+                if removed {
+                    self.refresh();
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn refresh(&self) {
         // Clear existing buttons
         while let Some(child) = self.widget.first_child() {
@@ -30,6 +79,8 @@ impl Workspaces {
             return;
         };
 
+        let urgent_workspaces = self.urgent_workspaces();
+
         let mut workspaces = workspaces;
         workspaces.sort_by_key(|w| w.id);
 
@@ -48,6 +99,10 @@ impl Workspaces {
                 btn.add_css_class("occupied");
             }
 
+            if urgent_workspaces.contains(&ws.id) {
+                btn.add_css_class("urgent");
+            }
+
             let client = self.client.clone();
             let ws_id = ws.id;
             btn.connect_clicked(move |_| {
@@ -57,4 +112,28 @@ impl Workspaces {
             self.widget.append(&btn);
         }
     }
+
+    /// Maps urgent windows to the workspaces they're currently on, dropping
+    /// any that have since closed.
+    fn urgent_workspaces(&self) -> HashSet<i32> {
+        let mut urgent = self.urgent.borrow_mut();
+        if urgent.is_empty() {
+            return HashSet::new();
+        }
+
+        let Ok(clients) = self.client.clients() else {
+            return HashSet::new();
+        };
+
+        urgent.retain(|address| clients.iter().any(|c| c.address == *address));
+        clients
+            .iter()
+            .filter(|c| urgent.contains(&c.address))
+            .map(|c| c.workspace.id)
+            .collect()
+    }
+}
+
+fn active_address(client: &HyprlandClient) -> String {
+    client.active_window().map(|w| w.address).unwrap_or_default()
 }
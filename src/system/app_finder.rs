@@ -1,191 +1,762 @@
+use crate::config::LaunchConfig;
+use crate::system::launch_history::LaunchHistory;
+use crate::system::launcher::{self, LaunchCommand};
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::Command;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
+// Terminals tried when `launch.terminal` is empty and $TERMINAL is unset,
+// with the arguments that make each run a command
+const KNOWN_TERMINALS: &[(&str, &[&str])] = &[
+    ("kitty", &[]),
+    ("foot", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("ghostty", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+#[derive(Clone)]
 pub struct DesktopEntry {
+    // Desktop file id: path below `applications/` with `/` turned into `-`,
+    // without `.desktop`
+    pub id: String,
+    pub path: PathBuf,
     pub name: String,
     pub exec: String,
     pub icon: Option<String>,
     pub comment: Option<String>,
     pub categories: Vec<String>,
+    pub keywords: Vec<String>,
     pub no_display: bool,
+    pub startup_wm_class: Option<String>,
+    pub terminal: bool,
+    pub working_dir: Option<String>,
+    pub actions: Vec<DesktopAction>,
 }
 
-pub fn load_desktop_entries() -> Vec<DesktopEntry> {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let dirs = [
-        PathBuf::from("/usr/share/applications"),
-        PathBuf::from("/usr/local/share/applications"),
-        PathBuf::from(format!("{home}/.local/share/applications")),
-        PathBuf::from("/var/lib/flatpak/exports/share/applications"),
-        PathBuf::from(format!("{home}/.local/share/flatpak/exports/share/applications")),
-    ];
-
-    let mut entries = Vec::new();
-    let mut seen = std::collections::HashSet::new();
+#[derive(Clone)]
+pub struct DesktopAction {
+    pub id: String,
+    pub name: String,
+    pub exec: String,
+    pub icon: Option<String>,
+}
 
-    for dir in &dirs {
-        if !dir.exists() {
-            continue;
+impl DesktopEntry {
+    /// File name of the program `Exec` runs, e.g. "code" for "/usr/bin/code %F".
+    /// Skips an `env VAR=value` prefix.
+    pub fn exec_basename(&self) -> Option<String> {
+        let args = self.exec_args(&self.exec);
+        let mut args = args.iter().map(|a| a.rsplit('/').next().unwrap_or(a));
+        let mut program = args.next()?;
+        if program == "env" {
+            program = args.find(|a| !a.contains('=') && !a.starts_with('-'))?;
         }
-        let Ok(read_dir) = std::fs::read_dir(dir) else {
-            continue;
-        };
+        Some(program.to_string()).filter(|p| !p.is_empty())
+    }
 
-        for entry in read_dir.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
-                continue;
-            }
+    /// Whether a window of `class` (compared case-insensitively) belongs to
+    /// this entry, by `StartupWMClass`, desktop file id or `Exec` basename.
+    pub fn matches_class(&self, class: &str) -> bool {
+        self.class_match(&class.to_lowercase()).is_some()
+    }
+
+    /// How strongly a lowercase window class matches, strongest first:
+    /// `StartupWMClass` (0), desktop file id (1), the id's last segment (2),
+    /// `Exec` basename (3). None when it doesn't match at all.
+    fn class_match(&self, class: &str) -> Option<usize> {
+        if class.is_empty() {
+            None
+        } else if self
+            .startup_wm_class
+            .as_deref()
+            .is_some_and(|wm| wm.to_lowercase() == class)
+        {
+            Some(0)
+        } else if self.id.to_lowercase() == class {
+            Some(1)
+        } else if self.id.rsplit('.').next().is_some_and(|l| l.to_lowercase() == class) {
+            Some(2)
+        } else if self.exec_basename().is_some_and(|b| b.to_lowercase() == class) {
+            Some(3)
+        } else {
+            None
+        }
+    }
 
-            if let Some(desktop_entry) = parse_desktop_file(&path) {
-                if desktop_entry.no_display {
-                    continue;
+    /// Splits an `Exec` value into arguments and expands its field codes. No
+    /// files or URLs are ever passed, so `%f`/`%F`/`%u`/`%U` expand to nothing.
+    pub fn exec_args(&self, exec: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        for token in tokenize_exec(exec) {
+            match token.as_str() {
+                "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        args.push("--icon".to_string());
+                        args.push(icon.clone());
+                    }
                 }
-                if seen.insert(desktop_entry.name.clone()) {
-                    entries.push(desktop_entry);
+                _ => {
+                    let expanded = self.expand_field_codes(&token);
+                    if !expanded.is_empty() || token.is_empty() {
+                        args.push(expanded);
+                    }
                 }
             }
         }
+        args
+    }
+
+    fn expand_field_codes(&self, arg: &str) -> String {
+        let mut result = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('c') => result.push_str(&self.name),
+                Some('k') => result.push_str(&self.path.to_string_lossy()),
+                // Other codes are invalid inside an argument; drop them
+                _ => {}
+            }
+        }
+        result
     }
+}
 
-    entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-    entries
+/// Locale and desktop environment that parsing depends on, read once so a
+/// batch of files shares them.
+pub struct ParseEnv {
+    locale: Option<Locale>,
+    desktops: Vec<String>,
 }
 
-fn parse_desktop_file(path: &PathBuf) -> Option<DesktopEntry> {
-    let content = std::fs::read_to_string(path).ok()?;
+impl ParseEnv {
+    pub fn from_env() -> Self {
+        Self {
+            locale: Locale::from_env(),
+            desktops: current_desktops(),
+        }
+    }
+}
+
+/// Message locale split into the parts used for `Key[locale]` matching.
+struct Locale {
+    lang: String,
+    country: Option<String>,
+    modifier: Option<String>,
+}
+
+impl Locale {
+    fn from_env() -> Option<Self> {
+        let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|v| !v.is_empty())?;
+        if value == "C" || value == "POSIX" || value.starts_with("C.") {
+            return None;
+        }
+
+        // lang_COUNTRY.ENCODING@MODIFIER
+        let (rest, modifier) = match value.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier.to_string())),
+            None => (value.as_str(), None),
+        };
+        let rest = rest.split('.').next().unwrap_or(rest);
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang.to_string(), Some(country.to_string())),
+            None => (rest.to_string(), None),
+        };
+
+        Some(Self {
+            lang,
+            country,
+            modifier,
+        })
+    }
+
+    /// Locale suffixes to try, most specific first.
+    fn candidates(&self) -> Vec<String> {
+        let mut candidates = Vec::new();
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            candidates.push(format!("{}_{country}@{modifier}", self.lang));
+        }
+        if let Some(country) = &self.country {
+            candidates.push(format!("{}_{country}", self.lang));
+        }
+        if let Some(modifier) = &self.modifier {
+            candidates.push(format!("{}@{modifier}", self.lang));
+        }
+        candidates.push(self.lang.clone());
+        candidates
+    }
+}
+
+fn current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| d.to_lowercase())
+        .collect()
+}
+
+/// Key/value pairs of one `[group]`, with raw (still escaped) values.
+struct Group(HashMap<String, String>);
+
+impl Group {
+    fn raw(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.raw(key).map(unescape_value)
+    }
+
+    fn localized(&self, key: &str, locale: &[String]) -> Option<String> {
+        locale
+            .iter()
+            .find_map(|l| self.string(&format!("{key}[{l}]")))
+            .or_else(|| self.string(key))
+    }
+
+    fn boolean(&self, key: &str) -> bool {
+        self.raw(key) == Some("true")
+    }
+
+    fn list(&self, key: &str) -> Vec<String> {
+        self.raw(key).map(split_list).unwrap_or_default()
+    }
+
+    fn localized_list(&self, key: &str, locale: &[String]) -> Vec<String> {
+        locale
+            .iter()
+            .find_map(|l| self.raw(&format!("{key}[{l}]")))
+            .or_else(|| self.raw(key))
+            .map(split_list)
+            .unwrap_or_default()
+    }
+}
 
-    let mut in_desktop_entry = false;
-    let mut fields = HashMap::new();
+fn parse_groups(content: &str) -> HashMap<String, Group> {
+    let mut groups = HashMap::new();
+    let mut current: Option<String> = None;
 
     for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with('[') {
-            in_desktop_entry = line == "[Desktop Entry]";
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
             continue;
         }
-        if !in_desktop_entry {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.trim_end().strip_suffix(']')) {
+            current = Some(name.to_string());
+            groups
+                .entry(name.to_string())
+                .or_insert_with(|| Group(HashMap::new()));
             continue;
         }
-        if let Some((key, value)) = line.split_once('=') {
-            fields.insert(key.trim().to_string(), value.trim().to_string());
+        let (Some(group), Some((key, value))) = (&current, line.split_once('=')) else {
+            continue;
+        };
+        if let Some(group) = groups.get_mut(group) {
+            // First occurrence wins, per spec duplicate keys are invalid
+            group
+                .0
+                .entry(key.trim().to_string())
+                .or_insert_with(|| value.trim().to_string());
         }
     }
 
-    let name = fields.get("Name")?.clone();
-    let exec = fields.get("Exec")?.clone();
+    groups
+}
+
+/// Parses the desktop file at `path` as entry `id`. None for files that
+/// aren't applications or shouldn't be shown on this desktop.
+pub fn parse_desktop_file(path: &Path, id: String, env: &ParseEnv) -> Option<DesktopEntry> {
+    let desktops = &env.desktops;
+    let content = std::fs::read_to_string(path).ok()?;
+    let groups = parse_groups(&content);
+    let main = groups.get("Desktop Entry")?;
 
     // Skip entries of type Link or Directory
-    if let Some(entry_type) = fields.get("Type") {
-        if entry_type != "Application" {
+    if main.raw("Type") != Some("Application") {
+        return None;
+    }
+    if main.boolean("Hidden") {
+        return None;
+    }
+
+    let only_show_in = main.list("OnlyShowIn");
+    if !only_show_in.is_empty()
+        && !only_show_in
+            .iter()
+            .any(|d| desktops.contains(&d.to_lowercase()))
+    {
+        return None;
+    }
+    if main
+        .list("NotShowIn")
+        .iter()
+        .any(|d| desktops.contains(&d.to_lowercase()))
+    {
+        return None;
+    }
+
+    if let Some(try_exec) = main.string("TryExec") {
+        if !is_executable(&try_exec) {
             return None;
         }
     }
 
-    let no_display = fields.get("NoDisplay")
-        .map(|v| v.to_lowercase() == "true")
-        .unwrap_or(false);
+    let locale = env.locale.as_ref().map(Locale::candidates).unwrap_or_default();
 
-    let hidden = fields.get("Hidden")
-        .map(|v| v.to_lowercase() == "true")
-        .unwrap_or(false);
+    let name = main.localized("Name", &locale)?;
+    let exec = main.string("Exec")?;
 
-    if hidden {
-        return None;
-    }
-
-    let icon = fields.get("Icon").cloned();
-    let comment = fields.get("Comment").cloned();
-    let categories = fields.get("Categories")
-        .map(|c| c.split(';').filter(|s| !s.is_empty()).map(String::from).collect())
-        .unwrap_or_default();
+    let actions = main
+        .list("Actions")
+        .into_iter()
+        .filter_map(|action_id| {
+            let group = groups.get(&format!("Desktop Action {action_id}"))?;
+            Some(DesktopAction {
+                name: group.localized("Name", &locale)?,
+                exec: group.string("Exec")?,
+                icon: group.string("Icon"),
+                id: action_id,
+            })
+        })
+        .collect();
 
     Some(DesktopEntry {
+        id,
+        path: path.to_path_buf(),
         name,
         exec,
-        icon,
-        comment,
-        categories,
-        no_display,
+        icon: main.string("Icon").filter(|i| !i.is_empty()),
+        comment: main.localized("Comment", &locale),
+        categories: main.list("Categories"),
+        keywords: main.localized_list("Keywords", &locale),
+        no_display: main.boolean("NoDisplay"),
+        startup_wm_class: main.string("StartupWMClass"),
+        terminal: main.boolean("Terminal"),
+        working_dir: main.string("Path").filter(|p| !p.is_empty()),
+        actions,
     })
 }
 
-pub fn search_entries<'a>(entries: &'a [DesktopEntry], query: &str) -> Vec<&'a DesktopEntry> {
-    if query.is_empty() {
-        return entries.iter().take(20).collect();
+/// Resolves the escape sequences allowed in string values.
+fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Splits a `;`-separated list value, honoring `\;` and trailing separators.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => {}
+            },
+            ';' => items.push(unescape_value(&std::mem::take(&mut current))),
+            _ => current.push(c),
+        }
     }
+    items.push(unescape_value(&current));
+    items.retain(|i| !i.is_empty());
+    items
+}
 
-    let query_lower = query.to_lowercase();
+/// Splits an (already unescaped) `Exec` value into arguments. Double quotes
+/// group an argument; inside them `\"`, `` \` ``, `\$` and `\\` are escapes.
+fn tokenize_exec(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
 
-    let mut results: Vec<(usize, &DesktopEntry)> = entries
-        .iter()
-        .filter_map(|entry| {
-            let name_lower = entry.name.to_lowercase();
-            let comment_lower = entry.comment.as_deref().unwrap_or("").to_lowercase();
-            let cats_lower: String = entry.categories.join(" ").to_lowercase();
-
-            // Priority: name starts with > name contains > comment contains > categories contains
-            if name_lower.starts_with(&query_lower) {
-                Some((0, entry))
-            } else if name_lower.contains(&query_lower) {
-                Some((1, entry))
-            } else if comment_lower.contains(&query_lower) {
-                Some((2, entry))
-            } else if cats_lower.contains(&query_lower) {
-                Some((3, entry))
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' => in_quotes = false,
+                '\\' => match chars.next() {
+                    Some(e @ ('"' | '`' | '$' | '\\')) => current.push(e),
+                    Some(other) => {
+                        current.push('\\');
+                        current.push(other);
+                    }
+                    None => current.push('\\'),
+                },
+                _ => current.push(c),
+            }
+        } else if c == '"' {
+            in_quotes = true;
+            in_arg = true;
+        } else if c.is_whitespace() {
+            if in_arg {
+                args.push(std::mem::take(&mut current));
+                in_arg = false;
+            }
+        } else {
+            current.push(c);
+            in_arg = true;
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+fn is_executable(program: &str) -> bool {
+    let runnable = |path: &Path| {
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+
+    if program.contains('/') {
+        return runnable(Path::new(program));
+    }
+    std::env::var("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .any(|dir| runnable(&Path::new(dir).join(program)))
+}
+
+/// Finds the desktop entry for a window class, trying in order: `StartupWMClass`,
+/// the desktop file id (whole or its last dotted segment, so "org.gnome.Nautilus"
+/// matches "nautilus"), then the `Exec` basename.
+pub fn find_entry_for_class<'a>(entries: &'a [DesktopEntry], class: &str) -> Option<&'a DesktopEntry> {
+    let class = class.to_lowercase();
+    let ranks: Vec<Option<usize>> = entries.iter().map(|e| e.class_match(&class)).collect();
+    // One pass per rule, so a weaker match never shadows a stronger one
+    // further down the list
+    (0..=3).find_map(|rank| {
+        ranks
+            .iter()
+            .position(|r| *r == Some(rank))
+            .map(|i| &entries[i])
+    })
+}
+
+pub const MAX_RESULTS: usize = 30;
+const FAVORITES_SHOWN: usize = 20;
+
+// Fuzzy scoring weights
+const SCORE_MATCH: i32 = 16;
+const BONUS_BOUNDARY: i32 = 10;
+const BONUS_FIRST_CHAR: i32 = 6;
+const BONUS_CONSECUTIVE: i32 = 8;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+// How much launch history counts against match quality
+const FRECENCY_WEIGHT: f64 = 12.0;
+
+pub struct FuzzyMatch {
+    pub score: i32,
+    // Char indices into the matched text
+    pub positions: Vec<usize>,
+}
+
+pub struct SearchResult<'a> {
+    pub entry: &'a DesktopEntry,
+    // Char indices into `entry.name` to highlight
+    pub name_matches: Vec<usize>,
+    // Match quality plus frecency; just frecency for the empty query
+    pub score: f64,
+}
+
+/// Case-insensitive subsequence match of `query` in `text`. Picks the
+/// alignment with the best score, rewarding matches at word starts
+/// (after a separator or a camelCase hump) and consecutive runs, and
+/// penalizing gaps. Whitespace in the query is ignored.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let lower_char = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lower_char)
+        .collect();
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|&c| lower_char(c)).collect();
+
+    let (m, n) = (query.len(), lower.len());
+    if m == 0 || m > n {
+        return None;
+    }
+
+    let bonus: Vec<i32> = (0..n)
+        .map(|j| {
+            if j == 0 {
+                return BONUS_BOUNDARY + BONUS_FIRST_CHAR;
+            }
+            let (prev, cur) = (chars[j - 1], chars[j]);
+            if (!prev.is_alphanumeric() && cur.is_alphanumeric())
+                || (prev.is_lowercase() && cur.is_uppercase())
+            {
+                BONUS_BOUNDARY
+            } else if !prev.is_ascii_digit() && cur.is_ascii_digit() {
+                BONUS_BOUNDARY / 2
             } else {
-                None
+                0
             }
         })
         .collect();
 
-    results.sort_by_key(|(priority, _)| *priority);
-    results.into_iter().map(|(_, entry)| entry).take(10).collect()
-}
+    // best[i][j]: best score with query[..=i] matched and query[i] at text[j]
+    let mut best: Vec<Vec<Option<i32>>> = vec![vec![None; n]; m];
+    let mut from = vec![vec![0usize; n]; m];
 
-pub fn launch_app(entry: &DesktopEntry) {
-    let exec = clean_exec(&entry.exec);
-    if exec.is_empty() {
-        return;
+    for j in 0..n {
+        if lower[j] == query[0] {
+            // Slight preference for matches that start early
+            best[0][j] = Some(SCORE_MATCH + bonus[j] - (j as i32).min(PENALTY_GAP_START));
+        }
     }
 
-    // Split into command and args
-    let parts: Vec<&str> = exec.split_whitespace().collect();
-    if parts.is_empty() {
-        return;
+    for i in 1..m {
+        for j in i..n {
+            if lower[j] != query[i] {
+                continue;
+            }
+            let mut best_here: Option<(i32, usize)> = None;
+            for (k, prev) in best[i - 1].iter().enumerate().take(j).skip(i - 1) {
+                let Some(prev) = *prev else { continue };
+                let gap = (j - k - 1) as i32;
+                let transition = if gap == 0 {
+                    BONUS_CONSECUTIVE
+                } else {
+                    -(PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap - 1))
+                };
+                if best_here.is_none_or(|(s, _)| prev + transition > s) {
+                    best_here = Some((prev + transition, k));
+                }
+            }
+            if let Some((score, k)) = best_here {
+                best[i][j] = Some(score + SCORE_MATCH + bonus[j]);
+                from[i][j] = k;
+            }
+        }
     }
 
-    let cmd = parts[0];
-    let args = &parts[1..];
+    // Earliest end position wins ties
+    let (mut j, score) = (0..n)
+        .filter_map(|j| best[m - 1][j].map(|s| (j, s)))
+        .fold(None, |acc: Option<(usize, i32)>, (j, s)| match acc {
+            Some((_, best_s)) if best_s >= s => acc,
+            _ => Some((j, s)),
+        })?;
 
-    let _ = Command::new(cmd)
-        .args(args)
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .spawn();
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(FuzzyMatch { score, positions })
 }
 
-fn clean_exec(exec: &str) -> String {
-    // Remove field codes: %f, %F, %u, %U, %d, %D, %n, %N, %i, %c, %k, %v, %m
-    let mut result = exec.to_string();
-    for code in &["%f", "%F", "%u", "%U", "%d", "%D", "%n", "%N", "%i", "%c", "%k", "%v", "%m"] {
-        result = result.replace(code, "");
+/// Ranks entries by fuzzy match quality plus launch frecency, leaving out
+/// `NoDisplay` ones. An empty query lists the most frecent apps first, then
+/// the rest alphabetically.
+pub fn search_entries<'a>(
+    entries: &'a [DesktopEntry],
+    query: &str,
+    history: &LaunchHistory,
+) -> Vec<SearchResult<'a>> {
+    if query.trim().is_empty() {
+        let mut favorites: Vec<(f64, &DesktopEntry)> = entries
+            .iter()
+            .filter(|entry| !entry.no_display)
+            .map(|entry| (history.frecency(&entry.id), entry))
+            .collect();
+        // Stable sort keeps alphabetical order among equals
+        favorites.sort_by(|a, b| b.0.total_cmp(&a.0));
+        return favorites
+            .into_iter()
+            .take(FAVORITES_SHOWN)
+            .map(|(score, entry)| SearchResult {
+                entry,
+                name_matches: Vec::new(),
+                score,
+            })
+            .collect();
     }
-    // Remove "env " prefix variations
-    if result.starts_with("env ") {
-        // Find the actual command after env vars
-        let parts: Vec<&str> = result.split_whitespace().collect();
-        for (i, part) in parts.iter().enumerate() {
-            if i == 0 {
-                continue; // skip "env"
+
+    let query_lower = query.trim().to_lowercase();
+    // Substring hits in descriptive text score like a short word-start match
+    let substring_score = SCORE_MATCH * 2;
+
+    let mut results: Vec<SearchResult> = entries
+        .iter()
+        .filter(|entry| !entry.no_display)
+        .filter_map(|entry| {
+            let name_match = fuzzy_match(query, &entry.name);
+            let mut score = name_match.as_ref().map(|m| m.score);
+
+            // Ids and program names catch e.g. "code" for "Visual Studio Code"
+            let alt_score = fuzzy_match(query, &entry.id)
+                .into_iter()
+                .chain(entry.exec_basename().and_then(|b| fuzzy_match(query, &b)))
+                .map(|m| m.score * 3 / 4)
+                .max();
+            score = score.max(alt_score);
+
+            let comment_hit = entry
+                .comment
+                .as_deref()
+                .is_some_and(|c| c.to_lowercase().contains(&query_lower))
+                || entry
+                    .keywords
+                    .iter()
+                    .any(|k| k.to_lowercase().contains(&query_lower));
+            let category_hit = entry
+                .categories
+                .iter()
+                .any(|c| c.to_lowercase().contains(&query_lower));
+            if comment_hit {
+                score = score.max(Some(substring_score));
+            } else if category_hit {
+                score = score.max(Some(substring_score / 2));
             }
-            if !part.contains('=') {
-                result = parts[i..].join(" ");
-                break;
+
+            let score =
+                score? as f64 + FRECENCY_WEIGHT * history.frecency(&entry.id).ln_1p();
+            let name_matches = name_match.map(|m| m.positions).unwrap_or_default();
+            Some(SearchResult {
+                entry,
+                name_matches,
+                score,
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(MAX_RESULTS);
+    results
+}
+
+/// Command prefix for running programs in a terminal: the configured one,
+/// else `$TERMINAL`, else the first known terminal found in `PATH`.
+pub fn terminal_command(config: &LaunchConfig) -> Option<Vec<String>> {
+    let configured = config.terminal.trim();
+    if !configured.is_empty() {
+        return Some(tokenize_exec(configured));
+    }
+
+    let known_args = |program: &str| {
+        let name = program.rsplit('/').next().unwrap_or(program);
+        KNOWN_TERMINALS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, args)| args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    };
+
+    if let Ok(terminal) = std::env::var("TERMINAL") {
+        let mut command = tokenize_exec(&terminal);
+        if let Some(program) = command.first().cloned() {
+            if command.len() == 1 {
+                command.extend(known_args(&program).unwrap_or_else(|| vec!["-e".to_string()]));
             }
+            return Some(command);
+        }
+    }
+
+    KNOWN_TERMINALS
+        .iter()
+        .find(|(program, _)| is_executable(program))
+        .map(|(program, args)| {
+            std::iter::once(*program)
+                .chain(args.iter().copied())
+                .map(String::from)
+                .collect()
+        })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    // Id of a desktop action to run instead of the main `Exec`
+    pub action: Option<String>,
+    // Run in a terminal even if the entry doesn't ask for one
+    pub terminal: bool,
+}
+
+pub fn launch_command(
+    entry: &DesktopEntry,
+    options: &LaunchOptions,
+    config: &LaunchConfig,
+) -> Result<LaunchCommand, String> {
+    let exec = match &options.action {
+        Some(id) => {
+            &entry
+                .actions
+                .iter()
+                .find(|a| a.id == *id)
+                .ok_or_else(|| format!("Unknown action {id}"))?
+                .exec
         }
+        None => &entry.exec,
+    };
+
+    let mut args = entry.exec_args(exec);
+    if args.is_empty() {
+        return Err("Empty Exec".to_string());
+    }
+
+    if entry.terminal || options.terminal {
+        let mut command = terminal_command(config).ok_or("No terminal emulator found")?;
+        command.append(&mut args);
+        args = command;
     }
-    result.trim().to_string()
+
+    let working_dir = entry
+        .working_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir());
+
+    Ok(LaunchCommand { args, working_dir })
+}
+
+pub fn launch_app(
+    entry: &DesktopEntry,
+    options: &LaunchOptions,
+    config: &LaunchConfig,
+) -> Result<(), String> {
+    launch_command(entry, options, config)
+        .and_then(|command| launcher::launch(&command, &entry.id, config))
 }
@@ -1,9 +1,17 @@
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
 use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
+use crate::system::pulse::{self, Facility};
+
+const CLIENT_NAME: &str = "crowbar";
+
+/// The loudest any control may set a volume.
+pub const MAX_VOLUME: i32 = 150;
+
 // === Backend detection ===
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -12,6 +20,7 @@ pub enum BackendType {
     None  = 0,
     Pactl = 1,
     Wpctl = 2,
+    Native = 3,
 }
 
 impl BackendType {
@@ -19,6 +28,7 @@ impl BackendType {
         match v {
             1 => Self::Pactl,
             2 => Self::Wpctl,
+            3 => Self::Native,
             _ => Self::None,
         }
     }
@@ -39,7 +49,12 @@ fn set_backend(b: BackendType) {
 }
 
 pub fn detect_backend() -> BackendType {
-    // Try pactl (preferred: full feature set)
+    // Talk to the server directly when it speaks the native protocol
+    // (PulseAudio or pipewire-pulse); no subprocesses needed
+    if with_native(|_| Ok(())).is_ok() {
+        return BackendType::Native;
+    }
+    // Try pactl (full feature set)
     if Command::new("pactl")
         .arg("info")
         .stdout(Stdio::null())
@@ -66,10 +81,107 @@ pub fn detect_backend() -> BackendType {
 
 // === Data types ===
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelSide {
+    Left,
+    Right,
+    // Center, LFE, mono and aux channels
+    Other,
+}
+
 pub struct AudioInfo {
+    // The loudest channel
     pub volume: i32,
     pub muted: bool,
     pub available: bool,
+    // The default sink, by name and description
+    pub sink_name: String,
+    pub sink_description: String,
+    // Per-channel volumes in channel order; empty if the backend can't tell
+    pub channels: Vec<(ChannelSide, i32)>,
+}
+
+impl AudioInfo {
+    fn unavailable() -> Self {
+        Self {
+            volume: 0,
+            muted: false,
+            available: false,
+            sink_name: String::new(),
+            sink_description: String::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Whether there are both left and right channels to balance.
+    pub fn has_balance(&self) -> bool {
+        let has = |side| self.channels.iter().any(|(s, _)| *s == side);
+        has(ChannelSide::Left) && has(ChannelSide::Right)
+    }
+
+    /// From -1.0 (left only) through 0.0 (even) to 1.0 (right only), the
+    /// way PulseAudio defines it: the quieter side relative to the louder.
+    pub fn balance(&self) -> f64 {
+        let loudest = |side| {
+            self.channels
+                .iter()
+                .filter(|(s, _)| *s == side)
+                .map(|(_, v)| *v)
+                .max()
+        };
+        let (Some(left), Some(right)) = (loudest(ChannelSide::Left), loudest(ChannelSide::Right))
+        else {
+            return 0.0;
+        };
+        if left == right {
+            0.0
+        } else if left > right {
+            right as f64 / left as f64 - 1.0
+        } else {
+            1.0 - left as f64 / right as f64
+        }
+    }
+}
+
+/// Per-channel volumes for `sides` with the loudest at `volume` and the
+/// given balance.
+fn balanced_volumes(sides: &[ChannelSide], volume: i32, balance: f64) -> Vec<i32> {
+    let balance = balance.clamp(-1.0, 1.0);
+    sides
+        .iter()
+        .map(|side| match side {
+            ChannelSide::Left if balance > 0.0 => (volume as f64 * (1.0 - balance)).round() as i32,
+            ChannelSide::Right if balance < 0.0 => (volume as f64 * (1.0 + balance)).round() as i32,
+            _ => volume,
+        })
+        .collect()
+}
+
+// Channel volumes for setting the default sink to `percent` without
+// losing its balance; None when a single volume for all channels will do
+fn keep_balance(info: &AudioInfo, percent: i32) -> Option<Vec<i32>> {
+    let balance = info.balance();
+    if balance == 0.0 {
+        return None;
+    }
+    let sides: Vec<ChannelSide> = info.channels.iter().map(|(side, _)| *side).collect();
+    Some(balanced_volumes(&sides, percent, balance))
+}
+
+// Which side a channel named by pactl ("front-left") or PipeWire ("FL") is on
+fn side_from_name(name: &str) -> ChannelSide {
+    let name = name.trim();
+    if name.contains("left")
+        || matches!(name, "FL" | "RL" | "SL" | "FLC" | "RLC" | "TFL" | "TRL" | "TSL" | "FLW" | "FLH")
+    {
+        ChannelSide::Left
+    } else if name.contains("right")
+        || matches!(name, "FR" | "RR" | "SR" | "FRC" | "RRC" | "TFR" | "TRR" | "TSR" | "FRW" | "FRH")
+    {
+        ChannelSide::Right
+    } else {
+        ChannelSide::Other
+    }
 }
 
 pub struct AudioDevice {
@@ -87,6 +199,10 @@ pub struct SourceInfo {
 pub struct SinkInput {
     pub index: u32,
     pub name: String,
+    // Stable per application: the binary, or the lowercased name without one
+    pub app_id: String,
+    // `application.icon_name`, empty if the app didn't set one
+    pub icon_name: String,
     pub sink_name: String,
     pub volume: i32,
     pub muted: bool,
@@ -118,192 +234,603 @@ pub struct AudioProfile {
 
 pub fn get_info() -> AudioInfo {
     match current_backend() {
-        BackendType::Pactl => get_info_pactl(),
-        BackendType::Wpctl => get_info_wpctl(),
-        BackendType::None  => AudioInfo { volume: 0, muted: false, available: false },
+        BackendType::Native => get_info_native(),
+        BackendType::Pactl  => get_info_pactl(),
+        BackendType::Wpctl  => get_info_wpctl(),
+        BackendType::None   => AudioInfo::unavailable(),
     }
 }
 
 pub fn set_volume(percent: i32) {
     match current_backend() {
-        BackendType::Pactl => set_volume_pactl(percent),
-        BackendType::Wpctl => set_volume_wpctl(percent),
-        BackendType::None  => {}
+        BackendType::Native => set_volume_native(percent),
+        BackendType::Pactl  => set_volume_pactl(percent),
+        BackendType::Wpctl  => set_volume_wpctl(percent),
+        BackendType::None   => {}
+    }
+}
+
+/// Sets per-channel volumes so the default sink has `balance` (-1.0 left
+/// to 1.0 right), keeping its loudest channel where it is.
+pub fn set_balance(balance: f64) {
+    let info = get_info();
+    if !info.available || !info.has_balance() {
+        return;
+    }
+    let sides: Vec<ChannelSide> = info.channels.iter().map(|(side, _)| *side).collect();
+    let volumes = balanced_volumes(&sides, info.volume, balance);
+    match current_backend() {
+        BackendType::Native => set_channel_volumes_native(&volumes),
+        BackendType::Pactl  => set_channel_volumes_pactl(&volumes),
+        BackendType::Wpctl  => set_channel_volumes_wpctl(&volumes),
+        BackendType::None   => {}
     }
 }
 
 pub fn toggle_mute() {
     match current_backend() {
-        BackendType::Pactl => toggle_mute_pactl(),
-        BackendType::Wpctl => toggle_mute_wpctl(),
-        BackendType::None  => {}
+        BackendType::Native => toggle_mute_native(),
+        BackendType::Pactl  => toggle_mute_pactl(),
+        BackendType::Wpctl  => toggle_mute_wpctl(),
+        BackendType::None   => {}
     }
 }
 
 pub fn get_source_info() -> SourceInfo {
     match current_backend() {
-        BackendType::Pactl => get_source_info_pactl(),
-        BackendType::Wpctl => get_source_info_wpctl(),
-        BackendType::None  => SourceInfo { volume: 0, muted: false, available: false },
+        BackendType::Native => get_source_info_native(),
+        BackendType::Pactl  => get_source_info_pactl(),
+        BackendType::Wpctl  => get_source_info_wpctl(),
+        BackendType::None   => SourceInfo { volume: 0, muted: false, available: false },
     }
 }
 
 pub fn set_source_volume(percent: i32) {
     match current_backend() {
-        BackendType::Pactl => set_source_volume_pactl(percent),
-        BackendType::Wpctl => set_source_volume_wpctl(percent),
-        BackendType::None  => {}
+        BackendType::Native => set_source_volume_native(percent),
+        BackendType::Pactl  => set_source_volume_pactl(percent),
+        BackendType::Wpctl  => set_source_volume_wpctl(percent),
+        BackendType::None   => {}
     }
 }
 
 pub fn toggle_source_mute() {
     match current_backend() {
-        BackendType::Pactl => toggle_source_mute_pactl(),
-        BackendType::Wpctl => toggle_source_mute_wpctl(),
-        BackendType::None  => {}
+        BackendType::Native => toggle_source_mute_native(),
+        BackendType::Pactl  => toggle_source_mute_pactl(),
+        BackendType::Wpctl  => toggle_source_mute_wpctl(),
+        BackendType::None   => {}
+    }
+}
+
+/// Whether every input device is muted, not just the default one.
+pub fn all_sources_muted() -> bool {
+    match current_backend() {
+        BackendType::Native => all_sources_muted_native(),
+        BackendType::Pactl  => all_sources_muted_pactl(),
+        BackendType::Wpctl  => all_sources_muted_wpctl(),
+        BackendType::None   => false,
+    }
+}
+
+/// Mutes or unmutes every input device, so no microphone is left live.
+pub fn set_all_sources_muted(muted: bool) {
+    match current_backend() {
+        BackendType::Native => set_all_sources_muted_native(muted),
+        BackendType::Pactl  => set_all_sources_muted_pactl(muted),
+        BackendType::Wpctl  => set_all_sources_muted_wpctl(muted),
+        BackendType::None   => {}
     }
 }
 
 pub fn list_sink_inputs() -> Vec<SinkInput> {
     match current_backend() {
-        BackendType::Pactl => list_sink_inputs_pactl(),
+        BackendType::Native => list_sink_inputs_native(),
+        BackendType::Pactl  => list_sink_inputs_pactl(),
         _ => Vec::new(),
     }
 }
 
 pub fn list_source_outputs() -> Vec<SourceOutput> {
     match current_backend() {
-        BackendType::Pactl => list_source_outputs_pactl(),
+        BackendType::Native => list_source_outputs_native(),
+        BackendType::Pactl  => list_source_outputs_pactl(),
         _ => Vec::new(),
     }
 }
 
 pub fn set_sink_input_volume(index: u32, percent: i32) {
-    if current_backend() == BackendType::Pactl {
-        set_sink_input_volume_pactl(index, percent);
+    match current_backend() {
+        BackendType::Native => set_sink_input_volume_native(index, percent),
+        BackendType::Pactl  => set_sink_input_volume_pactl(index, percent),
+        _ => {}
     }
 }
 
 pub fn set_sink_input_mute(index: u32, toggle: &str) {
-    if current_backend() == BackendType::Pactl {
-        set_sink_input_mute_pactl(index, toggle);
+    match current_backend() {
+        BackendType::Native => set_sink_input_mute_native(index, toggle),
+        BackendType::Pactl  => set_sink_input_mute_pactl(index, toggle),
+        _ => {}
     }
 }
 
 pub fn set_source_output_volume(index: u32, percent: i32) {
-    if current_backend() == BackendType::Pactl {
-        set_source_output_volume_pactl(index, percent);
+    match current_backend() {
+        BackendType::Native => set_source_output_volume_native(index, percent),
+        BackendType::Pactl  => set_source_output_volume_pactl(index, percent),
+        _ => {}
     }
 }
 
 pub fn set_source_output_mute(index: u32, toggle: &str) {
-    if current_backend() == BackendType::Pactl {
-        set_source_output_mute_pactl(index, toggle);
+    match current_backend() {
+        BackendType::Native => set_source_output_mute_native(index, toggle),
+        BackendType::Pactl  => set_source_output_mute_pactl(index, toggle),
+        _ => {}
     }
 }
 
 pub fn move_sink_input(index: u32, sink_name: &str) {
-    if current_backend() == BackendType::Pactl {
-        move_sink_input_pactl(index, sink_name);
+    match current_backend() {
+        BackendType::Native => move_sink_input_native(index, sink_name),
+        BackendType::Pactl  => move_sink_input_pactl(index, sink_name),
+        _ => {}
     }
 }
 
 pub fn move_source_output(index: u32, source_name: &str) {
-    if current_backend() == BackendType::Pactl {
-        move_source_output_pactl(index, source_name);
+    match current_backend() {
+        BackendType::Native => move_source_output_native(index, source_name),
+        BackendType::Pactl  => move_source_output_pactl(index, source_name),
+        _ => {}
     }
 }
 
 pub fn list_cards() -> Vec<AudioCard> {
     match current_backend() {
-        BackendType::Pactl => list_cards_pactl(),
+        BackendType::Native => list_cards_native(),
+        BackendType::Pactl  => list_cards_pactl(),
         _ => Vec::new(),
     }
 }
 
 pub fn set_card_profile(card_name: &str, profile: &str) {
-    if current_backend() == BackendType::Pactl {
-        set_card_profile_pactl(card_name, profile);
+    match current_backend() {
+        BackendType::Native => set_card_profile_native(card_name, profile),
+        BackendType::Pactl  => set_card_profile_pactl(card_name, profile),
+        _ => {}
     }
 }
 
 pub fn list_sinks() -> Vec<AudioDevice> {
     match current_backend() {
-        BackendType::Pactl => list_sinks_pactl(),
-        BackendType::Wpctl => list_sinks_wpctl(),
-        BackendType::None  => Vec::new(),
+        BackendType::Native => list_sinks_native(),
+        BackendType::Pactl  => list_sinks_pactl(),
+        BackendType::Wpctl  => list_sinks_wpctl(),
+        BackendType::None   => Vec::new(),
     }
 }
 
 pub fn list_sources() -> Vec<AudioDevice> {
     match current_backend() {
-        BackendType::Pactl => list_sources_pactl(),
-        BackendType::Wpctl => list_sources_wpctl(),
-        BackendType::None  => Vec::new(),
+        BackendType::Native => list_sources_native(),
+        BackendType::Pactl  => list_sources_pactl(),
+        BackendType::Wpctl  => list_sources_wpctl(),
+        BackendType::None   => Vec::new(),
     }
 }
 
 pub fn set_default_sink(name: &str) {
     match current_backend() {
-        BackendType::Pactl => set_default_sink_pactl(name),
-        BackendType::Wpctl => set_default_sink_wpctl(name),
-        BackendType::None  => {}
+        BackendType::Native => set_default_sink_native(name),
+        BackendType::Pactl  => set_default_sink_pactl(name),
+        BackendType::Wpctl  => set_default_sink_wpctl(name),
+        BackendType::None   => {}
     }
 }
 
 pub fn set_default_source(name: &str) {
     match current_backend() {
-        BackendType::Pactl => set_default_source_pactl(name),
-        BackendType::Wpctl => set_default_source_wpctl(name),
-        BackendType::None  => {}
+        BackendType::Native => set_default_source_native(name),
+        BackendType::Pactl  => set_default_source_pactl(name),
+        BackendType::Wpctl  => set_default_source_wpctl(name),
+        BackendType::None   => {}
     }
 }
 
-// === pactl (PulseAudio / pipewire-pulse) backend ===
+// === Native protocol (PulseAudio / pipewire-pulse) backend ===
 
-fn get_info_pactl() -> AudioInfo {
-    let volume = match get_volume_pactl() {
-        Ok(v) => v,
-        Err(_) => return AudioInfo { volume: 0, muted: false, available: false },
-    };
-    AudioInfo {
-        volume,
-        muted: get_muted_pactl(),
-        available: true,
+// Shared by all calls from the UI; the event listener has its own
+static NATIVE: Mutex<Option<pulse::Connection>> = Mutex::new(None);
+
+// Runs `f` on the shared connection, connecting first if there is none or
+// the previous one broke
+fn with_native<T>(
+    f: impl FnOnce(&mut pulse::Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut native = NATIVE.lock().map_err(|e| e.to_string())?;
+    if native.as_ref().map_or(true, |c| c.is_closed()) {
+        *native = Some(pulse::Connection::connect(CLIENT_NAME)?);
+    }
+    let conn = native.as_mut().ok_or("No audio server connection")?;
+    let result = f(conn);
+    if conn.is_closed() {
+        *native = None;
     }
+    result
 }
 
-fn get_volume_pactl() -> Result<i32, String> {
-    let output = Command::new("pactl")
-        .args(["get-sink-volume", "@DEFAULT_SINK@"])
-        .output()
-        .map_err(|e| e.to_string())?;
+// Like `with_native`, for commands whose failure only needs logging
+fn run_native(what: &str, f: impl FnOnce(&mut pulse::Connection) -> Result<(), String>) {
+    if let Err(e) = with_native(f) {
+        eprintln!("Warning: Could not {what}: {e}");
+    }
+}
 
-    let text = String::from_utf8_lossy(&output.stdout);
-    // Parse volume percentage from output like: "Volume: front-left: 55707 /  85% / ..."
-    for part in text.split_whitespace() {
-        if let Some(pct) = part.strip_suffix('%') {
-            if let Ok(vol) = pct.parse::<i32>() {
-                return Ok(vol);
-            }
+// "1" mutes, "0" unmutes, anything else ("toggle") flips `current`
+fn parse_mute(toggle: &str, current: bool) -> bool {
+    match toggle {
+        "1" | "true" | "yes" => true,
+        "0" | "false" | "no" => false,
+        _ => !current,
+    }
+}
+
+// Which side a channel position from the native protocol is on
+fn side_from_position(position: u8) -> ChannelSide {
+    match position {
+        // Front, rear, side, front-of-center and top front/rear left
+        1 | 5 | 8 | 10 | 45 | 48 => ChannelSide::Left,
+        2 | 6 | 9 | 11 | 46 | 49 => ChannelSide::Right,
+        _ => ChannelSide::Other,
+    }
+}
+
+fn get_info_native() -> AudioInfo {
+    match with_native(|c| c.sink(pulse::DEFAULT_SINK)) {
+        Ok(sink) => AudioInfo {
+            volume: pulse::volume_percent(&sink.volume),
+            muted: sink.muted,
+            available: true,
+            channels: sink
+                .channel_map
+                .iter()
+                .zip(&sink.volume)
+                .map(|(&position, &raw)| (side_from_position(position), pulse::volume_percent(&[raw])))
+                .collect(),
+            sink_name: sink.name,
+            sink_description: sink.description,
+        },
+        Err(_) => AudioInfo::unavailable(),
+    }
+}
+
+fn get_source_info_native() -> SourceInfo {
+    match with_native(|c| c.source(pulse::DEFAULT_SOURCE)) {
+        Ok(source) => SourceInfo {
+            volume: pulse::volume_percent(&source.volume),
+            muted: source.muted,
+            available: true,
+        },
+        Err(_) => SourceInfo { volume: 0, muted: false, available: false },
+    }
+}
+
+fn set_volume_native(percent: i32) {
+    let percent = percent.clamp(0, MAX_VOLUME);
+    run_native("set the volume", |c| {
+        let sink = c.sink(pulse::DEFAULT_SINK)?;
+        c.set_sink_volume(sink.index, &pulse::scale_volume(&sink.volume, percent))
+    });
+}
+
+fn set_channel_volumes_native(volumes: &[i32]) {
+    let raw: Vec<u32> = volumes
+        .iter()
+        .map(|&p| (p.clamp(0, MAX_VOLUME) as u64 * pulse::VOLUME_NORM as u64 / 100) as u32)
+        .collect();
+    run_native("set the balance", |c| {
+        let sink = c.sink(pulse::DEFAULT_SINK)?;
+        if sink.volume.len() != raw.len() {
+            return Err("Channels changed".to_string());
         }
+        c.set_sink_volume(sink.index, &raw)
+    });
+}
+
+fn toggle_mute_native() {
+    run_native("toggle mute", |c| {
+        let sink = c.sink(pulse::DEFAULT_SINK)?;
+        c.set_sink_mute(sink.index, !sink.muted)
+    });
+}
+
+fn set_source_volume_native(percent: i32) {
+    let percent = percent.clamp(0, MAX_VOLUME);
+    run_native("set the microphone volume", |c| {
+        let source = c.source(pulse::DEFAULT_SOURCE)?;
+        c.set_source_volume(source.index, &pulse::scale_volume(&source.volume, percent))
+    });
+}
+
+fn toggle_source_mute_native() {
+    run_native("toggle microphone mute", |c| {
+        let source = c.source(pulse::DEFAULT_SOURCE)?;
+        c.set_source_mute(source.index, !source.muted)
+    });
+}
+
+// Inputs that aren't monitors of an output
+fn real_sources_native(c: &mut pulse::Connection) -> Result<Vec<pulse::Device>, String> {
+    let mut sources = c.sources()?;
+    sources.retain(|s| s.monitor_of == pulse::INVALID_INDEX);
+    Ok(sources)
+}
+
+fn all_sources_muted_native() -> bool {
+    with_native(real_sources_native)
+        .is_ok_and(|sources| !sources.is_empty() && sources.iter().all(|s| s.muted))
+}
+
+fn set_all_sources_muted_native(muted: bool) {
+    run_native("mute all microphones", |c| {
+        for source in real_sources_native(c)? {
+            c.set_source_mute(source.index, muted)?;
+        }
+        Ok(())
+    });
+}
+
+// Application name with the same fallbacks as the pactl backend, or None
+// for PipeWire's own internal streams
+fn stream_name(stream: &pulse::Stream) -> Option<String> {
+    let props = &stream.properties;
+    let binary = props.get("application.process.binary").map(String::as_str);
+    if matches!(binary, Some("pipewire" | "pipewire-pulse")) {
+        return None;
+    }
+    let name = props
+        .get("application.name")
+        .or_else(|| props.get("media.name"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or(&stream.name);
+    Some(if name.is_empty() { "Unknown".to_string() } else { name.clone() })
+}
+
+fn app_id(binary: Option<&str>, name: &str) -> String {
+    match binary {
+        Some(binary) if !binary.is_empty() => binary.to_string(),
+        _ => name.to_lowercase(),
     }
-    Err("Could not parse volume".into())
 }
 
-fn get_muted_pactl() -> bool {
-    Command::new("pactl")
-        .args(["get-sink-mute", "@DEFAULT_SINK@"])
+fn list_sink_inputs_native() -> Vec<SinkInput> {
+    let result = with_native(|c| Ok((c.sink_inputs()?, c.sinks()?)));
+    let Ok((inputs, sinks)) = result else {
+        return Vec::new();
+    };
+
+    inputs
+        .iter()
+        .filter_map(|input| {
+            let name = stream_name(input)?;
+            let sink_name = sinks
+                .iter()
+                .find(|s| s.index == input.device)
+                .map(|s| s.name.clone())
+                .unwrap_or_default();
+            let props = &input.properties;
+            Some(SinkInput {
+                index: input.index,
+                app_id: app_id(props.get("application.process.binary").map(String::as_str), &name),
+                icon_name: props.get("application.icon_name").cloned().unwrap_or_default(),
+                name,
+                sink_name,
+                volume: pulse::volume_percent(&input.volume),
+                muted: input.muted,
+            })
+        })
+        .collect()
+}
+
+fn list_source_outputs_native() -> Vec<SourceOutput> {
+    let result = with_native(|c| Ok((c.source_outputs()?, c.sources()?)));
+    let Ok((outputs, sources)) = result else {
+        return Vec::new();
+    };
+
+    outputs
+        .iter()
+        .filter_map(|output| {
+            let name = stream_name(output)?;
+            let source = sources.iter().find(|s| s.index == output.device);
+            // Level meters and visualizers recording a monitor aren't apps
+            // using the microphone
+            if source.is_some_and(|s| s.monitor_of != pulse::INVALID_INDEX) {
+                return None;
+            }
+            Some(SourceOutput {
+                index: output.index,
+                name,
+                source_name: source.map(|s| s.name.clone()).unwrap_or_default(),
+                volume: pulse::volume_percent(&output.volume),
+                muted: output.muted,
+            })
+        })
+        .collect()
+}
+
+fn set_sink_input_volume_native(index: u32, percent: i32) {
+    let percent = percent.clamp(0, MAX_VOLUME);
+    run_native("set the stream volume", |c| {
+        let input = c.sink_input(index)?;
+        c.set_sink_input_volume(index, &pulse::scale_volume(&input.volume, percent))
+    });
+}
+
+fn set_sink_input_mute_native(index: u32, toggle: &str) {
+    run_native("mute the stream", |c| {
+        let input = c.sink_input(index)?;
+        c.set_sink_input_mute(index, parse_mute(toggle, input.muted))
+    });
+}
+
+fn set_source_output_volume_native(index: u32, percent: i32) {
+    let percent = percent.clamp(0, MAX_VOLUME);
+    run_native("set the recording volume", |c| {
+        let output = c.source_output(index)?;
+        c.set_source_output_volume(index, &pulse::scale_volume(&output.volume, percent))
+    });
+}
+
+fn set_source_output_mute_native(index: u32, toggle: &str) {
+    run_native("mute the recording", |c| {
+        let output = c.source_output(index)?;
+        c.set_source_output_mute(index, parse_mute(toggle, output.muted))
+    });
+}
+
+fn move_sink_input_native(index: u32, sink_name: &str) {
+    run_native("move the stream", |c| c.move_sink_input(index, sink_name));
+}
+
+fn move_source_output_native(index: u32, source_name: &str) {
+    run_native("move the recording", |c| c.move_source_output(index, source_name));
+}
+
+fn list_cards_native() -> Vec<AudioCard> {
+    let Ok(cards) = with_native(|c| c.cards()) else {
+        return Vec::new();
+    };
+
+    cards
+        .into_iter()
+        .map(|card| AudioCard {
+            index: card.index,
+            description: card
+                .properties
+                .get("device.description")
+                .cloned()
+                .unwrap_or_else(|| card.name.clone()),
+            name: card.name,
+            active_profile: card.active_profile,
+            profiles: card
+                .profiles
+                .into_iter()
+                .map(|p| AudioProfile {
+                    description: if p.description.is_empty() { p.name.clone() } else { p.description },
+                    name: p.name,
+                    available: p.available,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn set_card_profile_native(card_name: &str, profile: &str) {
+    run_native("set the card profile", |c| c.set_card_profile(card_name, profile));
+}
+
+fn list_devices_native(devices: Vec<pulse::Device>, default_name: &str) -> Vec<AudioDevice> {
+    devices
+        .into_iter()
+        // Monitor sources are virtual loopbacks of sinks
+        .filter(|d| d.monitor_of == pulse::INVALID_INDEX)
+        .map(|d| AudioDevice {
+            is_default: d.name == default_name,
+            description: if d.description.is_empty() { d.name.clone() } else { d.description },
+            name: d.name,
+        })
+        .collect()
+}
+
+fn list_sinks_native() -> Vec<AudioDevice> {
+    with_native(|c| Ok((c.sinks()?, c.server_info()?)))
+        .map(|(sinks, server)| list_devices_native(sinks, &server.default_sink))
+        .unwrap_or_default()
+}
+
+fn list_sources_native() -> Vec<AudioDevice> {
+    with_native(|c| Ok((c.sources()?, c.server_info()?)))
+        .map(|(sources, server)| list_devices_native(sources, &server.default_source))
+        .unwrap_or_default()
+}
+
+fn set_default_sink_native(name: &str) {
+    run_native("set the default output", |c| c.set_default_sink(name));
+}
+
+fn set_default_source_native(name: &str) {
+    run_native("set the default input", |c| c.set_default_source(name));
+}
+
+// === pactl (PulseAudio / pipewire-pulse) backend ===
+
+fn get_info_pactl() -> AudioInfo {
+    let Some(default) = get_default_sink_name_pactl() else {
+        return AudioInfo::unavailable();
+    };
+    let Ok(output) = Command::new("pactl")
+        .args(["--format=json", "list", "sinks"])
         .output()
-        .map(|o| String::from_utf8_lossy(&o.stdout).contains("yes"))
-        .unwrap_or(false)
+    else {
+        return AudioInfo::unavailable();
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) else {
+        return AudioInfo::unavailable();
+    };
+    let Some(sink) = json
+        .as_array()
+        .and_then(|sinks| sinks.iter().find(|s| s.get("name").and_then(|n| n.as_str()) == Some(&default)))
+    else {
+        return AudioInfo::unavailable();
+    };
+
+    let volume = sink.get("volume");
+    // "front-left,front-right", naming the keys of the volume object in order
+    let channels = sink
+        .get("channel_map")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|name| {
+            let percent = value_percent(volume?.get(name)?)?;
+            Some((side_from_name(name), percent))
+        })
+        .collect();
+
+    AudioInfo {
+        volume: volume.map(parse_volume_percent).unwrap_or(0),
+        muted: sink.get("mute").and_then(|v| v.as_bool()).unwrap_or(false),
+        available: true,
+        sink_description: sink
+            .get("description")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&default)
+            .to_string(),
+        sink_name: default,
+        channels,
+    }
 }
 
 fn set_volume_pactl(percent: i32) {
-    let percent = percent.clamp(0, 150);
-    let _ = Command::new("pactl")
-        .args(["set-sink-volume", "@DEFAULT_SINK@", &format!("{percent}%")])
-        .status();
+    let percent = percent.clamp(0, MAX_VOLUME);
+    match keep_balance(&get_info_pactl(), percent) {
+        Some(volumes) => set_channel_volumes_pactl(&volumes),
+        None => {
+            let _ = Command::new("pactl")
+                .args(["set-sink-volume", "@DEFAULT_SINK@", &format!("{percent}%")])
+                .status();
+        }
+    }
+}
+
+fn set_channel_volumes_pactl(volumes: &[i32]) {
+    let mut args = vec!["set-sink-volume".to_string(), "@DEFAULT_SINK@".to_string()];
+    args.extend(volumes.iter().map(|v| format!("{}%", v.clamp(&0, &MAX_VOLUME))));
+    let _ = Command::new("pactl").args(&args).status();
 }
 
 fn toggle_mute_pactl() {
@@ -350,7 +877,7 @@ fn get_source_muted_pactl() -> bool {
 }
 
 fn set_source_volume_pactl(percent: i32) {
-    let percent = percent.clamp(0, 150);
+    let percent = percent.clamp(0, MAX_VOLUME);
     let _ = Command::new("pactl")
         .args(["set-source-volume", "@DEFAULT_SOURCE@", &format!("{percent}%")])
         .status();
@@ -362,20 +889,58 @@ fn toggle_source_mute_pactl() {
         .status();
 }
 
-fn parse_volume_percent(volume_obj: &serde_json::Value) -> i32 {
-    // pactl JSON volume objects have channel entries like {"front-left": {"value": 65536, "value_percent": "100%", ...}}
-    if let Some(obj) = volume_obj.as_object() {
-        for (_channel, val) in obj {
-            if let Some(pct_str) = val.get("value_percent").and_then(|v| v.as_str()) {
-                if let Some(num) = pct_str.strip_suffix('%') {
-                    if let Ok(v) = num.trim().parse::<i32>() {
-                        return v;
-                    }
-                }
+// Names and mute states of the inputs that aren't monitors of an output
+fn source_mutes_pactl() -> Vec<(String, bool)> {
+    let Ok(output) = Command::new("pactl")
+        .args(["--format=json", "list", "sources"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return Vec::new();
+    };
+    json.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|source| {
+            let name = source.get("name")?.as_str()?;
+            if name.ends_with(".monitor") {
+                return None;
             }
-        }
+            let muted = source.get("mute").and_then(|v| v.as_bool()).unwrap_or(false);
+            Some((name.to_string(), muted))
+        })
+        .collect()
+}
+
+fn all_sources_muted_pactl() -> bool {
+    let sources = source_mutes_pactl();
+    !sources.is_empty() && sources.iter().all(|(_, muted)| *muted)
+}
+
+fn set_all_sources_muted_pactl(muted: bool) {
+    let value = if muted { "1" } else { "0" };
+    for (name, _) in source_mutes_pactl() {
+        let _ = Command::new("pactl")
+            .args(["set-source-mute", &name, value])
+            .status();
     }
-    100
+}
+
+fn parse_volume_percent(volume_obj: &serde_json::Value) -> i32 {
+    // pactl JSON volume objects have channel entries like {"front-left": {"value": 65536, "value_percent": "100%", ...}}
+    // The loudest channel counts, so a panned stream still reads as its volume
+    volume_obj
+        .as_object()
+        .and_then(|obj| obj.values().filter_map(value_percent).max())
+        .unwrap_or(100)
+}
+
+// One channel entry of a pactl JSON volume object
+fn value_percent(channel: &serde_json::Value) -> Option<i32> {
+    let pct_str = channel.get("value_percent")?.as_str()?;
+    pct_str.strip_suffix('%')?.trim().parse().ok()
 }
 
 fn list_sink_inputs_pactl() -> Vec<SinkInput> {
@@ -394,11 +959,14 @@ fn list_sink_inputs_pactl() -> Vec<SinkInput> {
     let Some(arr) = json.as_array() else {
         return Vec::new();
     };
+    // Streams name their sink by index
+    let sink_names = device_names_pactl("sinks");
 
     arr.iter()
         .filter_map(|item| {
             let index = item.get("index")?.as_u64()? as u32;
             let props = item.get("properties");
+            let prop = |key: &str| props.and_then(|p| p.get(key)).and_then(|v| v.as_str());
 
             // Get application name with fallbacks
             let name = props
@@ -422,11 +990,15 @@ fn list_sink_inputs_pactl() -> Vec<SinkInput> {
                 return None;
             }
 
-            let sink_name = item
-                .get("sink")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+            let sink_name = match item.get("sink") {
+                Some(serde_json::Value::Number(n)) => n
+                    .as_u64()
+                    .and_then(|n| sink_names.get(&(n as u32)))
+                    .cloned()
+                    .unwrap_or_default(),
+                Some(v) => v.as_str().unwrap_or("").to_string(),
+                None => String::new(),
+            };
 
             let volume = item
                 .get("volume")
@@ -438,7 +1010,36 @@ fn list_sink_inputs_pactl() -> Vec<SinkInput> {
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
 
-            Some(SinkInput { index, name, sink_name, volume, muted })
+            Some(SinkInput {
+                index,
+                app_id: app_id(prop("application.process.binary"), &name),
+                icon_name: prop("application.icon_name").unwrap_or("").to_string(),
+                name,
+                sink_name,
+                volume,
+                muted,
+            })
+        })
+        .collect()
+}
+
+// Sink or source names by index; `kind` is "sinks" or "sources"
+fn device_names_pactl(kind: &str) -> HashMap<u32, String> {
+    let Ok(output) = Command::new("pactl")
+        .args(["--format=json", "list", kind])
+        .output()
+    else {
+        return HashMap::new();
+    };
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return HashMap::new();
+    };
+    json.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|device| {
+            let index = device.get("index")?.as_u64()? as u32;
+            Some((index, device.get("name")?.as_str()?.to_string()))
         })
         .collect()
 }
@@ -459,6 +1060,8 @@ fn list_source_outputs_pactl() -> Vec<SourceOutput> {
     let Some(arr) = json.as_array() else {
         return Vec::new();
     };
+    // Streams name their source by index
+    let source_names = device_names_pactl("sources");
 
     arr.iter()
         .filter_map(|item| {
@@ -485,11 +1088,20 @@ fn list_source_outputs_pactl() -> Vec<SourceOutput> {
                 return None;
             }
 
-            let source_name = item
-                .get("source")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+            let source_name = match item.get("source") {
+                Some(serde_json::Value::Number(n)) => n
+                    .as_u64()
+                    .and_then(|n| source_names.get(&(n as u32)))
+                    .cloned()
+                    .unwrap_or_default(),
+                Some(v) => v.as_str().unwrap_or("").to_string(),
+                None => String::new(),
+            };
+            // Level meters and visualizers recording a monitor aren't apps
+            // using the microphone
+            if source_name.ends_with(".monitor") {
+                return None;
+            }
 
             let volume = item
                 .get("volume")
@@ -507,7 +1119,7 @@ fn list_source_outputs_pactl() -> Vec<SourceOutput> {
 }
 
 fn set_sink_input_volume_pactl(index: u32, percent: i32) {
-    let percent = percent.clamp(0, 150);
+    let percent = percent.clamp(0, MAX_VOLUME);
     let _ = Command::new("pactl")
         .args([
             "set-sink-input-volume",
@@ -524,7 +1136,7 @@ fn set_sink_input_mute_pactl(index: u32, toggle: &str) {
 }
 
 fn set_source_output_volume_pactl(index: u32, percent: i32) {
-    let percent = percent.clamp(0, 150);
+    let percent = percent.clamp(0, MAX_VOLUME);
     let _ = Command::new("pactl")
         .args([
             "set-source-output-volume",
@@ -708,7 +1320,64 @@ fn parse_wpctl_volume(text: &str) -> Option<(i32, bool)> {
     let after = line.strip_prefix("Volume:")?.trim();
     let muted = after.contains("[MUTED]");
     let frac: f64 = after.split_whitespace().next()?.parse().ok()?;
-    Some((((frac * 100.0).round() as i32).clamp(0, 150), muted))
+    Some((((frac * 100.0).round() as i32).clamp(0, MAX_VOLUME), muted))
+}
+
+// The default sink's node id, name and description from `wpctl inspect`
+fn inspect_default_sink_wpctl() -> Option<(String, String, String)> {
+    let output = Command::new("wpctl")
+        .args(["inspect", "@DEFAULT_AUDIO_SINK@"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    // "id 56, type PipeWire:Interface:Node"
+    let id = text.lines().next()?.strip_prefix("id ")?.split(',').next()?.trim().to_string();
+    // Properties follow as `  * node.name = "alsa_output..."`
+    let property = |key: &str| {
+        text.lines().find_map(|line| {
+            let (k, v) = line.split_once(" = ")?;
+            (k.trim_start_matches([' ', '*']) == key).then(|| v.trim().trim_matches('"').to_string())
+        })
+    };
+    let name = property("node.name").unwrap_or_default();
+    let description = property("node.description").unwrap_or_else(|| name.clone());
+    Some((id, name, description))
+}
+
+// Per-channel volumes of node `id` from `pw-dump`, as wpctl-style percentages
+fn channels_wpctl(id: &str) -> Vec<(ChannelSide, i32)> {
+    let Ok(output) = Command::new("pw-dump").arg(id).output() else {
+        return Vec::new();
+    };
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return Vec::new();
+    };
+    let props = json
+        .as_array()
+        .and_then(|objects| objects.first())
+        .and_then(|node| node.pointer("/info/params/Props"))
+        .and_then(|props| props.as_array())
+        .and_then(|props| props.iter().find(|p| p.get("channelVolumes").is_some()));
+    let Some(props) = props else {
+        return Vec::new();
+    };
+
+    let volumes = props.get("channelVolumes").and_then(|v| v.as_array());
+    let names = props.get("channelMap").and_then(|v| v.as_array());
+    let (Some(volumes), Some(names)) = (volumes, names) else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .zip(volumes)
+        .filter_map(|(name, volume)| {
+            // Linear gain; wpctl shows its cube root
+            let linear = volume.as_f64()?;
+            let percent = (linear.cbrt() * 100.0).round() as i32;
+            Some((side_from_name(name.as_str()?), percent))
+        })
+        .collect()
 }
 
 // Parses `wpctl status` Sinks/Sources sections → Vec<AudioDevice>
@@ -767,13 +1436,20 @@ fn get_info_wpctl() -> AudioInfo {
         .args(["get-volume", "@DEFAULT_AUDIO_SINK@"])
         .output()
     else {
-        return AudioInfo { volume: 0, muted: false, available: false };
+        return AudioInfo::unavailable();
     };
     let text = String::from_utf8_lossy(&output.stdout);
-    match parse_wpctl_volume(&text) {
-        Some((volume, muted)) => AudioInfo { volume, muted, available: true },
-        None => AudioInfo { volume: 0, muted: false, available: false },
+    let Some((volume, muted)) = parse_wpctl_volume(&text) else {
+        return AudioInfo::unavailable();
+    };
+
+    let mut info = AudioInfo { volume, muted, available: true, ..AudioInfo::unavailable() };
+    if let Some((id, name, description)) = inspect_default_sink_wpctl() {
+        info.channels = channels_wpctl(&id);
+        info.sink_name = name;
+        info.sink_description = description;
     }
+    info
 }
 
 fn get_source_info_wpctl() -> SourceInfo {
@@ -791,9 +1467,31 @@ fn get_source_info_wpctl() -> SourceInfo {
 }
 
 fn set_volume_wpctl(percent: i32) {
-    let percent = percent.clamp(0, 150);
-    let _ = Command::new("wpctl")
-        .args(["set-volume", "@DEFAULT_AUDIO_SINK@", &format!("{percent}%")])
+    let percent = percent.clamp(0, MAX_VOLUME);
+    // wpctl evens out the channels; keep an off-center balance by hand
+    match keep_balance(&get_info_wpctl(), percent) {
+        Some(volumes) => set_channel_volumes_wpctl(&volumes),
+        None => {
+            let _ = Command::new("wpctl")
+                .args(["set-volume", "@DEFAULT_AUDIO_SINK@", &format!("{percent}%")])
+                .status();
+        }
+    }
+}
+
+// wpctl can't set channels separately, so this goes through pw-cli
+fn set_channel_volumes_wpctl(volumes: &[i32]) {
+    let Some((id, _, _)) = inspect_default_sink_wpctl() else {
+        return;
+    };
+    let linear: Vec<String> = volumes
+        .iter()
+        .map(|&p| format!("{:.6}", (p.clamp(0, MAX_VOLUME) as f64 / 100.0).powi(3)))
+        .collect();
+    let props = format!("{{ channelVolumes: [ {} ] }}", linear.join(", "));
+    let _ = Command::new("pw-cli")
+        .args(["set-param", &id, "Props", &props])
+        .stdout(Stdio::null())
         .status();
 }
 
@@ -804,7 +1502,7 @@ fn toggle_mute_wpctl() {
 }
 
 fn set_source_volume_wpctl(percent: i32) {
-    let percent = percent.clamp(0, 150);
+    let percent = percent.clamp(0, MAX_VOLUME);
     let _ = Command::new("wpctl")
         .args(["set-volume", "@DEFAULT_AUDIO_SOURCE@", &format!("{percent}%")])
         .status();
@@ -816,6 +1514,29 @@ fn toggle_source_mute_wpctl() {
         .status();
 }
 
+fn all_sources_muted_wpctl() -> bool {
+    let sources = list_sources_wpctl();
+    !sources.is_empty()
+        && sources.iter().all(|source| {
+            Command::new("wpctl")
+                .args(["get-volume", &source.name])
+                .output()
+                .ok()
+                .and_then(|o| parse_wpctl_volume(&String::from_utf8_lossy(&o.stdout)))
+                .is_some_and(|(_, muted)| muted)
+        })
+}
+
+fn set_all_sources_muted_wpctl(muted: bool) {
+    let value = if muted { "1" } else { "0" };
+    // Device names from wpctl are node ids
+    for source in list_sources_wpctl() {
+        let _ = Command::new("wpctl")
+            .args(["set-mute", &source.name, value])
+            .status();
+    }
+}
+
 fn list_sinks_wpctl() -> Vec<AudioDevice> {
     let Ok(output) = Command::new("wpctl").arg("status").output() else {
         return Vec::new();
@@ -874,6 +1595,37 @@ impl AudioEventListener {
                         // No audio backend available — retry after a pause
                         std::thread::sleep(Duration::from_secs(3));
                     }
+                    BackendType::Native => {
+                        // Send initial refresh, then stream events
+                        let _ = sender.send_blocking(());
+                        let events = pulse::Connection::connect(CLIENT_NAME)
+                            .and_then(|mut conn| conn.subscribe().map(|_| conn));
+                        match events {
+                            Ok(mut events) => loop {
+                                match events.next_event() {
+                                    Ok(facility) => {
+                                        if !running.load(Ordering::SeqCst) {
+                                            return;
+                                        }
+                                        if facility != Facility::Other {
+                                            let _ = sender.send_blocking(());
+                                        }
+                                    }
+                                    Err(e) => {
+                                        eprintln!("Warning: Audio event stream ended: {e}");
+                                        break;
+                                    }
+                                }
+                            },
+                            Err(e) => eprintln!("Warning: Could not subscribe to audio events: {e}"),
+                        }
+
+                        // Server gone or restarting — retry after a pause
+                        if running.load(Ordering::SeqCst) {
+                            std::thread::sleep(Duration::from_secs(2));
+                            let _ = sender.send_blocking(());
+                        }
+                    }
                     BackendType::Pactl => {
                         // Send initial refresh, then stream events
                         let _ = sender.send_blocking(());
@@ -0,0 +1,350 @@
+use crate::config::{state_dir, AudioConfig};
+use crate::system::audio::{self, AudioDevice};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// The last volume set on each device (by name), persisted as JSON under
+/// `$XDG_STATE_HOME/crowbar/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceVolumes {
+    #[serde(default)]
+    pub sinks: HashMap<String, i32>,
+    #[serde(default)]
+    pub sources: HashMap<String, i32>,
+}
+
+impl DeviceVolumes {
+    pub fn load() -> Self {
+        load_state(DEVICE_VOLUMES, "device volumes")
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        save_state(self, DEVICE_VOLUMES, "device volumes")
+    }
+}
+
+/// What an application's streams were last left at.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamDefaults {
+    pub volume: i32,
+    pub muted: bool,
+    // The sink its streams were moved to, empty while they follow the
+    // default
+    #[serde(default)]
+    pub sink: String,
+}
+
+/// Per-application stream settings (by `SinkInput::app_id`), persisted as
+/// JSON under `$XDG_STATE_HOME/crowbar/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppStreams {
+    #[serde(default)]
+    pub apps: HashMap<String, StreamDefaults>,
+}
+
+impl AppStreams {
+    pub fn load() -> Self {
+        load_state(APP_STREAMS, "app streams")
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        save_state(self, APP_STREAMS, "app streams")
+    }
+}
+
+const DEVICE_VOLUMES: &str = "device_volumes.json";
+const APP_STREAMS: &str = "app_streams.json";
+
+fn load_state<T: Default + for<'de> Deserialize<'de>>(file: &str, what: &str) -> T {
+    let Ok(content) = std::fs::read_to_string(state_dir().join(file)) else {
+        return T::default();
+    };
+    match serde_json::from_str(&content) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Warning: Failed to parse {what}: {e}");
+            T::default()
+        }
+    }
+}
+
+fn save_state<T: Serialize>(state: &T, file: &str, what: &str) -> Result<(), String> {
+    let path: PathBuf = state_dir().join(file);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create state dir: {e}"))?;
+    }
+    let content =
+        serde_json::to_string(state).map_err(|e| format!("Could not serialize {what}: {e}"))?;
+    std::fs::write(&path, content).map_err(|e| format!("Could not write {what}: {e}"))?;
+    Ok(())
+}
+
+/// What the rules make of one direction, sinks or sources.
+#[derive(Default)]
+struct Side {
+    priority: Vec<String>,
+    // Device names present at the last check; None before the first
+    known: Option<HashSet<String>>,
+    default: Option<String>,
+}
+
+/// Switches to preferred devices as they appear, gives each device back
+/// the volume it last had and holds the default sink to its safety limit.
+pub struct DeviceRules {
+    sinks: Side,
+    sources: Side,
+    remember_volume: bool,
+    volumes: DeviceVolumes,
+    limits: HashMap<String, i32>,
+}
+
+impl DeviceRules {
+    pub fn new(config: &AudioConfig) -> Self {
+        Self {
+            sinks: Side {
+                priority: config.sink_priority.clone(),
+                ..Default::default()
+            },
+            sources: Side {
+                priority: config.source_priority.clone(),
+                ..Default::default()
+            },
+            remember_volume: config.remember_volume,
+            volumes: if config.remember_volume {
+                DeviceVolumes::load()
+            } else {
+                DeviceVolumes::default()
+            },
+            limits: config.limits.clone(),
+        }
+    }
+
+    /// Looks at the devices after an audio event: switches to a newly
+    /// appeared device that beats the others in the priority list,
+    /// restores or records per-device volumes, and lowers the default sink
+    /// to its limit.
+    pub fn apply(&mut self) {
+        let mut changed = false;
+
+        if let Some(name) = newly_preferred(&mut self.sinks, &audio::list_sinks()) {
+            audio::set_default_sink(&name);
+            for input in audio::list_sink_inputs() {
+                if input.sink_name != name {
+                    audio::move_sink_input(input.index, &name);
+                }
+            }
+        }
+        if let Some(name) = newly_preferred(&mut self.sources, &audio::list_sources()) {
+            audio::set_default_source(&name);
+            for output in audio::list_source_outputs() {
+                if output.source_name != name {
+                    audio::move_source_output(output.index, &name);
+                }
+            }
+        }
+
+        if self.remember_volume {
+            let sink = default_name(&audio::list_sinks());
+            changed |= track_volume(
+                &mut self.sinks.default,
+                sink,
+                &mut self.volumes.sinks,
+                || audio::get_info().volume,
+                audio::set_volume,
+            );
+            let source = default_name(&audio::list_sources());
+            changed |= track_volume(
+                &mut self.sources.default,
+                source,
+                &mut self.volumes.sources,
+                || audio::get_source_info().volume,
+                audio::set_source_volume,
+            );
+        }
+
+        // Whatever raised it, a limited device doesn't stay above its limit
+        if !self.limits.is_empty() {
+            let info = audio::get_info();
+            let limit = device_limit(&self.limits, &info);
+            if let Some(limit) = limit.filter(|&l| info.available && info.volume > l) {
+                audio::set_volume(limit);
+            }
+        }
+
+        if changed {
+            if let Err(e) = self.volumes.save() {
+                eprintln!("Warning: {e}");
+            }
+        }
+    }
+}
+
+/// The lowest of `limits` whose key appears (case-insensitively) in the
+/// sink's name or description.
+pub fn device_limit(limits: &HashMap<String, i32>, info: &audio::AudioInfo) -> Option<i32> {
+    let name = info.sink_name.to_lowercase();
+    let description = info.sink_description.to_lowercase();
+    limits
+        .iter()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            name.contains(&key) || description.contains(&key)
+        })
+        .map(|(_, limit)| (*limit).max(0))
+        .min()
+}
+
+/// The `sink_priority` entry the default sink matches, if any.
+pub fn active_sink_rule(priority: &[String]) -> Option<String> {
+    active_rule(priority, &audio::list_sinks())
+}
+
+/// The `source_priority` entry the default source matches, if any.
+pub fn active_source_rule(priority: &[String]) -> Option<String> {
+    active_rule(priority, &audio::list_sources())
+}
+
+// Where `device` ranks in `priority`: the index of the first entry found
+// (case-insensitively) in its name or description
+fn rank(priority: &[String], device: &AudioDevice) -> Option<usize> {
+    let name = device.name.to_lowercase();
+    let description = device.description.to_lowercase();
+    priority.iter().position(|pattern| {
+        let pattern = pattern.to_lowercase();
+        !pattern.is_empty() && (name.contains(&pattern) || description.contains(&pattern))
+    })
+}
+
+// The device to switch to, if one that just appeared outranks every other.
+// Devices that were already there are left alone so a manual choice
+// sticks until something is plugged in.
+fn newly_preferred(side: &mut Side, devices: &[AudioDevice]) -> Option<String> {
+    let names: HashSet<String> = devices.iter().map(|d| d.name.clone()).collect();
+    let known = side.known.replace(names);
+    if side.priority.is_empty() {
+        return None;
+    }
+
+    let best = devices
+        .iter()
+        .filter_map(|d| Some((rank(&side.priority, d)?, d)))
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, d)| d)?;
+    // At startup everything counts as new
+    let appeared = known.is_none_or(|known| !known.contains(&best.name));
+    (appeared && !best.is_default).then(|| best.name.clone())
+}
+
+fn default_name(devices: &[AudioDevice]) -> Option<String> {
+    devices.iter().find(|d| d.is_default).map(|d| d.name.clone())
+}
+
+// Restores the remembered volume when the default device changes and
+// records it otherwise. True when `remembered` changed.
+fn track_volume(
+    last_default: &mut Option<String>,
+    default: Option<String>,
+    remembered: &mut HashMap<String, i32>,
+    volume: impl Fn() -> i32,
+    set_volume: impl Fn(i32),
+) -> bool {
+    let Some(default) = default else {
+        return false;
+    };
+    let switched = last_default.as_deref() != Some(default.as_str());
+    *last_default = Some(default.clone());
+
+    if switched {
+        if let Some(&saved) = remembered.get(&default) {
+            if saved != volume() {
+                set_volume(saved);
+            }
+            return false;
+        }
+    }
+    let current = volume();
+    remembered.insert(default, current) != Some(current)
+}
+
+fn active_rule(priority: &[String], devices: &[AudioDevice]) -> Option<String> {
+    let default = devices.iter().find(|d| d.is_default)?;
+    rank(priority, default).map(|i| priority[i].clone())
+}
+
+/// Gives an application's new streams the volume and mute state its
+/// earlier ones were left with, and the output they were moved to if they
+/// were taken off the default.
+pub struct StreamMemory {
+    // Stream indices seen at the last check; None before the first
+    known: Option<HashSet<u32>>,
+    streams: AppStreams,
+}
+
+impl StreamMemory {
+    pub fn new() -> Self {
+        Self {
+            known: None,
+            streams: AppStreams::load(),
+        }
+    }
+
+    /// Restores remembered settings on streams that appeared since the last
+    /// call and remembers the settings of the others. Streams already
+    /// playing at startup are only remembered.
+    pub fn apply(&mut self) {
+        let inputs = audio::list_sink_inputs();
+        let indices: HashSet<u32> = inputs.iter().map(|i| i.index).collect();
+        let known = self.known.replace(indices);
+        let mut sinks = None;
+        let mut changed = false;
+
+        for input in &inputs {
+            let is_new = known.as_ref().is_some_and(|k| !k.contains(&input.index));
+            match self.streams.apps.get(&input.app_id) {
+                Some(saved) if is_new => {
+                    if saved.volume != input.volume {
+                        audio::set_sink_input_volume(input.index, saved.volume);
+                    }
+                    if saved.muted != input.muted {
+                        audio::set_sink_input_mute(input.index, if saved.muted { "1" } else { "0" });
+                    }
+                    // Only to an output that's still around
+                    let sinks = sinks.get_or_insert_with(audio::list_sinks);
+                    if !saved.sink.is_empty()
+                        && saved.sink != input.sink_name
+                        && sinks.iter().any(|s| s.name == saved.sink)
+                    {
+                        audio::move_sink_input(input.index, &saved.sink);
+                    }
+                }
+                _ => {
+                    // A stream on the default is following it, and should go
+                    // on following whatever the default becomes
+                    let sinks = sinks.get_or_insert_with(audio::list_sinks);
+                    let on_default = sinks
+                        .iter()
+                        .any(|s| s.is_default && s.name == input.sink_name);
+                    let current = StreamDefaults {
+                        volume: input.volume,
+                        muted: input.muted,
+                        sink: if on_default {
+                            String::new()
+                        } else {
+                            input.sink_name.clone()
+                        },
+                    };
+                    let previous = self.streams.apps.insert(input.app_id.clone(), current.clone());
+                    changed |= previous != Some(current);
+                }
+            }
+        }
+
+        if changed {
+            if let Err(e) = self.streams.save() {
+                eprintln!("Warning: {e}");
+            }
+        }
+    }
+}
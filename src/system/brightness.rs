@@ -1,4 +1,6 @@
 use std::fs;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -69,6 +71,66 @@ pub fn set_brightness(_device: &str, percent: i32) {
         .status();
 }
 
+/// Sends the brightness percentage of `device` whenever it changes,
+/// including from hardware keys, until the receiver is dropped.
+pub fn watch(device: &str, sender: async_channel::Sender<i32>) {
+    let device_path = PathBuf::from(BASE_PATH).join(device);
+    std::thread::spawn(move || {
+        let Some(max) = read_sysfs_int(&device_path.join("max_brightness")).filter(|m| *m > 0)
+        else {
+            return;
+        };
+        let file = match fs::File::open(device_path.join("actual_brightness")) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Warning: Could not watch brightness: {e}");
+                return;
+            }
+        };
+
+        let read_percent = || {
+            let mut buf = [0u8; 32];
+            let len = file.read_at(&mut buf, 0).ok()?;
+            let value: i32 = std::str::from_utf8(&buf[..len]).ok()?.trim().parse().ok()?;
+            Some(value * 100 / max)
+        };
+
+        let mut last = read_percent();
+        loop {
+            // The backlight class flags actual_brightness on every change;
+            // the timeout covers drivers that don't
+            let mut fds = libc::pollfd {
+                fd: file.as_raw_fd(),
+                events: libc::POLLPRI | libc::POLLERR,
+                revents: 0,
+            };
+            // SAFETY: `fds` is one valid pollfd that outlives the call
+            if unsafe { libc::poll(&mut fds, 1, 1000) } < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                // Anything else would fail again at once, spinning the loop
+                eprintln!("Warning: Could not poll brightness: {err}");
+                return;
+            }
+
+            let percent = read_percent();
+            if percent != last {
+                last = percent;
+                if let Some(percent) = percent {
+                    if sender.send_blocking(percent).is_err() {
+                        return;
+                    }
+                }
+            }
+            if sender.is_closed() {
+                return;
+            }
+        }
+    });
+}
+
 pub fn detect_night_backend() -> NightModeBackend {
     if Command::new("which")
         .arg("wlsunset")
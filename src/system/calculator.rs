@@ -0,0 +1,529 @@
+// Arithmetic and unit conversion for the app finder's inline calculator
+
+/// Evaluates `expr` if it's an arithmetic expression with at least one
+/// operator or function, so plain numbers and words don't count.
+/// Supports `+ - * / % ^` (also `**`, `×`, `÷`), parentheses, `pi`, `e`,
+/// `tau` and common functions like `sqrt(2)`.
+pub fn evaluate(expr: &str) -> Option<f64> {
+    let mut parser = Parser::new(expr);
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() || parser.operations == 0 || !value.is_finite() {
+        return None;
+    }
+    Some(value)
+}
+
+/// Like `evaluate`, but also accepts a bare number.
+fn amount(expr: &str) -> Option<f64> {
+    let mut parser = Parser::new(expr);
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    (parser.pos == parser.chars.len() && value.is_finite()).then_some(value)
+}
+
+/// Formats a result without float noise: integers as-is, others with up
+/// to 10 decimals, and very large or small values in scientific notation.
+pub fn format_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let abs = value.abs();
+    if !(1e-6..1e15).contains(&abs) {
+        let formatted = format!("{value:.6e}");
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        return format!("{}e{exponent}", trim_zeros(mantissa));
+    }
+    if value.fract() == 0.0 {
+        return format!("{value:.0}");
+    }
+    trim_zeros(&format!("{value:.10}")).to_string()
+}
+
+fn trim_zeros(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    // Operators and function calls seen
+    operations: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            operations: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') || self.eat('\u{2212}') {
+                value -= self.term()?;
+            } else {
+                return Some(value);
+            }
+            self.operations += 1;
+        }
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        loop {
+            if self.chars.get(self.pos..self.pos + 2) == Some(&['*', '*']) {
+                return Some(value);
+            }
+            if self.eat('*') || self.eat('\u{00D7}') {
+                value *= self.unary()?;
+            } else if self.eat('/') || self.eat('\u{00F7}') {
+                value /= self.unary()?;
+            } else if self.eat('%') {
+                value %= self.unary()?;
+            } else {
+                return Some(value);
+            }
+            self.operations += 1;
+        }
+    }
+
+    // unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> Option<f64> {
+        if self.eat('-') || self.eat('\u{2212}') {
+            return self.unary().map(|v| -v);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    // power := atom (('^' | '**') unary)?, right-associative
+    fn power(&mut self) -> Option<f64> {
+        let base = self.atom()?;
+        self.skip_whitespace();
+        let is_power = if self.chars.get(self.pos..self.pos + 2) == Some(&['*', '*']) {
+            self.pos += 2;
+            true
+        } else {
+            self.eat('^')
+        };
+        if !is_power {
+            return Some(base);
+        }
+        self.operations += 1;
+        Some(base.powf(self.unary()?))
+    }
+
+    // atom := number | constant | function '(' expression ')' | '(' expression ')'
+    fn atom(&mut self) -> Option<f64> {
+        let c = self.peek()?;
+        if c == '(' {
+            self.pos += 1;
+            let value = self.expression()?;
+            return self.eat(')').then_some(value);
+        }
+        if c.is_ascii_digit() || c == '.' {
+            return self.number();
+        }
+        if !c.is_alphabetic() {
+            return None;
+        }
+
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_alphanumeric())
+        {
+            self.pos += 1;
+        }
+        let name: String = self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .to_lowercase();
+
+        match name.as_str() {
+            "pi" => return Some(std::f64::consts::PI),
+            "e" => return Some(std::f64::consts::E),
+            "tau" => return Some(std::f64::consts::TAU),
+            _ => {}
+        }
+        self.operations += 1;
+
+        if !self.eat('(') {
+            return None;
+        }
+        let arg = self.expression()?;
+        if !self.eat(')') {
+            return None;
+        }
+        let value = match name.as_str() {
+            "sqrt" => arg.sqrt(),
+            "cbrt" => arg.cbrt(),
+            "abs" => arg.abs(),
+            "round" => arg.round(),
+            "floor" => arg.floor(),
+            "ceil" => arg.ceil(),
+            "ln" => arg.ln(),
+            "log" => arg.log10(),
+            "log2" => arg.log2(),
+            "exp" => arg.exp(),
+            "sin" => arg.sin(),
+            "cos" => arg.cos(),
+            "tan" => arg.tan(),
+            "asin" => arg.asin(),
+            "acos" => arg.acos(),
+            "atan" => arg.atan(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Decimal with optional exponent, e.g. `1.5`, `.5`, `2e-3`
+    fn number(&mut self) -> Option<f64> {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+            self.pos += 1;
+        }
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            let digits_at = match self.chars.get(self.pos + 1) {
+                Some('+' | '-') => self.pos + 2,
+                _ => self.pos + 1,
+            };
+            if self
+                .chars
+                .get(digits_at)
+                .is_some_and(|c| c.is_ascii_digit())
+            {
+                self.pos = digits_at;
+                while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Length,
+    Mass,
+    Volume,
+    Time,
+    Data,
+    Speed,
+    Temperature,
+}
+
+struct Unit {
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    // base = value * factor + offset
+    factor: f64,
+    offset: f64,
+}
+
+const fn unit(
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    factor: f64,
+) -> Unit {
+    Unit {
+        symbol,
+        aliases,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+// Base units: m, kg, l, s, byte, m/s, K
+const UNITS: &[Unit] = &[
+    unit(
+        "mm",
+        &["millimeter", "millimeters", "millimetre", "millimetres"],
+        Dimension::Length,
+        0.001,
+    ),
+    unit(
+        "cm",
+        &["centimeter", "centimeters", "centimetre", "centimetres"],
+        Dimension::Length,
+        0.01,
+    ),
+    unit(
+        "m",
+        &["meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    unit(
+        "km",
+        &["kilometer", "kilometers", "kilometre", "kilometres"],
+        Dimension::Length,
+        1000.0,
+    ),
+    unit("in", &["inch", "inches", "\""], Dimension::Length, 0.0254),
+    unit("ft", &["foot", "feet", "'"], Dimension::Length, 0.3048),
+    unit("yd", &["yard", "yards"], Dimension::Length, 0.9144),
+    unit("mi", &["mile", "miles"], Dimension::Length, 1609.344),
+    unit(
+        "nmi",
+        &["nauticalmile", "nauticalmiles"],
+        Dimension::Length,
+        1852.0,
+    ),
+    unit("mg", &["milligram", "milligrams"], Dimension::Mass, 1e-6),
+    unit("g", &["gram", "grams"], Dimension::Mass, 0.001),
+    unit(
+        "kg",
+        &["kilogram", "kilograms", "kilo", "kilos"],
+        Dimension::Mass,
+        1.0,
+    ),
+    unit(
+        "t",
+        &["tonne", "tonnes", "ton", "tons"],
+        Dimension::Mass,
+        1000.0,
+    ),
+    unit("oz", &["ounce", "ounces"], Dimension::Mass, 0.028349523125),
+    unit(
+        "lb",
+        &["lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.45359237,
+    ),
+    unit("st", &["stone", "stones"], Dimension::Mass, 6.35029318),
+    unit(
+        "ml",
+        &["milliliter", "milliliters", "millilitre", "millilitres"],
+        Dimension::Volume,
+        0.001,
+    ),
+    unit(
+        "l",
+        &["liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1.0,
+    ),
+    unit(
+        "m3",
+        &["m\u{00B3}", "cubicmeter", "cubicmeters"],
+        Dimension::Volume,
+        1000.0,
+    ),
+    unit(
+        "tsp",
+        &["teaspoon", "teaspoons"],
+        Dimension::Volume,
+        0.00492892159375,
+    ),
+    unit(
+        "tbsp",
+        &["tablespoon", "tablespoons"],
+        Dimension::Volume,
+        0.01478676478125,
+    ),
+    unit(
+        "floz",
+        &["fl oz", "fluidounce", "fluidounces"],
+        Dimension::Volume,
+        0.0295735295625,
+    ),
+    unit("cup", &["cups"], Dimension::Volume, 0.2365882365),
+    unit("pt", &["pint", "pints"], Dimension::Volume, 0.473176473),
+    unit("qt", &["quart", "quarts"], Dimension::Volume, 0.946352946),
+    unit(
+        "gal",
+        &["gallon", "gallons"],
+        Dimension::Volume,
+        3.785411784,
+    ),
+    unit(
+        "ms",
+        &["millisecond", "milliseconds"],
+        Dimension::Time,
+        0.001,
+    ),
+    unit(
+        "s",
+        &["sec", "secs", "second", "seconds"],
+        Dimension::Time,
+        1.0,
+    ),
+    unit("min", &["mins", "minute", "minutes"], Dimension::Time, 60.0),
+    unit(
+        "h",
+        &["hr", "hrs", "hour", "hours"],
+        Dimension::Time,
+        3600.0,
+    ),
+    unit("d", &["day", "days"], Dimension::Time, 86_400.0),
+    unit("wk", &["week", "weeks"], Dimension::Time, 604_800.0),
+    unit("yr", &["year", "years"], Dimension::Time, 31_557_600.0),
+    unit("bit", &["bits"], Dimension::Data, 0.125),
+    unit("B", &["byte", "bytes"], Dimension::Data, 1.0),
+    unit("KB", &["kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    unit("MB", &["megabyte", "megabytes"], Dimension::Data, 1e6),
+    unit("GB", &["gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    unit("TB", &["terabyte", "terabytes"], Dimension::Data, 1e12),
+    unit("KiB", &["kibibyte", "kibibytes"], Dimension::Data, 1024.0),
+    unit(
+        "MiB",
+        &["mebibyte", "mebibytes"],
+        Dimension::Data,
+        1_048_576.0,
+    ),
+    unit(
+        "GiB",
+        &["gibibyte", "gibibytes"],
+        Dimension::Data,
+        1_073_741_824.0,
+    ),
+    unit(
+        "TiB",
+        &["tebibyte", "tebibytes"],
+        Dimension::Data,
+        1_099_511_627_776.0,
+    ),
+    unit("m/s", &["mps"], Dimension::Speed, 1.0),
+    unit("km/h", &["kmh", "kph"], Dimension::Speed, 1.0 / 3.6),
+    unit("mph", &["mi/h"], Dimension::Speed, 0.44704),
+    unit(
+        "kn",
+        &["knot", "knots", "kt"],
+        Dimension::Speed,
+        1852.0 / 3600.0,
+    ),
+    unit("ft/s", &["fps"], Dimension::Speed, 0.3048),
+    Unit {
+        symbol: "\u{00B0}C",
+        aliases: &["c", "celsius", "degc"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        symbol: "\u{00B0}F",
+        aliases: &["f", "fahrenheit", "degf"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 459.67 * 5.0 / 9.0,
+    },
+    unit("K", &["kelvin"], Dimension::Temperature, 1.0),
+];
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let lower = name.to_lowercase();
+    let lower = lower.strip_prefix('\u{00B0}').unwrap_or(&lower);
+    UNITS.iter().find(|u| {
+        u.symbol.to_lowercase() == lower
+            || u.symbol.trim_start_matches('\u{00B0}').to_lowercase() == lower
+            || u.aliases.contains(&lower)
+    })
+}
+
+pub struct Conversion {
+    pub value: f64,
+    pub unit: &'static str,
+}
+
+pub struct ConversionResult {
+    pub amount: f64,
+    pub from: &'static str,
+    pub to: Vec<Conversion>,
+}
+
+/// Parses `<amount> <unit> [to|in|as <unit>]`, e.g. `10 km to mi`,
+/// `72f in c` or `(2+3) GiB`. Without a target unit, converts to every
+/// other unit of the same kind. The amount may be an expression.
+pub fn convert(query: &str) -> Option<ConversionResult> {
+    let query = query.trim();
+    let (source, target) = [" to ", " in ", " as ", " -> ", "->"]
+        .iter()
+        .find_map(|sep| query.rsplit_once(sep))
+        .map(|(source, target)| (source, Some(target)))
+        .unwrap_or((query, None));
+
+    let (amount, from) = split_amount(source)?;
+    let to: Vec<&Unit> = match target {
+        Some(name) => {
+            let unit = find_unit(name)?;
+            if unit.dimension != from.dimension {
+                return None;
+            }
+            vec![unit]
+        }
+        None => UNITS
+            .iter()
+            .filter(|u| u.dimension == from.dimension && u.symbol != from.symbol)
+            .collect(),
+    };
+
+    let base = amount * from.factor + from.offset;
+    Some(ConversionResult {
+        amount,
+        from: from.symbol,
+        to: to
+            .into_iter()
+            .map(|unit| Conversion {
+                value: (base - unit.offset) / unit.factor,
+                unit: unit.symbol,
+            })
+            .collect(),
+    })
+}
+
+// The leftmost split where the tail is a unit and the head an amount
+fn split_amount(source: &str) -> Option<(f64, &'static Unit)> {
+    source.char_indices().skip(1).find_map(|(i, _)| {
+        let unit = find_unit(&source[i..])?;
+        amount(&source[..i]).map(|value| (value, unit))
+    })
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zbus::zvariant::{OwnedValue, Value};
+
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+
+// `(ia{sv}av)`: id, properties, children as variants
+type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToggleType {
+    None,
+    Checkmark,
+    Radio,
+}
+
+/// One `com.canonical.dbusmenu` entry with its children. Properties the
+/// item leaves out take the defaults from the spec.
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub id: i32,
+    pub label: String,
+    pub enabled: bool,
+    pub visible: bool,
+    pub separator: bool,
+    pub toggle: ToggleType,
+    pub toggled: bool,
+    pub icon_name: Option<String>,
+    // PNG data
+    pub icon_data: Option<Vec<u8>>,
+    pub children: Vec<MenuItem>,
+}
+
+impl MenuItem {
+    pub fn is_submenu(&self) -> bool {
+        !self.children.is_empty()
+    }
+}
+
+/// Fetches the whole menu tree, letting the app update it first.
+pub async fn fetch_menu(
+    conn: &zbus::Connection,
+    service: &str,
+    path: &str,
+) -> zbus::Result<MenuItem> {
+    let proxy = zbus::Proxy::new(conn, service, path, MENU_INTERFACE).await?;
+
+    // Optional; some apps build the menu lazily here
+    let _: zbus::Result<bool> = proxy.call("AboutToShow", &(0i32,)).await;
+
+    let (_revision, (id, properties, children)): (u32, Layout) = proxy
+        .call("GetLayout", &(0i32, -1i32, Vec::<&str>::new()))
+        .await?;
+
+    Ok(build_item(
+        id,
+        properties.iter().map(|(key, value)| (key.as_str(), &**value)),
+        children.iter().filter_map(|child| parse_item(child)).collect(),
+    ))
+}
+
+/// Reports a click on item `id`.
+pub async fn send_clicked(
+    conn: &zbus::Connection,
+    service: &str,
+    path: &str,
+    id: i32,
+) -> zbus::Result<()> {
+    let proxy = zbus::Proxy::new(conn, service, path, MENU_INTERFACE).await?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default();
+    proxy
+        .call_noreply("Event", &(id, "clicked", Value::from(0i32), timestamp))
+        .await
+}
+
+// A `(ia{sv}av)` layout node, possibly wrapped in a variant
+fn parse_item(value: &Value) -> Option<MenuItem> {
+    let value = match value {
+        Value::Value(inner) => inner.as_ref(),
+        other => other,
+    };
+    let Value::Structure(structure) = value else {
+        return None;
+    };
+    let [Value::I32(id), Value::Dict(properties), Value::Array(children)] = structure.fields() else {
+        return None;
+    };
+
+    let properties = properties.iter().filter_map(|(key, value)| match key {
+        Value::Str(key) => Some((key.as_str(), value)),
+        _ => None,
+    });
+    let children = children.inner().iter().filter_map(parse_item).collect();
+    Some(build_item(*id, properties, children))
+}
+
+fn build_item<'a>(
+    id: i32,
+    properties: impl Iterator<Item = (&'a str, &'a Value<'a>)>,
+    children: Vec<MenuItem>,
+) -> MenuItem {
+    let mut item = MenuItem {
+        id,
+        label: String::new(),
+        enabled: true,
+        visible: true,
+        separator: false,
+        toggle: ToggleType::None,
+        toggled: false,
+        icon_name: None,
+        icon_data: None,
+        children,
+    };
+
+    for (key, value) in properties {
+        let value = match value {
+            Value::Value(inner) => inner.as_ref(),
+            other => other,
+        };
+        match (key, value) {
+            ("label", Value::Str(label)) => item.label = strip_mnemonic(label),
+            ("enabled", Value::Bool(enabled)) => item.enabled = *enabled,
+            ("visible", Value::Bool(visible)) => item.visible = *visible,
+            ("type", Value::Str(kind)) => item.separator = kind.as_str() == "separator",
+            ("toggle-type", Value::Str(kind)) => {
+                item.toggle = match kind.as_str() {
+                    "checkmark" => ToggleType::Checkmark,
+                    "radio" => ToggleType::Radio,
+                    _ => ToggleType::None,
+                }
+            }
+            ("toggle-state", Value::I32(state)) => item.toggled = *state == 1,
+            ("icon-name", Value::Str(name)) if !name.is_empty() => {
+                item.icon_name = Some(name.to_string())
+            }
+            ("icon-data", Value::Array(bytes)) => {
+                let data: Vec<u8> = bytes
+                    .inner()
+                    .iter()
+                    .filter_map(|b| match b {
+                        Value::U8(b) => Some(*b),
+                        _ => None,
+                    })
+                    .collect();
+                if !data.is_empty() {
+                    item.icon_data = Some(data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    item
+}
+
+/// Drops the `_` access-key markers: `_File` reads "File", `__` is a literal `_`.
+fn strip_mnemonic(label: &str) -> String {
+    let mut result = String::with_capacity(label.len());
+    let mut chars = label.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '_' {
+            if chars.peek() == Some(&'_') {
+                chars.next();
+                result.push('_');
+            }
+            continue;
+        }
+        result.push(c);
+    }
+    result
+}
@@ -0,0 +1,283 @@
+use crate::system::app_finder::{self, DesktopEntry, ParseEnv};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, Once};
+use std::time::Duration;
+
+// Package installs touch many files at once; apply them as one update
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+static START: Once = Once::new();
+static SNAPSHOT: Mutex<Option<Arc<Vec<DesktopEntry>>>> = Mutex::new(None);
+static SUBSCRIBERS: Mutex<Vec<async_channel::Sender<()>>> = Mutex::new(Vec::new());
+
+/// The indexed desktop entries, sorted by name. Starts the background scan
+/// on first use and is empty until it finishes.
+pub fn entries() -> Arc<Vec<DesktopEntry>> {
+    START.call_once(start);
+    SNAPSHOT
+        .lock()
+        .ok()
+        .and_then(|snapshot| snapshot.clone())
+        .unwrap_or_default()
+}
+
+/// Notified after every index update, including the initial scan.
+pub fn subscribe() -> async_channel::Receiver<()> {
+    START.call_once(start);
+    let (sender, receiver) = async_channel::unbounded();
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push(sender);
+    }
+    receiver
+}
+
+fn publish(entries: Vec<DesktopEntry>) {
+    if let Ok(mut snapshot) = SNAPSHOT.lock() {
+        *snapshot = Some(Arc::new(entries));
+    }
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        // Drop subscribers whose receiver is gone
+        subscribers.retain(|sender| sender.try_send(()).is_ok());
+    }
+}
+
+fn start() {
+    std::thread::spawn(|| {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+            if let Ok(event) = res {
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    let _ = tx.send(event.paths);
+                }
+            }
+        });
+
+        let mut index = Index::new();
+        let mut watched = HashSet::new();
+        // Watch before scanning so nothing installed meanwhile is missed
+        let mut watcher = match watcher {
+            Ok(mut watcher) => {
+                watch_dirs(&mut watcher, &index.dirs, &mut watched);
+                Some(watcher)
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not create desktop entry watcher: {e}");
+                None
+            }
+        };
+
+        index.scan();
+        publish(index.merged());
+
+        let Some(watcher) = watcher.as_mut() else {
+            return;
+        };
+        while let Ok(paths) = rx.recv() {
+            let mut changed: HashSet<PathBuf> = paths.into_iter().collect();
+            while let Ok(paths) = rx.recv_timeout(DEBOUNCE) {
+                changed.extend(paths);
+            }
+
+            if index.apply(&changed) {
+                publish(index.merged());
+            }
+            // Dirs may have appeared or vanished, e.g. on the first Flatpak install
+            watch_dirs(watcher, &index.dirs, &mut watched);
+        }
+    });
+}
+
+/// Application directories in priority order: `$XDG_DATA_HOME`, then
+/// `$XDG_DATA_DIRS`, then the Flatpak export dirs if they aren't listed.
+fn application_dirs() -> Vec<PathBuf> {
+    let home = std::env::var("HOME").unwrap_or_default();
+
+    let data_home = std::env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| format!("{home}/.local/share"));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    let mut dirs: Vec<PathBuf> = std::iter::once(data_home.as_str())
+        .chain(data_dirs.split(':'))
+        .chain([
+            format!("{home}/.local/share/flatpak/exports/share").as_str(),
+            "/var/lib/flatpak/exports/share",
+        ])
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join("applications"))
+        .collect();
+
+    let mut seen = HashSet::new();
+    dirs.retain(|d| seen.insert(d.clone()));
+    dirs
+}
+
+/// Watches existing application dirs recursively, and the nearest existing
+/// parent of missing ones so their creation is noticed.
+fn watch_dirs(watcher: &mut RecommendedWatcher, dirs: &[PathBuf], watched: &mut HashSet<PathBuf>) {
+    for dir in dirs {
+        let (path, mode) = if dir.is_dir() {
+            (dir.clone(), RecursiveMode::Recursive)
+        } else {
+            // A deleted dir loses its watch; fall back to the parent
+            watched.remove(dir);
+            let Some(parent) = dir.ancestors().skip(1).find(|p| p.is_dir()) else {
+                continue;
+            };
+            (parent.to_path_buf(), RecursiveMode::NonRecursive)
+        };
+
+        if watched.contains(&path) {
+            continue;
+        }
+        match watcher.watch(&path, mode) {
+            Ok(()) => {
+                watched.insert(path);
+            }
+            Err(e) => eprintln!("Warning: Could not watch {}: {e}", path.display()),
+        }
+    }
+}
+
+struct Index {
+    env: ParseEnv,
+    dirs: Vec<PathBuf>,
+    // Per dir, by desktop file id. None for files that didn't parse or are
+    // hidden here; they still shadow the same id in lower-priority dirs.
+    files: Vec<HashMap<String, Option<DesktopEntry>>>,
+}
+
+impl Index {
+    fn new() -> Self {
+        let dirs = application_dirs();
+        Self {
+            env: ParseEnv::from_env(),
+            files: vec![HashMap::new(); dirs.len()],
+            dirs,
+        }
+    }
+
+    fn scan(&mut self) {
+        for i in 0..self.dirs.len() {
+            self.scan_dir(i);
+        }
+    }
+
+    fn scan_dir(&mut self, i: usize) {
+        let root = &self.dirs[i];
+        let mut paths = Vec::new();
+        collect_desktop_files(root, &mut paths);
+        paths.sort();
+
+        let mut files = HashMap::new();
+        for path in paths {
+            let Some(id) = desktop_file_id(root, &path) else {
+                continue;
+            };
+            // The first file wins when two paths map to the same id
+            files
+                .entry(id.clone())
+                .or_insert_with(|| app_finder::parse_desktop_file(&path, id, &self.env));
+        }
+        self.files[i] = files;
+    }
+
+    /// Re-reads what changed under `paths`: single desktop files in place,
+    /// whole dirs when a directory came or went. Returns whether anything
+    /// was re-read.
+    fn apply(&mut self, paths: &HashSet<PathBuf>) -> bool {
+        let mut rescan = HashSet::new();
+        let mut updated = false;
+
+        for path in paths {
+            let Some(i) = self.dirs.iter().position(|dir| path.starts_with(dir)) else {
+                // A parent of application dirs, e.g. one of them being created
+                rescan.extend(
+                    self.dirs
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, dir)| dir.starts_with(path))
+                        .map(|(i, _)| i),
+                );
+                continue;
+            };
+
+            let is_desktop_file = path.extension().and_then(|e| e.to_str()) == Some("desktop");
+            if path.is_dir() || (!path.exists() && path.extension().is_none()) {
+                // A subdirectory (or the dir itself) came or went
+                rescan.insert(i);
+                continue;
+            }
+            if !is_desktop_file {
+                // e.g. mimeinfo.cache, rewritten on every install
+                continue;
+            }
+            let Some(id) = desktop_file_id(&self.dirs[i], path) else {
+                continue;
+            };
+            if path.is_file() {
+                let entry = app_finder::parse_desktop_file(path, id.clone(), &self.env);
+                self.files[i].insert(id, entry);
+            } else {
+                self.files[i].remove(&id);
+            }
+            updated = true;
+        }
+
+        for &i in &rescan {
+            self.scan_dir(i);
+        }
+        updated || !rescan.is_empty()
+    }
+
+    /// Entries with higher-priority dirs shadowing lower ones. `NoDisplay`
+    /// entries stay in for window class and icon lookups; only search
+    /// leaves them out.
+    fn merged(&self) -> Vec<DesktopEntry> {
+        let mut seen_ids = HashSet::new();
+        let mut entries: Vec<DesktopEntry> = self
+            .files
+            .iter()
+            .flat_map(|files| files.iter())
+            .filter(|(id, _)| seen_ids.insert(id.as_str()))
+            .filter_map(|(_, entry)| entry.as_ref())
+            .cloned()
+            .collect();
+
+        entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        entries
+    }
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, files);
+        } else if path.extension().and_then(|e| e.to_str()) == Some("desktop") {
+            files.push(path);
+        }
+    }
+}
+
+/// The desktop file id: the path below the application dir with `/`
+/// replaced by `-`, e.g. `kde4/dolphin.desktop` is `kde4-dolphin`.
+fn desktop_file_id(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(
+        relative
+            .with_extension("")
+            .to_string_lossy()
+            .replace('/', "-"),
+    )
+}
@@ -0,0 +1,465 @@
+use crate::config::LaunchConfig;
+use crate::hyprland::client::Window;
+use crate::system::app_finder::{self, DesktopEntry, MAX_RESULTS};
+use crate::system::calculator;
+use crate::system::launch_history::LaunchHistory;
+use crate::system::launcher::{self, LaunchCommand};
+use std::collections::BTreeSet;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+// Ranks above any match, for rows the query asked for explicitly
+const TOP_SCORE: f64 = f64::MAX;
+// Windows rank a little under apps with an equally good match
+const WINDOW_WEIGHT: f64 = 0.9;
+// Executables offered while typing a `>` command's first word
+const COMMANDS_SHOWN: usize = 10;
+
+/// Sources of app finder results. Apps, windows and the calculator answer
+/// plain queries; the rest take over when the query starts with their prefix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    Apps,
+    Windows,
+    Calculator,
+    // `>` prefix
+    Commands,
+    // `/` or `~` prefix
+    Files,
+    // `=` prefix
+    Units,
+}
+
+impl Provider {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "apps" => Some(Self::Apps),
+            "windows" => Some(Self::Windows),
+            "calculator" => Some(Self::Calculator),
+            "commands" => Some(Self::Commands),
+            "files" => Some(Self::Files),
+            "units" => Some(Self::Units),
+            _ => None,
+        }
+    }
+
+    /// The configured providers, skipping (and warning about) unknown names.
+    pub fn from_config(names: &[String]) -> Vec<Self> {
+        names
+            .iter()
+            .filter_map(|name| {
+                let provider = Self::from_name(name);
+                if provider.is_none() {
+                    eprintln!("Warning: Unknown app finder provider '{name}'");
+                }
+                provider
+            })
+            .collect()
+    }
+}
+
+/// What activating a result does.
+#[derive(Clone)]
+pub enum FinderAction {
+    Launch(Box<DesktopEntry>),
+    FocusWindow { address: String, minimized: bool },
+    // Copy the text to the clipboard
+    Copy(String),
+    // `sh -c` command line
+    RunCommand(String),
+    // Open with the default handler
+    Open(PathBuf),
+}
+
+/// A scored row, ready for the finder to render.
+#[derive(Clone)]
+pub struct FinderResult {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub icon: Option<String>,
+    // Char indices into `title` to highlight
+    pub title_matches: Vec<usize>,
+    // What Tab puts in the search entry with this row selected
+    pub completion: Option<String>,
+    pub action: FinderAction,
+    score: f64,
+}
+
+impl FinderResult {
+    fn new(title: String, icon: &str, action: FinderAction, score: f64) -> Self {
+        Self {
+            title,
+            subtitle: None,
+            icon: Some(icon.to_string()),
+            title_matches: Vec::new(),
+            completion: None,
+            action,
+            score,
+        }
+    }
+
+    fn subtitle(mut self, subtitle: impl Into<String>) -> Self {
+        self.subtitle = Some(subtitle.into());
+        self
+    }
+
+    fn completion(mut self, completion: String) -> Self {
+        self.completion = Some(completion);
+        self
+    }
+}
+
+pub struct SearchContext<'a> {
+    pub entries: &'a [DesktopEntry],
+    pub history: &'a LaunchHistory,
+    // Open windows, empty without Hyprland; only called for plain queries
+    // with the windows provider enabled
+    pub windows: &'a dyn Fn() -> Vec<Window>,
+    pub providers: &'a [Provider],
+}
+
+impl SearchContext<'_> {
+    fn enabled(&self, provider: Provider) -> bool {
+        self.providers.contains(&provider)
+    }
+}
+
+/// Runs `query` through the enabled providers and merges their rows by score.
+pub fn search(query: &str, ctx: &SearchContext) -> Vec<FinderResult> {
+    let trimmed = query.trim_start();
+
+    let mut results = if let Some(command) = trimmed
+        .strip_prefix('>')
+        .filter(|_| ctx.enabled(Provider::Commands))
+    {
+        command_results(command)
+    } else if (trimmed.starts_with('/') || trimmed.starts_with('~')) && ctx.enabled(Provider::Files)
+    {
+        path_results(trimmed)
+    } else if let Some(conversion) = trimmed
+        .strip_prefix('=')
+        .filter(|_| ctx.enabled(Provider::Units))
+    {
+        unit_results(conversion, ctx)
+    } else {
+        let mut results = Vec::new();
+        if ctx.enabled(Provider::Calculator) {
+            results.extend(calculator_result(query));
+        }
+        if ctx.enabled(Provider::Apps) {
+            results.extend(app_results(query, ctx));
+        }
+        if ctx.enabled(Provider::Windows) && !query.trim().is_empty() {
+            results.extend(window_results(query, ctx));
+        }
+        results
+    };
+
+    // Stable sort keeps each provider's own order among equals
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(MAX_RESULTS);
+    results
+}
+
+/// Tab completion for `query`: the selected row's completion, else the
+/// longest prefix shared by every row's completion, if that adds anything.
+pub fn complete(query: &str, results: &[FinderResult], selected: Option<usize>) -> Option<String> {
+    if let Some(completion) = selected.and_then(|i| results.get(i)?.completion.clone()) {
+        return Some(completion);
+    }
+
+    let mut completions = results.iter().filter_map(|r| r.completion.as_deref());
+    let first = completions.next()?;
+    let shared = completions.fold(first, |prefix, c| {
+        let len = prefix
+            .char_indices()
+            .zip(c.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+        &prefix[..len]
+    });
+    (shared.chars().count() > query.chars().count()).then(|| shared.to_string())
+}
+
+fn app_results(query: &str, ctx: &SearchContext) -> Vec<FinderResult> {
+    app_finder::search_entries(ctx.entries, query, ctx.history)
+        .into_iter()
+        .map(|result| {
+            let entry = result.entry;
+            FinderResult {
+                title: entry.name.clone(),
+                subtitle: entry.comment.clone(),
+                icon: entry.icon.clone(),
+                title_matches: result.name_matches,
+                completion: None,
+                action: FinderAction::Launch(Box::new(entry.clone())),
+                score: result.score,
+            }
+        })
+        .collect()
+}
+
+fn window_results(query: &str, ctx: &SearchContext) -> Vec<FinderResult> {
+    (ctx.windows)()
+        .iter()
+        .filter(|w| !w.title.is_empty())
+        .filter_map(|window| {
+            let title_match = app_finder::fuzzy_match(query, &window.title);
+            let class_score =
+                app_finder::fuzzy_match(query, &window.class).map(|m| m.score * 3 / 4);
+            let score = title_match.as_ref().map(|m| m.score).max(class_score)?;
+
+            let entry = app_finder::find_entry_for_class(ctx.entries, &window.class);
+            let icon = entry
+                .and_then(|e| e.icon.clone())
+                .unwrap_or_else(|| window.class.to_lowercase());
+            let app_name = entry.map_or(window.class.as_str(), |e| e.name.as_str());
+            let minimized = window.workspace.name.starts_with("special:minimized");
+            let place = if minimized {
+                "minimized".to_string()
+            } else {
+                format!("workspace {}", window.workspace.name)
+            };
+
+            let action = FinderAction::FocusWindow {
+                address: window.address.clone(),
+                minimized,
+            };
+            let mut result = FinderResult::new(
+                window.title.clone(),
+                &icon,
+                action,
+                score as f64 * WINDOW_WEIGHT,
+            )
+            .subtitle(format!("{app_name} \u{2014} {place}"));
+            result.title_matches = title_match.map(|m| m.positions).unwrap_or_default();
+            Some(result)
+        })
+        .collect()
+}
+
+fn calculator_result(query: &str) -> Option<FinderResult> {
+    let value = calculator::format_number(calculator::evaluate(query)?);
+    Some(
+        FinderResult::new(
+            format!("= {value}"),
+            "accessories-calculator-symbolic",
+            FinderAction::Copy(value),
+            TOP_SCORE,
+        )
+        .subtitle("Enter to copy"),
+    )
+}
+
+fn unit_results(query: &str, ctx: &SearchContext) -> Vec<FinderResult> {
+    let Some(conversion) = calculator::convert(query) else {
+        // `=2+2` works as a calculator too
+        if ctx.enabled(Provider::Calculator) {
+            return calculator_result(query).into_iter().collect();
+        }
+        return Vec::new();
+    };
+
+    let source = format!(
+        "{} {}",
+        calculator::format_number(conversion.amount),
+        conversion.from
+    );
+    conversion
+        .to
+        .iter()
+        .map(|target| {
+            let value = format!(
+                "{} {}",
+                calculator::format_number(target.value),
+                target.unit
+            );
+            FinderResult::new(
+                format!("{source} = {value}"),
+                "accessories-calculator-symbolic",
+                FinderAction::Copy(value),
+                TOP_SCORE,
+            )
+            .subtitle("Enter to copy")
+        })
+        .collect()
+}
+
+fn command_results(command: &str) -> Vec<FinderResult> {
+    let command = command.trim();
+    if command.is_empty() {
+        return Vec::new();
+    }
+
+    let mut results = vec![FinderResult::new(
+        format!("Run {command}"),
+        "utilities-terminal-symbolic",
+        FinderAction::RunCommand(command.to_string()),
+        TOP_SCORE,
+    )
+    .subtitle("Shell command (Ctrl+Enter: in a terminal)")];
+
+    // Still typing the program name: offer matching executables
+    if !command.contains(char::is_whitespace) {
+        let programs = path_executables(command);
+        let count = programs.len();
+        results.extend(programs.into_iter().take(COMMANDS_SHOWN).enumerate().map(
+            |(i, program)| {
+                FinderResult::new(
+                    program.clone(),
+                    "application-x-executable",
+                    FinderAction::RunCommand(program.clone()),
+                    // Keep alphabetical order below the run row
+                    (count - i) as f64,
+                )
+                .completion(format!(">{program} "))
+            },
+        ));
+    }
+    results
+}
+
+// Executables in `$PATH` whose names start with `prefix`, sorted
+fn path_executables(prefix: &str) -> BTreeSet<String> {
+    let path = std::env::var("PATH").unwrap_or_default();
+    let mut programs = BTreeSet::new();
+    for dir in std::env::split_paths(&path) {
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || programs.contains(&name) {
+                continue;
+            }
+            let executable = entry
+                .path()
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if executable {
+                programs.insert(name);
+            }
+        }
+    }
+    programs
+}
+
+fn home_dir() -> String {
+    std::env::var("HOME").unwrap_or_default()
+}
+
+fn expand_home(path: &str) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{rest}", home_dir()),
+        _ => path.to_string(),
+    }
+}
+
+fn path_results(query: &str) -> Vec<FinderResult> {
+    let query = query.trim_end();
+    let expanded = expand_home(query);
+    // `~/Doc` lists `~/` entries starting with "Doc"; a bare `~` lists home
+    let (dir, partial) = if query == "~" {
+        (format!("{expanded}/"), "")
+    } else {
+        match expanded.rfind('/') {
+            Some(i) => (expanded[..=i].to_string(), &expanded[i + 1..]),
+            None => return Vec::new(),
+        }
+    };
+    // Completions keep the `~` the user typed
+    let display_dir = match dir.strip_prefix(&home_dir()) {
+        Some(rest) if query.starts_with('~') => format!("~{rest}"),
+        _ => dir.clone(),
+    };
+
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let partial_lower = partial.to_lowercase();
+    let mut matches: Vec<(bool, String)> = read_dir
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden files only once the query asks for them
+            if name.starts_with('.') && !partial.starts_with('.') {
+                return None;
+            }
+            if !name.to_lowercase().starts_with(&partial_lower) {
+                return None;
+            }
+            Some((entry.path().is_dir(), name))
+        })
+        .collect();
+    // Directories first, then by name
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase()))
+    });
+
+    let mut results = Vec::new();
+    if partial.is_empty() && Path::new(&dir).is_dir() {
+        results.push(path_result(&dir, &display_dir, true, TOP_SCORE));
+    }
+
+    let count = matches.len();
+    results.extend(
+        matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .enumerate()
+            .map(|(i, (is_dir, name))| {
+                let path = format!("{dir}{name}");
+                let display = format!("{display_dir}{name}");
+                let mut result = path_result(&path, &display, is_dir, (count - i) as f64);
+                result.title = name;
+                result.title_matches = (0..partial.chars().count()).collect();
+                result
+            }),
+    );
+    results
+}
+
+fn path_result(path: &str, display: &str, is_dir: bool, score: f64) -> FinderResult {
+    let (icon, completion) = if is_dir {
+        ("folder", format!("{}/", display.trim_end_matches('/')))
+    } else {
+        ("text-x-generic", display.to_string())
+    };
+    FinderResult::new(
+        display.to_string(),
+        icon,
+        FinderAction::Open(PathBuf::from(path)),
+        score,
+    )
+    .subtitle(display)
+    .completion(completion)
+}
+
+/// Runs `command` through `sh -c` with the configured launch strategy,
+/// inside a terminal if `terminal` is set.
+pub fn run_command(command: &str, terminal: bool, config: &LaunchConfig) -> Result<(), String> {
+    let mut args = vec!["sh".to_string(), "-c".to_string(), command.to_string()];
+    if terminal {
+        let mut prefix =
+            app_finder::terminal_command(config).ok_or("No terminal emulator found")?;
+        prefix.append(&mut args);
+        args = prefix;
+    }
+    let launch = LaunchCommand {
+        args,
+        working_dir: Some(PathBuf::from(home_dir())).filter(|dir| dir.is_dir()),
+    };
+    launcher::launch(&launch, "shell", config)
+}
+
+/// Opens `path` with its default handler via `xdg-open`.
+pub fn open_path(path: &Path, config: &LaunchConfig) -> Result<(), String> {
+    let command = LaunchCommand {
+        args: vec!["xdg-open".to_string(), path.to_string_lossy().to_string()],
+        working_dir: None,
+    };
+    launcher::launch(&command, "xdg-open", config)
+}
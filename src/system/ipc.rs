@@ -0,0 +1,114 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Connections are served one at a time, so a client that never finishes
+// its line can only hold up the others this long. `send` waits as long for
+// a crowbar that stopped answering.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A command read from the socket and the way back to whoever sent it.
+pub struct Request {
+    pub command: String,
+    reply: async_channel::Sender<Result<String, String>>,
+}
+
+impl Request {
+    /// Answers the sender: "ok" or the output on success, the message
+    /// prefixed with "error: " otherwise.
+    pub fn respond(self, result: Result<String, String>) {
+        let _ = self.reply.send_blocking(result);
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/crowbar.sock`, one line per command and per reply.
+pub fn socket_path() -> PathBuf {
+    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime).join("crowbar.sock")
+}
+
+/// Removes the socket, once the app that bound it shuts down.
+pub fn remove_socket() {
+    let _ = std::fs::remove_file(socket_path());
+}
+
+/// Listens on the socket in a background thread and hands each command to
+/// `sender`, to be answered through `Request::respond`.
+pub fn listen(sender: async_channel::Sender<Request>) -> Result<(), String> {
+    let path = socket_path();
+    // Left behind by a crowbar that didn't shut down cleanly
+    if UnixStream::connect(&path).is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener =
+        UnixListener::bind(&path).map_err(|e| format!("Could not bind {}: {e}", path.display()))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if !serve(stream, &sender) {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+// Answers one connection; false once the app stopped taking commands
+fn serve(stream: UnixStream, sender: &async_channel::Sender<Request>) -> bool {
+    let mut line = String::new();
+    if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+        || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+    {
+        return true;
+    }
+    let Ok(reader) = stream.try_clone() else {
+        return true;
+    };
+    if BufReader::new(reader).read_line(&mut line).is_err() {
+        return true;
+    }
+
+    let (reply, response) = async_channel::bounded(1);
+    let request = Request {
+        command: line.trim().to_string(),
+        reply,
+    };
+    if sender.send_blocking(request).is_err() {
+        return false;
+    }
+    let text = match response.recv_blocking() {
+        Ok(Ok(output)) if output.is_empty() => "ok".to_string(),
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => format!("error: {e}"),
+        Err(_) => "error: no reply".to_string(),
+    };
+    let mut stream = stream;
+    let _ = writeln!(stream, "{text}");
+    true
+}
+
+/// Sends `command` to the running crowbar and returns its reply.
+pub fn send(command: &str) -> Result<String, String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("Could not connect to {}: {e}", path.display()))?;
+    stream
+        .set_read_timeout(Some(CLIENT_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(CLIENT_TIMEOUT)))
+        .map_err(|e| format!("Could not set socket timeout: {e}"))?;
+    writeln!(stream, "{command}").map_err(|e| format!("Could not send command: {e}"))?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|e| format!("Could not read reply: {e}"))?;
+    let reply = reply.trim_end().to_string();
+    match reply.strip_prefix("error: ") {
+        Some(e) => Err(e.to_string()),
+        None => Ok(reply),
+    }
+}
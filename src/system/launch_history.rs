@@ -0,0 +1,86 @@
+use crate::config::state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Launches this old count for half as much as one made now
+const HALF_LIFE_DAYS: f64 = 14.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchRecord {
+    pub count: u32,
+    // Unix seconds
+    pub last_launch: u64,
+}
+
+/// How often and how recently each desktop entry (by id) was launched,
+/// persisted as JSON under `$XDG_STATE_HOME/crowbar/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchHistory {
+    #[serde(default)]
+    pub apps: HashMap<String, LaunchRecord>,
+}
+
+impl LaunchHistory {
+    pub fn load() -> Self {
+        let path = history_path();
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match serde_json::from_str(&content) {
+            Ok(history) => history,
+            Err(e) => {
+                eprintln!("Warning: Failed to parse launch history: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = history_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Could not create state dir: {e}"))?;
+        }
+        let content = serde_json::to_string(self)
+            .map_err(|e| format!("Could not serialize launch history: {e}"))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Could not write launch history: {e}"))?;
+        Ok(())
+    }
+
+    /// Records a launch of `id` and saves the history, on top of what's on
+    /// disk so launches saved from other bars since loading aren't lost.
+    pub fn record(&mut self, id: &str) {
+        *self = Self::load();
+        let record = self.apps.entry(id.to_string()).or_default();
+        record.count = record.count.saturating_add(1);
+        record.last_launch = now_secs();
+
+        if let Err(e) = self.save() {
+            eprintln!("Warning: {e}");
+        }
+    }
+
+    /// Launch count weighted by recency, halving every `HALF_LIFE_DAYS`.
+    /// Zero for apps that were never launched.
+    pub fn frecency(&self, id: &str) -> f64 {
+        let Some(record) = self.apps.get(id) else {
+            return 0.0;
+        };
+        let age_days = now_secs().saturating_sub(record.last_launch) as f64 / 86_400.0;
+        record.count as f64 * 0.5f64.powf(age_days / HALF_LIFE_DAYS)
+    }
+}
+
+fn history_path() -> PathBuf {
+    state_dir().join("launch_history.json")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
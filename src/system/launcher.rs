@@ -0,0 +1,234 @@
+use crate::config::LaunchConfig;
+use crate::hyprland::HyprlandClient;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zbus::zvariant::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LaunchStrategy {
+    // Spawn detached, then move the process into its own transient
+    // `app-crowbar-<id>-<rand>.scope` via systemd's D-Bus API
+    Systemd,
+    // Let Hyprland spawn it with `dispatch exec`
+    Hyprland,
+    // Double-fork into a new session, reparenting the app away from crowbar
+    Detach,
+    // Plain child process of crowbar
+    Direct,
+}
+
+impl LaunchStrategy {
+    pub fn from_config(value: &str) -> Self {
+        match value.trim() {
+            "systemd" => Self::Systemd,
+            "hyprland" => Self::Hyprland,
+            "detach" => Self::Detach,
+            "direct" => Self::Direct,
+            _ => Self::detect(),
+        }
+    }
+
+    /// systemd when a user manager is running, else a detached process.
+    fn detect() -> Self {
+        let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+        if !runtime.is_empty() && Path::new(&runtime).join("systemd").is_dir() {
+            Self::Systemd
+        } else {
+            Self::Detach
+        }
+    }
+}
+
+/// A fully expanded command line, ready to spawn or hand to a compositor.
+pub struct LaunchCommand {
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+}
+
+impl LaunchCommand {
+    /// The command as a single `sh` command line, for launchers that take one
+    /// (e.g. Hyprland's `exec` dispatcher).
+    pub fn shell_line(&self) -> String {
+        let command = self
+            .args
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match &self.working_dir {
+            Some(dir) => format!("cd {} && {command}", shell_quote(&dir.to_string_lossy())),
+            None => command,
+        }
+    }
+
+    fn command(&self) -> Result<Command, String> {
+        let program = self.args.first().ok_or("Empty command")?;
+        let mut command = Command::new(program);
+        command
+            .args(&self.args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+        Ok(command)
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Starts `command` using the configured strategy. `app_id` (usually the
+/// desktop file id) names the systemd scope.
+pub fn launch(command: &LaunchCommand, app_id: &str, config: &LaunchConfig) -> Result<(), String> {
+    match LaunchStrategy::from_config(&config.strategy) {
+        LaunchStrategy::Systemd => launch_scope(command, app_id),
+        LaunchStrategy::Hyprland => HyprlandClient::new()?.dispatch(&format!("exec {}", command.shell_line())),
+        LaunchStrategy::Detach => launch_detached(command),
+        LaunchStrategy::Direct => {
+            let child = spawn(command)?;
+            reap(child);
+            Ok(())
+        }
+    }
+}
+
+/// Runs a short `sh -c` command line as a plain child of crowbar, reaped
+/// off the main thread. For bar click and scroll actions, which can fire
+/// many times a second and don't need a launch strategy's own scope or
+/// session.
+pub fn run_shell(line: &str) -> Result<(), String> {
+    let command = LaunchCommand {
+        args: vec!["sh".to_string(), "-c".to_string(), line.to_string()],
+        working_dir: None,
+    };
+    let child = spawn(&command)?;
+    reap(child);
+    Ok(())
+}
+
+fn spawn(command: &LaunchCommand) -> Result<Child, String> {
+    command
+        .command()?
+        .spawn()
+        .map_err(|e| format!("{}: {e}", command.args[0]))
+}
+
+// Wait on the child off the main thread so it doesn't linger as a zombie
+fn reap(mut child: Child) {
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
+
+fn launch_detached(command: &LaunchCommand) -> Result<(), String> {
+    let mut cmd = command.command()?;
+    // SAFETY: only async-signal-safe calls between fork and exec. The
+    // intermediate child exits right away, leaving the grandchild (which
+    // goes on to exec) orphaned in its own session.
+    unsafe {
+        cmd.pre_exec(|| {
+            match libc::fork() {
+                -1 => return Err(std::io::Error::last_os_error()),
+                0 => {}
+                _ => libc::_exit(0),
+            }
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("{}: {e}", command.args[0]))?;
+    let _ = child.wait();
+    Ok(())
+}
+
+fn launch_scope(command: &LaunchCommand, app_id: &str) -> Result<(), String> {
+    let child = spawn(command)?;
+    let pid = child.id();
+    let unit = scope_unit_name(app_id, pid);
+    let description = format!("{} launched by crowbar", command.args[0]);
+
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("Warning: Could not create tokio runtime for launch: {e}");
+                reap(child);
+                return;
+            }
+        };
+
+        if let Err(e) = rt.block_on(start_transient_scope(&unit, &description, pid)) {
+            // The app is already running, just outside its own scope
+            eprintln!("Warning: Could not move pid {pid} into {unit}: {e}");
+        }
+
+        let mut child = child;
+        let _ = child.wait();
+    });
+
+    Ok(())
+}
+
+async fn start_transient_scope(unit: &str, description: &str, pid: u32) -> zbus::Result<()> {
+    let conn = zbus::Connection::session().await?;
+    let proxy = zbus::Proxy::new(
+        &conn,
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        "org.freedesktop.systemd1.Manager",
+    )
+    .await?;
+
+    let properties: Vec<(&str, Value)> = vec![
+        ("PIDs", Value::from(vec![pid])),
+        ("Description", Value::from(description)),
+        ("CollectMode", Value::from("inactive-or-failed")),
+    ];
+    let aux: Vec<(&str, Vec<(&str, Value)>)> = Vec::new();
+
+    let _job: zbus::zvariant::OwnedObjectPath = proxy
+        .call("StartTransientUnit", &(unit, "fail", properties, aux))
+        .await?;
+    Ok(())
+}
+
+/// `app-crowbar-<id>-<rand>.scope`, with `id` escaped like `systemd-escape`
+/// so dashes in it don't read as separators.
+fn scope_unit_name(app_id: &str, pid: u32) -> String {
+    let mut escaped = String::new();
+    for (i, byte) in app_id.bytes().enumerate() {
+        let keep = byte.is_ascii_alphanumeric() || byte == b'_' || byte == b':' || (byte == b'.' && i > 0);
+        if keep {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    format!("app-crowbar-{escaped}-{:08x}.scope", nanos ^ pid.rotate_left(16))
+}
@@ -1,4 +1,13 @@
-use std::process::Command;
+use crate::config::MediaConfig;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use zbus::zvariant::{OwnedValue, Value};
+
+const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PlaybackStatus {
@@ -8,107 +17,495 @@ pub enum PlaybackStatus {
     Unknown,
 }
 
-#[derive(Debug)]
-pub struct MediaInfo {
-    pub available: bool,
+/// An MPRIS player as it last reported itself.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub bus_name: String,
+    pub identity: String,
     pub status: PlaybackStatus,
     pub title: String,
     pub artist: String,
     pub album: String,
-    pub player: String,
-    pub position: i64,  // microseconds
-    pub length: i64,    // microseconds
-}
-
-pub fn get_media_info() -> MediaInfo {
-    let mut info = MediaInfo {
-        available: false,
-        status: PlaybackStatus::Unknown,
-        title: String::new(),
-        artist: String::new(),
-        album: String::new(),
-        player: String::new(),
-        position: 0,
-        length: 0,
-    };
+    pub length: i64, // microseconds
+    pub rate: f64,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    // Microseconds, as of `reported`; players don't signal it moving on
+    position: i64,
+    reported: Instant,
+}
 
-    // Get player list
-    let Ok(output) = Command::new("playerctl").arg("-l").output() else {
-        return info;
-    };
-    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if text.is_empty() {
-        return info;
+impl Player {
+    fn new(bus_name: &str) -> Self {
+        Self {
+            bus_name: bus_name.to_string(),
+            identity: String::new(),
+            status: PlaybackStatus::Unknown,
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            length: 0,
+            rate: 1.0,
+            can_go_next: false,
+            can_go_previous: false,
+            position: 0,
+            reported: Instant::now(),
+        }
     }
 
-    if let Some(player) = text.lines().next() {
-        info.player = player.to_string();
-        info.available = true;
-    } else {
-        return info;
+    /// The player's name for people: its Identity, or the bus name's last
+    /// part for players without one.
+    pub fn display_name(&self) -> &str {
+        if !self.identity.is_empty() {
+            return &self.identity;
+        }
+        let name = self
+            .bus_name
+            .strip_prefix(BUS_PREFIX)
+            .unwrap_or(&self.bus_name);
+        // Drops the ".instance1234" some players run under
+        name.split('.').next().unwrap_or(name)
     }
 
-    // Get playback status
-    if let Ok(output) = Command::new("playerctl").arg("status").output() {
-        let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        info.status = match status.as_str() {
-            "Playing" => PlaybackStatus::Playing,
-            "Paused" => PlaybackStatus::Paused,
-            "Stopped" => PlaybackStatus::Stopped,
-            _ => PlaybackStatus::Unknown,
-        };
+    /// Position in microseconds, carried on from the last report at the
+    /// player's rate while it plays.
+    pub fn position(&self) -> i64 {
+        let mut position = self.position;
+        if self.status == PlaybackStatus::Playing {
+            let elapsed = self.reported.elapsed().as_micros() as f64 * self.rate;
+            position += elapsed as i64;
+        }
+        if self.length > 0 {
+            position = position.min(self.length);
+        }
+        position.max(0)
     }
 
-    // Get metadata
-    if let Ok(output) = Command::new("playerctl")
-        .args(["metadata", "--format", "{{title}}|||{{artist}}|||{{album}}"])
-        .output()
-    {
-        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let parts: Vec<&str> = text.split("|||").collect();
-        if !parts.is_empty() {
-            info.title = parts[0].to_string();
+    /// Whether there's a track worth showing.
+    pub fn has_track(&self) -> bool {
+        matches!(
+            self.status,
+            PlaybackStatus::Playing | PlaybackStatus::Paused
+        ) && !self.title.is_empty()
+    }
+}
+
+/// Every player on the bus but the ignored ones, and the one the media
+/// module follows: the last to start playing, unless a player higher on the
+/// priority list is still playing, or whichever was picked by hand.
+pub struct Players {
+    players: HashMap<String, Player>,
+    // When each player last started playing, as a running count
+    started: HashMap<String, u64>,
+    starts: u64,
+    focused: Option<String>,
+    // Whether `focused` was picked by hand rather than followed
+    picked: bool,
+    priority: Vec<String>,
+    ignore: Vec<String>,
+}
+
+impl Players {
+    pub fn new(config: &MediaConfig) -> Self {
+        let lowercase = |patterns: &[String]| patterns.iter().map(|p| p.to_lowercase()).collect();
+        Self {
+            players: HashMap::new(),
+            started: HashMap::new(),
+            starts: 0,
+            focused: None,
+            picked: false,
+            priority: lowercase(&config.priority),
+            ignore: lowercase(&config.ignore),
         }
-        if parts.len() >= 2 {
-            info.artist = parts[1].to_string();
+    }
+
+    pub fn is_ignored(&self, player: &Player) -> bool {
+        self.ignore.iter().any(|pattern| matches(player, pattern))
+    }
+
+    pub fn apply(&mut self, event: MediaEvent) {
+        match event {
+            MediaEvent::Updated(player) if self.is_ignored(&player) => {
+                self.remove(&player.bus_name);
+            }
+            MediaEvent::Updated(player) => {
+                let was_playing = self
+                    .players
+                    .get(&player.bus_name)
+                    .is_some_and(|p| p.status == PlaybackStatus::Playing);
+                let name = player.bus_name.clone();
+                let playing = player.status == PlaybackStatus::Playing;
+                self.players.insert(name.clone(), player);
+
+                if playing && !was_playing {
+                    self.starts += 1;
+                    self.started.insert(name.clone(), self.starts);
+                    if self.takes_focus(&name) {
+                        self.follow(Some(name));
+                    }
+                } else if was_playing
+                    && !playing
+                    && !self.picked
+                    && self.focused.as_deref() == Some(name.as_str())
+                {
+                    // Back to whatever else is playing, rather than staying
+                    // on a player that paused over it
+                    self.follow(self.best().map(|p| p.bus_name.clone()));
+                }
+            }
+            MediaEvent::Removed(name) => self.remove(&name),
         }
-        if parts.len() >= 3 {
-            info.album = parts[2].to_string();
+        if self.focused().is_none() {
+            self.follow(self.best().map(|p| p.bus_name.clone()));
         }
     }
 
-    // Get position
-    if let Ok(output) = Command::new("playerctl").arg("position").output() {
-        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if let Ok(pos) = text.parse::<f64>() {
-            info.position = (pos * 1_000_000.0) as i64;
+    /// Follows `bus_name` until another player starts playing.
+    pub fn focus(&mut self, bus_name: &str) {
+        if self.players.get(bus_name).is_some_and(|p| p.has_track()) {
+            self.focused = Some(bus_name.to_string());
+            self.picked = true;
         }
     }
 
-    // Get length
-    if let Ok(output) = Command::new("playerctl")
-        .args(["metadata", "mpris:length"])
-        .output()
-    {
-        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if let Ok(length) = text.parse::<i64>() {
-            info.length = length;
+    /// The player followed, if any has a track.
+    pub fn focused(&self) -> Option<&Player> {
+        self.focused
+            .as_ref()
+            .and_then(|name| self.players.get(name))
+            .filter(|p| p.has_track())
+    }
+
+    /// Players with a track, by priority and then name.
+    pub fn list(&self) -> Vec<&Player> {
+        let mut list: Vec<&Player> = self.players.values().filter(|p| p.has_track()).collect();
+        list.sort_by(|a, b| (self.rank(a), &a.bus_name).cmp(&(self.rank(b), &b.bus_name)));
+        list
+    }
+
+    fn follow(&mut self, bus_name: Option<String>) {
+        self.focused = bus_name;
+        self.picked = false;
+    }
+
+    fn remove(&mut self, bus_name: &str) {
+        self.players.remove(bus_name);
+        self.started.remove(bus_name);
+        if self.focused.as_deref() == Some(bus_name) {
+            self.focused = None;
         }
     }
 
-    info
+    // A player that just started playing takes over unless the one
+    // followed is playing and ranks higher
+    fn takes_focus(&self, bus_name: &str) -> bool {
+        let Some(current) = self.focused() else {
+            return true;
+        };
+        if current.status != PlaybackStatus::Playing || current.bus_name == bus_name {
+            return true;
+        }
+        self.players
+            .get(bus_name)
+            .is_some_and(|p| self.rank(p) <= self.rank(current))
+    }
+
+    // Playing before paused, then by priority, then the latest to start
+    fn best(&self) -> Option<&Player> {
+        self.players
+            .values()
+            .filter(|p| p.has_track())
+            .min_by_key(|p| {
+                (
+                    p.status != PlaybackStatus::Playing,
+                    self.rank(p),
+                    std::cmp::Reverse(self.started.get(&p.bus_name).copied().unwrap_or(0)),
+                    &p.bus_name,
+                )
+            })
+    }
+
+    // Position of the first priority entry matching, after all of them
+    // for none
+    fn rank(&self, player: &Player) -> usize {
+        self.priority
+            .iter()
+            .position(|pattern| matches(player, pattern))
+            .unwrap_or(self.priority.len())
+    }
+}
+
+// `pattern` is already lowercase
+fn matches(player: &Player, pattern: &str) -> bool {
+    player.bus_name.to_lowercase().contains(pattern)
+        || player.identity.to_lowercase().contains(pattern)
 }
 
-pub fn play_pause() {
-    let _ = Command::new("playerctl").arg("play-pause").status();
+#[derive(Debug, Clone)]
+pub enum MediaEvent {
+    Updated(Player),
+    /// A player left the bus, by bus name.
+    Removed(String),
 }
 
-pub fn next() {
-    let _ = Command::new("playerctl").arg("next").status();
+#[derive(Debug, Clone)]
+pub enum MediaCommand {
+    PlayPause(String),
+    Next(String),
+    Previous(String),
 }
 
-pub fn previous() {
-    let _ = Command::new("playerctl").arg("previous").status();
+/// Follows every MPRIS player on the session bus on its own thread,
+/// sending their changes to `sender` and running `commands` against them
+/// until the command channel closes.
+pub fn start(
+    commands: async_channel::Receiver<MediaCommand>,
+    sender: async_channel::Sender<MediaEvent>,
+) {
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("Warning: Could not create tokio runtime for media: {e}");
+                return;
+            }
+        };
+
+        rt.block_on(async move {
+            let Ok(conn) = zbus::Connection::session().await else {
+                eprintln!("Warning: Could not connect to session bus for media");
+                return;
+            };
+            tokio::spawn(watch_players(conn.clone(), sender));
+            // Dropping the runtime afterwards ends the watchers too
+            handle_commands(conn, commands).await;
+        });
+    });
+}
+
+async fn handle_commands(conn: zbus::Connection, commands: async_channel::Receiver<MediaCommand>) {
+    while let Ok(command) = commands.recv().await {
+        let (bus_name, method) = match &command {
+            MediaCommand::PlayPause(name) => (name, "PlayPause"),
+            MediaCommand::Next(name) => (name, "Next"),
+            MediaCommand::Previous(name) => (name, "Previous"),
+        };
+        let result = async {
+            let proxy =
+                zbus::Proxy::new(&conn, bus_name.as_str(), PLAYER_PATH, PLAYER_INTERFACE).await?;
+            proxy.call_method(method, &()).await
+        }
+        .await;
+        if let Err(e) = result {
+            eprintln!("Warning: {method} failed for {bus_name}: {e}");
+        }
+    }
+}
+
+/// Watches the players already on the bus and every one that turns up.
+async fn watch_players(conn: zbus::Connection, sender: async_channel::Sender<MediaEvent>) {
+    use futures_util::StreamExt;
+
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(&conn).await else {
+        eprintln!("Warning: Could not create DBus proxy for media players");
+        return;
+    };
+    // Before listing, so no player starting in between is missed
+    let Ok(mut owners) = dbus.receive_name_owner_changed().await else {
+        eprintln!("Warning: Could not subscribe to NameOwnerChanged");
+        return;
+    };
+
+    let watched: Arc<Mutex<HashSet<String>>> = Arc::default();
+    let watch = |name: &str| {
+        if name.starts_with(BUS_PREFIX) && watched.lock().unwrap().insert(name.to_string()) {
+            tokio::spawn(watch_player(
+                conn.clone(),
+                name.to_string(),
+                sender.clone(),
+                watched.clone(),
+            ));
+        }
+    };
+
+    match dbus.list_names().await {
+        Ok(names) => names.iter().for_each(|name| watch(name.as_str())),
+        Err(e) => eprintln!("Warning: Could not list media players: {e}"),
+    }
+    while let Some(signal) = owners.next().await {
+        if let Ok(args) = signal.args() {
+            if args.new_owner().is_some() {
+                watch(args.name().as_str());
+            }
+        }
+    }
+}
+
+/// Sends the player as it is now and again on every change, until it
+/// leaves the bus.
+async fn watch_player(
+    conn: zbus::Connection,
+    bus_name: String,
+    sender: async_channel::Sender<MediaEvent>,
+    watched: Arc<Mutex<HashSet<String>>>,
+) {
+    use futures_util::StreamExt;
+
+    let mut player = Player::new(&bus_name);
+    // Subscribe first so no change after the first read is missed
+    let changes = subscribe(&conn, &bus_name).await;
+    if let Err(e) = &changes {
+        eprintln!("Warning: Could not watch media player {bus_name}: {e}");
+    }
+    player.identity = fetch_identity(&conn, &bus_name).await.unwrap_or_default();
+
+    if let Ok(mut changes) = changes {
+        let mut alive = true;
+        while alive {
+            match fetch_player(&conn, &mut player).await {
+                Ok(()) => {
+                    if sender
+                        .send(MediaEvent::Updated(player.clone()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("Warning: Could not read media player {bus_name}: {e}"),
+            }
+            alive = changes.next().await == Some(true);
+        }
+    }
+    watched.lock().unwrap().remove(&bus_name);
+    let _ = sender.send(MediaEvent::Removed(bus_name)).await;
+}
+
+/// PropertiesChanged and Seeked as true, and false once the player has
+/// left the bus.
+async fn subscribe(
+    conn: &zbus::Connection,
+    bus_name: &str,
+) -> zbus::Result<impl futures_util::Stream<Item = bool>> {
+    use futures_util::StreamExt;
+
+    let proxy = |interface: &'static str| async move {
+        zbus::proxy::Builder::<zbus::Proxy>::new(conn)
+            .destination(bus_name.to_string())?
+            .path(PLAYER_PATH)?
+            .interface(interface)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+    };
+    let properties = proxy("org.freedesktop.DBus.Properties").await?;
+    let player = proxy(PLAYER_INTERFACE).await?;
+    let changed = properties
+        .receive_signal("PropertiesChanged")
+        .await?
+        .map(|_| true);
+    let seeked = player.receive_signal("Seeked").await?.map(|_| true);
+
+    let dbus = zbus::fdo::DBusProxy::new(conn).await?;
+    let owners = dbus
+        .receive_name_owner_changed_with_args(&[(0, bus_name)])
+        .await?
+        .map(|signal| signal.args().is_ok_and(|args| args.new_owner().is_some()));
+
+    // It may have left before we subscribed
+    let alive = dbus.name_has_owner(player.destination().to_owned()).await?;
+    let gone = futures_util::stream::iter((!alive).then_some(false));
+    Ok(gone.chain(futures_util::stream::select(
+        futures_util::stream::select(changed, seeked),
+        owners,
+    )))
+}
+
+async fn properties_proxy<'a>(
+    conn: &zbus::Connection,
+    bus_name: &'a str,
+) -> zbus::Result<zbus::Proxy<'a>> {
+    zbus::Proxy::new(
+        conn,
+        bus_name,
+        PLAYER_PATH,
+        "org.freedesktop.DBus.Properties",
+    )
+    .await
+}
+
+async fn fetch_identity(conn: &zbus::Connection, bus_name: &str) -> zbus::Result<String> {
+    let proxy = properties_proxy(conn, bus_name).await?;
+    let identity: OwnedValue = proxy.call("Get", &(ROOT_INTERFACE, "Identity")).await?;
+    Ok(String::try_from(identity).unwrap_or_default())
+}
+
+async fn fetch_player(conn: &zbus::Connection, player: &mut Player) -> zbus::Result<()> {
+    // One round trip instead of a call per property; PropertiesChanged
+    // never carries Position, so a fresh read is needed anyway
+    let proxy = properties_proxy(conn, &player.bus_name).await?;
+    let mut props: HashMap<String, OwnedValue> = proxy.call("GetAll", &(PLAYER_INTERFACE,)).await?;
+
+    player.status = match take::<String>(&mut props, "PlaybackStatus").as_deref() {
+        Some("Playing") => PlaybackStatus::Playing,
+        Some("Paused") => PlaybackStatus::Paused,
+        Some("Stopped") => PlaybackStatus::Stopped,
+        _ => PlaybackStatus::Unknown,
+    };
+    player.rate = take::<f64>(&mut props, "Rate").unwrap_or(1.0);
+    player.position = props.remove("Position").and_then(integer).unwrap_or(0);
+    player.reported = Instant::now();
+    player.can_go_next = take::<bool>(&mut props, "CanGoNext").unwrap_or(false);
+    player.can_go_previous = take::<bool>(&mut props, "CanGoPrevious").unwrap_or(false);
+
+    let mut metadata: HashMap<String, OwnedValue> =
+        take(&mut props, "Metadata").unwrap_or_default();
+    player.title = take::<String>(&mut metadata, "xesam:title").unwrap_or_default();
+    player.album = take::<String>(&mut metadata, "xesam:album").unwrap_or_default();
+    player.artist = metadata
+        .remove("xesam:artist")
+        .map(artists)
+        .unwrap_or_default();
+    player.length = metadata
+        .remove("mpris:length")
+        .and_then(integer)
+        .unwrap_or(0);
+    Ok(())
+}
+
+fn take<T: TryFrom<OwnedValue>>(props: &mut HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    props.remove(key).and_then(|value| T::try_from(value).ok())
+}
+
+// The spec says `x`, but players send whatever integer they like
+fn integer(value: OwnedValue) -> Option<i64> {
+    match &*value {
+        Value::I64(n) => Some(*n),
+        Value::U64(n) => i64::try_from(*n).ok(),
+        Value::I32(n) => Some(*n as i64),
+        Value::U32(n) => Some(*n as i64),
+        Value::F64(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+// A list of artists by the spec, a plain string from some players
+fn artists(value: OwnedValue) -> String {
+    match &*value {
+        Value::Str(s) => s.to_string(),
+        Value::Array(array) => array
+            .iter()
+            .filter_map(|v| match v {
+                Value::Str(s) => Some(s.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::new(),
+    }
 }
 
 pub fn format_duration(microseconds: i64) -> String {
@@ -127,3 +524,92 @@ pub fn truncate_string(s: &str, max_len: usize) -> String {
         format!("{}...", &s[..max_len - 3])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(bus_name: &str, status: PlaybackStatus) -> Player {
+        let mut player = Player::new(&format!("{BUS_PREFIX}{bus_name}"));
+        player.status = status;
+        player.title = "Track".to_string();
+        player
+    }
+
+    fn players(priority: &[&str], ignore: &[&str]) -> Players {
+        let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+        Players::new(&MediaConfig {
+            priority: strings(priority),
+            ignore: strings(ignore),
+        })
+    }
+
+    fn update(players: &mut Players, bus_name: &str, status: PlaybackStatus) {
+        players.apply(MediaEvent::Updated(player(bus_name, status)));
+    }
+
+    fn focused(players: &Players) -> Option<&str> {
+        players.focused().map(|p| p.display_name())
+    }
+
+    #[test]
+    fn latest_to_start_playing_takes_focus() {
+        let mut p = players(&[], &[]);
+        update(&mut p, "spotify", PlaybackStatus::Playing);
+        assert_eq!(focused(&p), Some("spotify"));
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        assert_eq!(focused(&p), Some("firefox"));
+    }
+
+    #[test]
+    fn pausing_falls_back_to_a_player_still_playing() {
+        let mut p = players(&[], &[]);
+        update(&mut p, "spotify", PlaybackStatus::Playing);
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        update(&mut p, "firefox", PlaybackStatus::Paused);
+        assert_eq!(focused(&p), Some("spotify"));
+    }
+
+    #[test]
+    fn pick_by_hand_survives_pausing() {
+        let mut p = players(&[], &[]);
+        update(&mut p, "spotify", PlaybackStatus::Playing);
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        p.focus(&format!("{BUS_PREFIX}firefox"));
+        update(&mut p, "firefox", PlaybackStatus::Paused);
+        assert_eq!(focused(&p), Some("firefox"));
+
+        // Until another player starts playing
+        update(&mut p, "mpv", PlaybackStatus::Playing);
+        assert_eq!(focused(&p), Some("mpv"));
+    }
+
+    #[test]
+    fn higher_priority_player_keeps_focus_while_playing() {
+        let mut p = players(&["spotify"], &[]);
+        update(&mut p, "spotify", PlaybackStatus::Playing);
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        assert_eq!(focused(&p), Some("spotify"));
+
+        update(&mut p, "spotify", PlaybackStatus::Paused);
+        assert_eq!(focused(&p), Some("firefox"));
+    }
+
+    #[test]
+    fn ignored_players_are_dropped() {
+        let mut p = players(&[], &["Firefox"]);
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        assert_eq!(focused(&p), None);
+        assert!(p.list().is_empty());
+    }
+
+    #[test]
+    fn removing_the_focused_player_moves_focus_on() {
+        let mut p = players(&[], &[]);
+        update(&mut p, "spotify", PlaybackStatus::Paused);
+        update(&mut p, "firefox", PlaybackStatus::Playing);
+        p.apply(MediaEvent::Removed(format!("{BUS_PREFIX}firefox")));
+        assert_eq!(focused(&p), Some("spotify"));
+        assert_eq!(p.list().len(), 1);
+    }
+}
@@ -1,7 +1,21 @@
 pub mod app_finder;
 pub mod audio;
+pub mod audio_profiles;
 pub mod battery;
 pub mod brightness;
+pub mod calculator;
 pub mod connectivity;
+pub mod dbusmenu;
+pub mod desktop_index;
+pub mod finder_providers;
+pub mod ipc;
+pub mod launch_history;
+pub mod launcher;
 pub mod media;
 pub mod power;
+pub mod privacy;
+pub mod process;
+pub mod pulse;
+pub mod screenshot;
+pub mod systray;
+pub mod visualizer;
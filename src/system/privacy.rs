@@ -0,0 +1,58 @@
+use crate::system::audio;
+use std::fs;
+
+/// Programs recording from a microphone, by application name.
+pub fn microphone_users() -> Vec<String> {
+    let mut names: Vec<String> = audio::list_source_outputs()
+        .into_iter()
+        .map(|output| output.name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Programs holding a camera (`/dev/video*`) open, by process name. Only
+/// processes whose file descriptors we may read are seen, which covers
+/// everything the user runs.
+pub fn camera_users() -> Vec<String> {
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id().to_string();
+
+    let mut names: Vec<String> = procs
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_string_lossy().into_owned();
+            if !pid.bytes().all(|b| b.is_ascii_digit()) || pid == own_pid {
+                return None;
+            }
+            let fds = fs::read_dir(entry.path().join("fd")).ok()?;
+            let uses_camera = fds.flatten().any(|fd| {
+                fs::read_link(fd.path())
+                    .is_ok_and(|target| target.to_string_lossy().starts_with("/dev/video"))
+            });
+            if !uses_camera {
+                return None;
+            }
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            Some(comm.trim().to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Runs one of the microphone commands shared by the bar and the IPC
+/// socket; false for names it doesn't know.
+pub fn run_command(name: &str) -> bool {
+    match name {
+        "mute-all-sources" => audio::set_all_sources_muted(true),
+        "unmute-all-sources" => audio::set_all_sources_muted(false),
+        "toggle-mute-all-sources" => audio::set_all_sources_muted(!audio::all_sources_muted()),
+        _ => return false,
+    }
+    true
+}
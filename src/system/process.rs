@@ -0,0 +1,37 @@
+use std::io;
+
+pub fn terminate(pid: i32) -> Result<(), String> {
+    send_signal(pid, libc::SIGTERM)
+}
+
+pub fn kill(pid: i32) -> Result<(), String> {
+    send_signal(pid, libc::SIGKILL)
+}
+
+/// Whether `pid` still exists. A process we may not signal (EPERM) still counts.
+pub fn is_running(pid: i32) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: kill(2) with signal 0 only checks for existence
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn send_signal(pid: i32, signal: libc::c_int) -> Result<(), String> {
+    // 0 and negative pids address process groups, never a single window
+    if pid <= 0 || pid as u32 == std::process::id() {
+        return Err(format!("Refusing to signal pid {pid}"));
+    }
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(pid, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to signal process {pid}: {}",
+            io::Error::last_os_error()
+        ))
+    }
+}
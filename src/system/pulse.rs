@@ -0,0 +1,1078 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Native protocol client for PulseAudio, also served by pipewire-pulse.
+// Only control packets are spoken: no playback or record streams.
+
+// The newest protocol whose replies we parse; servers answer in the older
+// of theirs and ours
+const PROTOCOL_VERSION: u32 = 32;
+const VERSION_MASK: u32 = 0xffff;
+const CONTROL_CHANNEL: u32 = u32::MAX;
+const COOKIE_LEN: usize = 256;
+const MAX_PACKET: usize = 16 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const INVALID_INDEX: u32 = u32::MAX;
+pub const VOLUME_NORM: u32 = 0x10000;
+pub const DEFAULT_SINK: &str = "@DEFAULT_SINK@";
+pub const DEFAULT_SOURCE: &str = "@DEFAULT_SOURCE@";
+
+mod command {
+    pub const ERROR: u32 = 0;
+    pub const REPLY: u32 = 2;
+    pub const AUTH: u32 = 8;
+    pub const SET_CLIENT_NAME: u32 = 9;
+    pub const GET_SERVER_INFO: u32 = 20;
+    pub const GET_SINK_INFO: u32 = 21;
+    pub const GET_SINK_INFO_LIST: u32 = 22;
+    pub const GET_SOURCE_INFO: u32 = 23;
+    pub const GET_SOURCE_INFO_LIST: u32 = 24;
+    pub const GET_SINK_INPUT_INFO: u32 = 29;
+    pub const GET_SINK_INPUT_INFO_LIST: u32 = 30;
+    pub const GET_SOURCE_OUTPUT_INFO: u32 = 31;
+    pub const GET_SOURCE_OUTPUT_INFO_LIST: u32 = 32;
+    pub const SUBSCRIBE: u32 = 35;
+    pub const SET_SINK_VOLUME: u32 = 36;
+    pub const SET_SINK_INPUT_VOLUME: u32 = 37;
+    pub const SET_SOURCE_VOLUME: u32 = 38;
+    pub const SET_SINK_MUTE: u32 = 39;
+    pub const SET_SOURCE_MUTE: u32 = 40;
+    pub const SET_DEFAULT_SINK: u32 = 44;
+    pub const SET_DEFAULT_SOURCE: u32 = 45;
+    pub const SUBSCRIBE_EVENT: u32 = 66;
+    pub const MOVE_SINK_INPUT: u32 = 67;
+    pub const MOVE_SOURCE_OUTPUT: u32 = 68;
+    pub const SET_SINK_INPUT_MUTE: u32 = 69;
+    pub const GET_CARD_INFO_LIST: u32 = 89;
+    pub const SET_CARD_PROFILE: u32 = 90;
+    pub const SET_SOURCE_OUTPUT_VOLUME: u32 = 98;
+    pub const SET_SOURCE_OUTPUT_MUTE: u32 = 99;
+}
+
+// Sinks, sources, sink inputs, source outputs, server and cards
+const SUBSCRIPTION_MASK: u32 = 0x1 | 0x2 | 0x4 | 0x8 | 0x80 | 0x200;
+
+// === Data types ===
+
+pub struct ServerInfo {
+    pub default_sink: String,
+    pub default_source: String,
+}
+
+/// A sink or source.
+pub struct Device {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    // Raw per-channel volumes, VOLUME_NORM is 100%
+    pub volume: Vec<u32>,
+    // The position of each channel in `volume`
+    pub channel_map: Vec<u8>,
+    pub muted: bool,
+    // For sources, the sink they monitor, else INVALID_INDEX
+    pub monitor_of: u32,
+}
+
+/// A sink input or source output.
+pub struct Stream {
+    pub index: u32,
+    pub name: String,
+    // The sink or source it plays to or records from
+    pub device: u32,
+    pub volume: Vec<u32>,
+    pub muted: bool,
+    pub properties: HashMap<String, String>,
+}
+
+pub struct Card {
+    pub index: u32,
+    pub name: String,
+    pub profiles: Vec<CardProfile>,
+    pub active_profile: String,
+    pub properties: HashMap<String, String>,
+}
+
+pub struct CardProfile {
+    pub name: String,
+    pub description: String,
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Facility {
+    Sink,
+    Source,
+    SinkInput,
+    SourceOutput,
+    Server,
+    Card,
+    Other,
+}
+
+// === Volumes ===
+
+/// The loudest channel as a percentage, like pactl shows it.
+pub fn volume_percent(volume: &[u32]) -> i32 {
+    let max = volume.iter().copied().max().unwrap_or(0) as u64;
+    ((max * 100 + VOLUME_NORM as u64 / 2) / VOLUME_NORM as u64) as i32
+}
+
+/// Scales `volume` so its loudest channel is at `percent`, keeping the
+/// balance between channels.
+pub fn scale_volume(volume: &[u32], percent: i32) -> Vec<u32> {
+    let target = (percent.max(0) as u64 * VOLUME_NORM as u64 / 100) as u32;
+    let max = volume.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return vec![target; volume.len().max(1)];
+    }
+    volume
+        .iter()
+        .map(|&v| (v as u64 * target as u64 / max as u64) as u32)
+        .collect()
+}
+
+// === Connection ===
+
+pub struct Connection {
+    stream: UnixStream,
+    version: u32,
+    next_tag: u32,
+    // Set once the socket failed; the connection is useless after that
+    closed: bool,
+}
+
+impl Connection {
+    /// Connects to the local server and authenticates as `client_name`.
+    pub fn connect(client_name: &str) -> Result<Self, String> {
+        let path = socket_path();
+        let stream = UnixStream::connect(&path)
+            .map_err(|e| format!("Could not connect to {}: {e}", path.display()))?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        stream
+            .set_write_timeout(Some(TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let mut conn = Self {
+            stream,
+            version: PROTOCOL_VERSION,
+            next_tag: 0,
+            closed: false,
+        };
+
+        let cookie = read_cookie();
+        let mut reply = conn.request(command::AUTH, |t| {
+            t.u32(PROTOCOL_VERSION);
+            t.arbitrary(&cookie);
+        })?;
+        conn.version = (reply.u32()? & VERSION_MASK).min(PROTOCOL_VERSION);
+        if conn.version < 13 {
+            return Err(format!("Protocol version {} is too old", conn.version));
+        }
+
+        let pid = std::process::id().to_string();
+        conn.request(command::SET_CLIENT_NAME, |t| {
+            t.proplist(&[
+                ("application.name", client_name),
+                ("application.process.id", &pid),
+            ]);
+        })?;
+        Ok(conn)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn server_info(&mut self) -> Result<ServerInfo, String> {
+        let mut t = self.request(command::GET_SERVER_INFO, |_| {})?;
+        for _ in 0..4 {
+            // Server name and version, user and host name
+            t.string()?;
+        }
+        t.sample_spec()?;
+        Ok(ServerInfo {
+            default_sink: t.string()?.unwrap_or_default(),
+            default_source: t.string()?.unwrap_or_default(),
+        })
+    }
+
+    pub fn sinks(&mut self) -> Result<Vec<Device>, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SINK_INFO_LIST, |_| {})?;
+        read_list(&mut t, |t| read_device(t, version, true))
+    }
+
+    pub fn sources(&mut self) -> Result<Vec<Device>, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SOURCE_INFO_LIST, |_| {})?;
+        read_list(&mut t, |t| read_device(t, version, false))
+    }
+
+    /// A sink by name, e.g. DEFAULT_SINK.
+    pub fn sink(&mut self, name: &str) -> Result<Device, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SINK_INFO, |t| {
+            t.u32(INVALID_INDEX);
+            t.string(Some(name));
+        })?;
+        read_device(&mut t, version, true)
+    }
+
+    /// A source by name, e.g. DEFAULT_SOURCE.
+    pub fn source(&mut self, name: &str) -> Result<Device, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SOURCE_INFO, |t| {
+            t.u32(INVALID_INDEX);
+            t.string(Some(name));
+        })?;
+        read_device(&mut t, version, false)
+    }
+
+    pub fn sink_inputs(&mut self) -> Result<Vec<Stream>, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SINK_INPUT_INFO_LIST, |_| {})?;
+        read_list(&mut t, |t| read_sink_input(t, version))
+    }
+
+    pub fn sink_input(&mut self, index: u32) -> Result<Stream, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SINK_INPUT_INFO, |t| {
+            t.u32(index);
+        })?;
+        read_sink_input(&mut t, version)
+    }
+
+    pub fn source_outputs(&mut self) -> Result<Vec<Stream>, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SOURCE_OUTPUT_INFO_LIST, |_| {})?;
+        read_list(&mut t, |t| read_source_output(t, version))
+    }
+
+    pub fn source_output(&mut self, index: u32) -> Result<Stream, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_SOURCE_OUTPUT_INFO, |t| {
+            t.u32(index);
+        })?;
+        read_source_output(&mut t, version)
+    }
+
+    pub fn cards(&mut self) -> Result<Vec<Card>, String> {
+        let version = self.version;
+        let mut t = self.request(command::GET_CARD_INFO_LIST, |_| {})?;
+        read_list(&mut t, |t| read_card(t, version))
+    }
+
+    pub fn set_sink_volume(&mut self, index: u32, volume: &[u32]) -> Result<(), String> {
+        self.request(command::SET_SINK_VOLUME, |t| {
+            t.u32(index);
+            t.string(None);
+            t.cvolume(volume);
+        })
+        .map(drop)
+    }
+
+    pub fn set_source_volume(&mut self, index: u32, volume: &[u32]) -> Result<(), String> {
+        self.request(command::SET_SOURCE_VOLUME, |t| {
+            t.u32(index);
+            t.string(None);
+            t.cvolume(volume);
+        })
+        .map(drop)
+    }
+
+    pub fn set_sink_mute(&mut self, index: u32, mute: bool) -> Result<(), String> {
+        self.request(command::SET_SINK_MUTE, |t| {
+            t.u32(index);
+            t.string(None);
+            t.bool(mute);
+        })
+        .map(drop)
+    }
+
+    pub fn set_source_mute(&mut self, index: u32, mute: bool) -> Result<(), String> {
+        self.request(command::SET_SOURCE_MUTE, |t| {
+            t.u32(index);
+            t.string(None);
+            t.bool(mute);
+        })
+        .map(drop)
+    }
+
+    pub fn set_sink_input_volume(&mut self, index: u32, volume: &[u32]) -> Result<(), String> {
+        self.request(command::SET_SINK_INPUT_VOLUME, |t| {
+            t.u32(index);
+            t.cvolume(volume);
+        })
+        .map(drop)
+    }
+
+    pub fn set_sink_input_mute(&mut self, index: u32, mute: bool) -> Result<(), String> {
+        self.request(command::SET_SINK_INPUT_MUTE, |t| {
+            t.u32(index);
+            t.bool(mute);
+        })
+        .map(drop)
+    }
+
+    pub fn set_source_output_volume(&mut self, index: u32, volume: &[u32]) -> Result<(), String> {
+        self.request(command::SET_SOURCE_OUTPUT_VOLUME, |t| {
+            t.u32(index);
+            t.cvolume(volume);
+        })
+        .map(drop)
+    }
+
+    pub fn set_source_output_mute(&mut self, index: u32, mute: bool) -> Result<(), String> {
+        self.request(command::SET_SOURCE_OUTPUT_MUTE, |t| {
+            t.u32(index);
+            t.bool(mute);
+        })
+        .map(drop)
+    }
+
+    pub fn set_default_sink(&mut self, name: &str) -> Result<(), String> {
+        self.request(command::SET_DEFAULT_SINK, |t| {
+            t.string(Some(name));
+        })
+        .map(drop)
+    }
+
+    pub fn set_default_source(&mut self, name: &str) -> Result<(), String> {
+        self.request(command::SET_DEFAULT_SOURCE, |t| {
+            t.string(Some(name));
+        })
+        .map(drop)
+    }
+
+    pub fn move_sink_input(&mut self, index: u32, sink_name: &str) -> Result<(), String> {
+        self.request(command::MOVE_SINK_INPUT, |t| {
+            t.u32(index);
+            t.u32(INVALID_INDEX);
+            t.string(Some(sink_name));
+        })
+        .map(drop)
+    }
+
+    pub fn move_source_output(&mut self, index: u32, source_name: &str) -> Result<(), String> {
+        self.request(command::MOVE_SOURCE_OUTPUT, |t| {
+            t.u32(index);
+            t.u32(INVALID_INDEX);
+            t.string(Some(source_name));
+        })
+        .map(drop)
+    }
+
+    pub fn set_card_profile(&mut self, card_name: &str, profile: &str) -> Result<(), String> {
+        self.request(command::SET_CARD_PROFILE, |t| {
+            t.u32(INVALID_INDEX);
+            t.string(Some(card_name));
+            t.string(Some(profile));
+        })
+        .map(drop)
+    }
+
+    /// Asks for change events on sinks, sources, streams, cards and the
+    /// server. Read them with `next_event`.
+    pub fn subscribe(&mut self) -> Result<(), String> {
+        self.request(command::SUBSCRIBE, |t| {
+            t.u32(SUBSCRIPTION_MASK);
+        })?;
+        // Events may be hours apart
+        self.stream
+            .set_read_timeout(None)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Blocks until the next subscription event, returning what changed.
+    pub fn next_event(&mut self) -> Result<Facility, String> {
+        loop {
+            let mut t = self.recv()?;
+            if t.u32()? != command::SUBSCRIBE_EVENT {
+                continue;
+            }
+            let _tag = t.u32()?;
+            // Facility in the low bits, new/change/remove above it
+            let event_type = t.u32()?;
+
+            return Ok(match event_type & 0xf {
+                0 => Facility::Sink,
+                1 => Facility::Source,
+                2 => Facility::SinkInput,
+                3 => Facility::SourceOutput,
+                7 => Facility::Server,
+                9 => Facility::Card,
+                _ => Facility::Other,
+            });
+        }
+    }
+
+    fn request(
+        &mut self,
+        command: u32,
+        args: impl FnOnce(&mut TagWriter),
+    ) -> Result<TagReader, String> {
+        let tag = self.next_tag;
+        self.next_tag = self.next_tag.wrapping_add(1) & 0x7fff_ffff;
+
+        let mut t = TagWriter::default();
+        t.u32(command);
+        t.u32(tag);
+        args(&mut t);
+        self.send(&t.data)?;
+
+        loop {
+            let mut reply = self.recv()?;
+            let reply_command = reply.u32()?;
+            if reply.u32()? != tag {
+                // e.g. a subscription event in between
+                continue;
+            }
+            return match reply_command {
+                command::REPLY => Ok(reply),
+                command::ERROR => Err(error_message(reply.u32().unwrap_or(0))),
+                other => Err(format!("Unexpected reply command {other}")),
+            };
+        }
+    }
+
+    fn send(&mut self, payload: &[u8]) -> Result<(), String> {
+        let mut packet = Vec::with_capacity(20 + payload.len());
+        // Length, channel, offset (high and low) and flags
+        for field in [payload.len() as u32, CONTROL_CHANNEL, 0, 0, 0] {
+            packet.extend_from_slice(&field.to_be_bytes());
+        }
+        packet.extend_from_slice(payload);
+        self.stream.write_all(&packet).map_err(|e| {
+            self.closed = true;
+            format!("Could not write to the audio server: {e}")
+        })
+    }
+
+    fn recv(&mut self) -> Result<TagReader, String> {
+        loop {
+            let mut header = [0u8; 20];
+            self.read_exact(&mut header)?;
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let channel = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            if length > MAX_PACKET {
+                self.closed = true;
+                return Err(format!("Packet of {length} bytes is too large"));
+            }
+
+            let mut data = vec![0u8; length];
+            self.read_exact(&mut data)?;
+            // Anything else is stream audio, which we never ask for
+            if channel == CONTROL_CHANNEL {
+                return Ok(TagReader { data, pos: 0 });
+            }
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.stream.read_exact(buf).map_err(|e| {
+            self.closed = true;
+            format!("Could not read from the audio server: {e}")
+        })
+    }
+}
+
+fn error_message(code: u32) -> String {
+    let reason = match code {
+        1 => "access denied",
+        2 => "unknown command",
+        3 => "invalid argument",
+        4 => "entity exists",
+        5 => "no such entity",
+        6 => "connection refused",
+        7 => "protocol error",
+        8 => "timeout",
+        9 => "no authentication key",
+        10 => "internal error",
+        11 => "connection terminated",
+        12 => "entity killed",
+        13 => "invalid server",
+        19 => "not supported",
+        _ => "unknown error",
+    };
+    format!("Audio server error: {reason} ({code})")
+}
+
+/// `$PULSE_SERVER` if it names a local socket, else the per-user socket
+/// in `$XDG_RUNTIME_DIR`.
+fn socket_path() -> PathBuf {
+    if let Ok(server) = std::env::var("PULSE_SERVER") {
+        // A list of servers; only local ones can be reached here
+        for entry in server.split_whitespace() {
+            let path = entry.strip_prefix("unix:").unwrap_or(entry);
+            if path.starts_with('/') {
+                return PathBuf::from(path);
+            }
+        }
+    }
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| format!("/run/user/{}", unsafe { libc::getuid() }));
+    PathBuf::from(runtime_dir).join("pulse/native")
+}
+
+/// The auth cookie, or zeros for servers that don't check it (PipeWire,
+/// or PulseAudio with anonymous auth).
+fn read_cookie() -> Vec<u8> {
+    let home = std::env::var("HOME").unwrap_or_default();
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| format!("{home}/.config"));
+
+    let candidates = [
+        std::env::var("PULSE_COOKIE").ok(),
+        Some(format!("{config_home}/pulse/cookie")),
+        Some(format!("{home}/.pulse-cookie")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::read(path).ok())
+        .find(|cookie| cookie.len() >= COOKIE_LEN)
+        .map(|mut cookie| {
+            cookie.truncate(COOKIE_LEN);
+            cookie
+        })
+        .unwrap_or_else(|| vec![0; COOKIE_LEN])
+}
+
+// === Reply parsing ===
+
+fn read_list<T>(
+    t: &mut TagReader,
+    mut read: impl FnMut(&mut TagReader) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let mut items = Vec::new();
+    while !t.is_empty() {
+        items.push(read(t)?);
+    }
+    Ok(items)
+}
+
+fn read_device(t: &mut TagReader, version: u32, is_sink: bool) -> Result<Device, String> {
+    let index = t.u32()?;
+    let name = t.string()?.unwrap_or_default();
+    let description = t.string()?.unwrap_or_default();
+    t.sample_spec()?;
+    let channel_map = t.channel_map()?;
+    let _owner_module = t.u32()?;
+    let volume = t.cvolume()?;
+    let muted = t.bool()?;
+    // A sink's monitor source, or the sink a source monitors
+    let monitor = t.u32()?;
+    let _monitor_name = t.string()?;
+    let _latency = t.usec()?;
+    let _driver = t.string()?;
+    let _flags = t.u32()?;
+
+    let _properties = t.proplist()?;
+    let _configured_latency = t.usec()?;
+
+    if version >= 15 {
+        let _base_volume = t.volume()?;
+        let _state = t.u32()?;
+        let _volume_steps = t.u32()?;
+        let _card = t.u32()?;
+    }
+    if version >= 16 {
+        let ports = t.u32()?;
+        for _ in 0..ports {
+            // Name, description and priority
+            t.string()?;
+            t.string()?;
+            t.u32()?;
+            if version >= 24 {
+                let _available = t.u32()?;
+            }
+        }
+        let _active_port = t.string()?;
+    }
+    if version >= if is_sink { 21 } else { 22 } {
+        let formats = t.u8()?;
+        for _ in 0..formats {
+            t.format_info()?;
+        }
+    }
+
+    Ok(Device {
+        index,
+        name,
+        description,
+        volume,
+        channel_map,
+        muted,
+        monitor_of: if is_sink { INVALID_INDEX } else { monitor },
+    })
+}
+
+fn read_sink_input(t: &mut TagReader, version: u32) -> Result<Stream, String> {
+    let index = t.u32()?;
+    let name = t.string()?.unwrap_or_default();
+    let _owner_module = t.u32()?;
+    let _client = t.u32()?;
+    let device = t.u32()?;
+    t.sample_spec()?;
+    t.channel_map()?;
+    let volume = t.cvolume()?;
+    let _buffer_latency = t.usec()?;
+    let _sink_latency = t.usec()?;
+    let _resample_method = t.string()?;
+    let _driver = t.string()?;
+    let muted = t.bool()?;
+    let properties = t.proplist()?;
+    if version >= 19 {
+        let _corked = t.bool()?;
+    }
+    if version >= 20 {
+        let _has_volume = t.bool()?;
+        let _volume_writable = t.bool()?;
+    }
+    if version >= 21 {
+        t.format_info()?;
+    }
+
+    Ok(Stream {
+        index,
+        name,
+        device,
+        volume,
+        muted,
+        properties,
+    })
+}
+
+fn read_source_output(t: &mut TagReader, version: u32) -> Result<Stream, String> {
+    let index = t.u32()?;
+    let name = t.string()?.unwrap_or_default();
+    let _owner_module = t.u32()?;
+    let _client = t.u32()?;
+    let device = t.u32()?;
+    t.sample_spec()?;
+    t.channel_map()?;
+    let _buffer_latency = t.usec()?;
+    let _source_latency = t.usec()?;
+    let _resample_method = t.string()?;
+    let _driver = t.string()?;
+    let properties = t.proplist()?;
+    if version >= 19 {
+        let _corked = t.bool()?;
+    }
+
+    // Older servers can't set source output volumes
+    let (mut volume, mut muted) = (vec![VOLUME_NORM], false);
+    if version >= 22 {
+        volume = t.cvolume()?;
+        muted = t.bool()?;
+        let _has_volume = t.bool()?;
+        let _volume_writable = t.bool()?;
+        t.format_info()?;
+    }
+
+    Ok(Stream {
+        index,
+        name,
+        device,
+        volume,
+        muted,
+        properties,
+    })
+}
+
+fn read_card(t: &mut TagReader, version: u32) -> Result<Card, String> {
+    let index = t.u32()?;
+    let name = t.string()?.unwrap_or_default();
+    let _owner_module = t.u32()?;
+    let _driver = t.string()?;
+
+    let count = t.u32()?;
+    let mut profiles = Vec::new();
+    for _ in 0..count {
+        let name = t.string()?.unwrap_or_default();
+        let description = t.string()?.unwrap_or_default();
+        let _sinks = t.u32()?;
+        let _sources = t.u32()?;
+        let _priority = t.u32()?;
+        let available = if version >= 29 { t.u32()? != 0 } else { true };
+        profiles.push(CardProfile {
+            name,
+            description,
+            available,
+        });
+    }
+    let active_profile = t.string()?.unwrap_or_default();
+    let properties = t.proplist()?;
+
+    if version >= 26 {
+        let ports = t.u32()?;
+        for _ in 0..ports {
+            // Name, description, priority, availability and direction
+            t.string()?;
+            t.string()?;
+            t.u32()?;
+            t.u32()?;
+            t.u8()?;
+            t.proplist()?;
+            let port_profiles = t.u32()?;
+            for _ in 0..port_profiles {
+                t.string()?;
+            }
+            if version >= 27 {
+                let _latency_offset = t.s64()?;
+            }
+        }
+    }
+
+    Ok(Card {
+        index,
+        name,
+        profiles,
+        active_profile,
+        properties,
+    })
+}
+
+// === Tagged values ===
+
+mod tag {
+    pub const STRING: u8 = b't';
+    pub const STRING_NULL: u8 = b'N';
+    pub const U32: u8 = b'L';
+    pub const U8: u8 = b'B';
+    pub const S64: u8 = b'r';
+    pub const SAMPLE_SPEC: u8 = b'a';
+    pub const ARBITRARY: u8 = b'x';
+    pub const BOOLEAN_TRUE: u8 = b'1';
+    pub const BOOLEAN_FALSE: u8 = b'0';
+    pub const USEC: u8 = b'U';
+    pub const CHANNEL_MAP: u8 = b'm';
+    pub const CVOLUME: u8 = b'v';
+    pub const PROPLIST: u8 = b'P';
+    pub const VOLUME: u8 = b'V';
+    pub const FORMAT_INFO: u8 = b'f';
+}
+
+#[derive(Default)]
+struct TagWriter {
+    data: Vec<u8>,
+}
+
+impl TagWriter {
+    fn u32(&mut self, value: u32) {
+        self.data.push(tag::U32);
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.data.push(if value {
+            tag::BOOLEAN_TRUE
+        } else {
+            tag::BOOLEAN_FALSE
+        });
+    }
+
+    fn string(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.data.push(tag::STRING);
+                self.data.extend_from_slice(value.as_bytes());
+                self.data.push(0);
+            }
+            None => self.data.push(tag::STRING_NULL),
+        }
+    }
+
+    fn arbitrary(&mut self, value: &[u8]) {
+        self.data.push(tag::ARBITRARY);
+        self.data
+            .extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.data.extend_from_slice(value);
+    }
+
+    fn cvolume(&mut self, volume: &[u32]) {
+        self.data.push(tag::CVOLUME);
+        self.data.push(volume.len() as u8);
+        for v in volume {
+            self.data.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    /// String properties; values go over the wire NUL-terminated.
+    fn proplist(&mut self, properties: &[(&str, &str)]) {
+        self.data.push(tag::PROPLIST);
+        for (key, value) in properties {
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.push(0);
+            self.string(Some(key));
+            self.u32(bytes.len() as u32);
+            self.arbitrary(&bytes);
+        }
+        self.string(None);
+    }
+}
+
+struct TagReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl TagReader {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        if self.data.len() - self.pos < len {
+            return Err("Truncated reply from the audio server".to_string());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        let found = self.take(1)?[0];
+        if found != expected {
+            return Err(format!(
+                "Malformed reply: expected '{}', found '{}'",
+                expected as char, found as char
+            ));
+        }
+        Ok(())
+    }
+
+    fn raw_u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn raw_u64(&mut self) -> Result<u64, String> {
+        Ok((self.raw_u32()? as u64) << 32 | self.raw_u32()? as u64)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        self.expect(tag::U32)?;
+        self.raw_u32()
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        self.expect(tag::U8)?;
+        Ok(self.take(1)?[0])
+    }
+
+    fn s64(&mut self) -> Result<i64, String> {
+        self.expect(tag::S64)?;
+        Ok(self.raw_u64()? as i64)
+    }
+
+    fn usec(&mut self) -> Result<u64, String> {
+        self.expect(tag::USEC)?;
+        self.raw_u64()
+    }
+
+    fn volume(&mut self) -> Result<u32, String> {
+        self.expect(tag::VOLUME)?;
+        self.raw_u32()
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.take(1)?[0] {
+            tag::BOOLEAN_TRUE => Ok(true),
+            tag::BOOLEAN_FALSE => Ok(false),
+            other => Err(format!(
+                "Malformed reply: expected a boolean, found '{}'",
+                other as char
+            )),
+        }
+    }
+
+    fn string(&mut self) -> Result<Option<String>, String> {
+        match self.take(1)?[0] {
+            tag::STRING_NULL => Ok(None),
+            tag::STRING => {
+                let rest = &self.data[self.pos..];
+                let len = rest
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or("Malformed reply: unterminated string")?;
+                let value = String::from_utf8_lossy(&rest[..len]).into_owned();
+                self.pos += len + 1;
+                Ok(Some(value))
+            }
+            other => Err(format!(
+                "Malformed reply: expected a string, found '{}'",
+                other as char
+            )),
+        }
+    }
+
+    fn arbitrary(&mut self) -> Result<Vec<u8>, String> {
+        self.expect(tag::ARBITRARY)?;
+        let len = self.raw_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn sample_spec(&mut self) -> Result<(), String> {
+        self.expect(tag::SAMPLE_SPEC)?;
+        // Format, channels and rate
+        self.take(1 + 1 + 4).map(drop)
+    }
+
+    /// Channel positions, e.g. 1 front left, 2 front right.
+    fn channel_map(&mut self) -> Result<Vec<u8>, String> {
+        self.expect(tag::CHANNEL_MAP)?;
+        let channels = self.take(1)?[0] as usize;
+        self.take(channels).map(<[u8]>::to_vec)
+    }
+
+    fn cvolume(&mut self) -> Result<Vec<u32>, String> {
+        self.expect(tag::CVOLUME)?;
+        let channels = self.take(1)?[0];
+        (0..channels).map(|_| self.raw_u32()).collect()
+    }
+
+    fn format_info(&mut self) -> Result<(), String> {
+        self.expect(tag::FORMAT_INFO)?;
+        let _encoding = self.u8()?;
+        self.proplist().map(drop)
+    }
+
+    /// Properties whose values are text; binary ones come out lossy.
+    fn proplist(&mut self) -> Result<HashMap<String, String>, String> {
+        self.expect(tag::PROPLIST)?;
+        let mut properties = HashMap::new();
+        while let Some(key) = self.string()? {
+            let len = self.u32()? as usize;
+            let value = self.arbitrary()?;
+            if value.len() != len {
+                return Err(format!("Malformed reply: bad length for property {key}"));
+            }
+            let text = value.strip_suffix(&[0]).unwrap_or(&value);
+            properties.insert(key, String::from_utf8_lossy(text).into_owned());
+        }
+        Ok(properties)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(writer: TagWriter) -> TagReader {
+        TagReader {
+            data: writer.data,
+            pos: 0,
+        }
+    }
+
+    #[test]
+    fn tags_round_trip() {
+        let mut w = TagWriter::default();
+        w.u32(0xdead_beef);
+        w.bool(true);
+        w.bool(false);
+        w.string(Some("alsa_output.pci"));
+        w.string(None);
+        w.string(Some(""));
+        w.arbitrary(&[1, 2, 3]);
+        w.cvolume(&[VOLUME_NORM, VOLUME_NORM / 2]);
+        w.proplist(&[("application.name", "crowbar"), ("media.role", "")]);
+
+        let mut r = reader(w);
+        assert_eq!(r.u32().unwrap(), 0xdead_beef);
+        assert!(r.bool().unwrap());
+        assert!(!r.bool().unwrap());
+        assert_eq!(r.string().unwrap().as_deref(), Some("alsa_output.pci"));
+        assert_eq!(r.string().unwrap(), None);
+        assert_eq!(r.string().unwrap().as_deref(), Some(""));
+        assert_eq!(r.arbitrary().unwrap(), vec![1, 2, 3]);
+        assert_eq!(r.cvolume().unwrap(), vec![VOLUME_NORM, VOLUME_NORM / 2]);
+        let properties = r.proplist().unwrap();
+        assert_eq!(properties["application.name"], "crowbar");
+        assert_eq!(properties["media.role"], "");
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn reads_tags_only_servers_send() {
+        let mut data = vec![tag::U8, 7];
+        data.push(tag::S64);
+        data.extend_from_slice(&(-2i64).to_be_bytes());
+        data.push(tag::USEC);
+        data.extend_from_slice(&0x1_0000_0002u64.to_be_bytes());
+        data.push(tag::VOLUME);
+        data.extend_from_slice(&VOLUME_NORM.to_be_bytes());
+        data.extend_from_slice(&[tag::SAMPLE_SPEC, 3, 2, 0, 0, 0xac, 0x44]);
+        data.extend_from_slice(&[tag::CHANNEL_MAP, 2, 1, 2]);
+
+        let mut r = TagReader { data, pos: 0 };
+        assert_eq!(r.u8().unwrap(), 7);
+        assert_eq!(r.s64().unwrap(), -2);
+        assert_eq!(r.usec().unwrap(), 0x1_0000_0002);
+        assert_eq!(r.volume().unwrap(), VOLUME_NORM);
+        r.sample_spec().unwrap();
+        assert_eq!(r.channel_map().unwrap(), vec![1, 2]);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let mut w = TagWriter::default();
+        w.u32(1);
+        let mut r = reader(w);
+        assert!(r.string().is_err());
+
+        let mut r = TagReader {
+            data: vec![tag::U32, 0, 0],
+            pos: 0,
+        };
+        assert!(r.u32().is_err());
+
+        let mut r = TagReader {
+            data: vec![tag::STRING, b'a', b'b'],
+            pos: 0,
+        };
+        assert!(r.string().is_err());
+
+        // Claims more channels than there are bytes
+        let mut r = TagReader {
+            data: vec![tag::CVOLUME, 4, 0, 0, 0, 1],
+            pos: 0,
+        };
+        assert!(r.cvolume().is_err());
+    }
+
+    #[test]
+    fn volume_percent_takes_the_loudest_channel() {
+        assert_eq!(volume_percent(&[VOLUME_NORM]), 100);
+        assert_eq!(volume_percent(&[VOLUME_NORM / 4, VOLUME_NORM / 2]), 50);
+        assert_eq!(volume_percent(&[VOLUME_NORM * 3 / 2]), 150);
+        assert_eq!(volume_percent(&[]), 0);
+        // Rounds to the nearest percent
+        assert_eq!(volume_percent(&[VOLUME_NORM * 996 / 1000]), 100);
+    }
+
+    #[test]
+    fn scale_volume_keeps_the_balance() {
+        assert_eq!(
+            scale_volume(&[VOLUME_NORM, VOLUME_NORM / 2], 50),
+            vec![VOLUME_NORM / 2, VOLUME_NORM / 4]
+        );
+        assert_eq!(volume_percent(&scale_volume(&[1234, 5678], 80)), 80);
+        // Silent channels all come up together
+        assert_eq!(scale_volume(&[0, 0], 100), vec![VOLUME_NORM; 2]);
+        assert_eq!(scale_volume(&[], 100), vec![VOLUME_NORM]);
+        assert_eq!(scale_volume(&[VOLUME_NORM], -5), vec![0]);
+    }
+}
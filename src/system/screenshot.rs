@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenshotBackend {
+    Grim,
+    // Shell command template with {geometry}, {x}, {y}, {w}, {h}, {scale} and {output}
+    Custom(String),
+    None,
+}
+
+impl ScreenshotBackend {
+    pub fn from_config(value: &str) -> Self {
+        match value.trim() {
+            "" | "none" => Self::None,
+            "grim" => Self::Grim,
+            template => Self::Custom(template.to_string()),
+        }
+    }
+}
+
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    // Geometry string in the `slurp`/`grim -g` format: "X,Y WxH"
+    pub fn geometry(&self) -> String {
+        format!("{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Where the capture of the window at `address` goes. None without a
+/// runtime dir, as a shared dir like /tmp would expose the captures.
+pub fn preview_path(address: &str) -> Option<PathBuf> {
+    let runtime = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())?;
+    Some(
+        PathBuf::from(runtime)
+            .join("crowbar/previews")
+            .join(format!("{}.png", address.trim_start_matches("0x"))),
+    )
+}
+
+/// Deletes the capture of a window that closed.
+pub fn remove_preview(address: &str) {
+    if let Some(path) = preview_path(address) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Captures `region` (in global layout coordinates) into a PNG at `output`,
+/// scaled by `scale`.
+pub fn capture_region(
+    backend: &ScreenshotBackend,
+    region: &Region,
+    scale: f64,
+    output: &Path,
+) -> Result<(), String> {
+    if region.width <= 0 || region.height <= 0 {
+        return Err("Empty capture region".to_string());
+    }
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create preview dir: {e}"))?;
+    }
+
+    let geometry = region.geometry();
+    let scale = format!("{:.3}", scale.clamp(0.05, 1.0));
+
+    let status = match backend {
+        ScreenshotBackend::Grim => Command::new("grim")
+            .args(["-g", &geometry, "-s", &scale, "-t", "png", "-l", "0"])
+            .arg(output)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status(),
+        ScreenshotBackend::Custom(template) => {
+            let cmd = template
+                .replace("{geometry}", &geometry)
+                .replace("{x}", &region.x.to_string())
+                .replace("{y}", &region.y.to_string())
+                .replace("{w}", &region.width.to_string())
+                .replace("{h}", &region.height.to_string())
+                .replace("{scale}", &scale)
+                .replace("{output}", &output.to_string_lossy());
+            Command::new("sh")
+                .args(["-c", &cmd])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+        }
+        ScreenshotBackend::None => return Err("Screenshot backend disabled".to_string()),
+    }
+    .map_err(|e| format!("Failed to run screenshot backend: {e}"))?;
+
+    if status.success() && output.exists() {
+        Ok(())
+    } else {
+        Err(format!("Screenshot backend failed: {status}"))
+    }
+}
@@ -0,0 +1,1071 @@
+use crate::system::dbusmenu::{self, MenuItem};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+
+// `a(iiay)`: width, height, ARGB32 pixels
+type RawPixmaps = Vec<(i32, i32, Vec<u8>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ItemStatus {
+    // Nothing to report; the item may be hidden
+    Passive,
+    #[default]
+    Active,
+    NeedsAttention,
+}
+
+/// One size of an item icon: ARGB32 pixels in network byte order.
+#[derive(Debug, Clone)]
+pub struct Pixmap {
+    pub width: i32,
+    pub height: i32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolTip {
+    pub title: String,
+    // May contain simple markup
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrayItem {
+    pub service: String,
+    pub path: String,
+    pub id: String,
+    pub title: String,
+    pub status: ItemStatus,
+    pub icon_name: String,
+    pub icon_pixmaps: Vec<Pixmap>,
+    // Extra directory to look up the item's icon names in
+    pub icon_theme_path: String,
+    pub overlay_icon_name: String,
+    pub overlay_pixmaps: Vec<Pixmap>,
+    pub attention_icon_name: String,
+    pub attention_pixmaps: Vec<Pixmap>,
+    pub tooltip: Option<ToolTip>,
+    // Object path of the item's com.canonical.dbusmenu menu
+    pub menu: Option<String>,
+    // The item only has a menu; left click opens it instead of Activate
+    pub item_is_menu: bool,
+}
+
+impl TrayItem {
+    /// An item without properties for a registered service string, which
+    /// is either a bus name or a bus name followed by an object path.
+    fn new(service_str: &str) -> Self {
+        let (service, path) = match service_str.find('/') {
+            Some(idx) => (&service_str[..idx], &service_str[idx..]),
+            None => (service_str, "/StatusNotifierItem"),
+        };
+        Self {
+            service: service.to_string(),
+            path: path.to_string(),
+            id: String::new(),
+            title: String::new(),
+            status: ItemStatus::default(),
+            icon_name: String::new(),
+            icon_pixmaps: Vec::new(),
+            icon_theme_path: String::new(),
+            overlay_icon_name: String::new(),
+            overlay_pixmaps: Vec::new(),
+            attention_icon_name: String::new(),
+            attention_pixmaps: Vec::new(),
+            tooltip: None,
+            menu: None,
+            item_is_menu: false,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        format!("{}{}", self.service, self.path)
+    }
+
+    /// Title, else the application id, else the bus name.
+    pub fn display_name(&self) -> &str {
+        [&self.title, &self.id]
+            .into_iter()
+            .find(|s| !s.is_empty())
+            .unwrap_or(&self.service)
+    }
+
+    /// Whether `name` is the item's Id, title or bus name, ignoring case.
+    pub fn matches(&self, name: &str) -> bool {
+        [&self.id, &self.title, &self.service]
+            .into_iter()
+            .any(|s| !s.is_empty() && s.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Pixmap {
+    /// The pixmap closest to `size`, preferring larger ones to scale down.
+    pub fn best(pixmaps: &[Pixmap], size: i32) -> Option<&Pixmap> {
+        pixmaps
+            .iter()
+            .filter(|p| p.width > 0 && p.height > 0)
+            // Sizes come from the item, so don't trust them not to overflow
+            .filter(|p| {
+                (p.width as usize)
+                    .checked_mul(p.height as usize)
+                    .and_then(|n| n.checked_mul(4))
+                    .is_some_and(|needed| p.data.len() >= needed)
+            })
+            .min_by_key(|p| {
+                let extent = p.width.max(p.height);
+                (extent < size, (extent - size).abs())
+            })
+    }
+}
+
+#[derive(Clone)]
+pub enum TrayEvent {
+    Added(TrayItem),
+    // The item's properties changed
+    Updated(TrayItem),
+    Removed(String),
+    // Menu fetched for the item with this key, ready to show
+    Menu(String, MenuItem),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ItemAction {
+    Activate,
+    SecondaryActivate,
+    ContextMenu,
+    // Delta and "vertical" or "horizontal"
+    Scroll(i32, &'static str),
+}
+
+/// Requests from the GTK side, run on the D-Bus thread.
+pub enum TrayCommand {
+    Item(TrayItem, ItemAction),
+    OpenMenu(TrayItem),
+    MenuClicked {
+        service: String,
+        menu: String,
+        id: i32,
+    },
+}
+
+/// Serves (or follows) the StatusNotifierWatcher on its own thread, sending
+/// item changes to `sender` and running `commands` against the items.
+pub fn start(
+    commands: async_channel::Receiver<TrayCommand>,
+    sender: async_channel::Sender<TrayEvent>,
+) {
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("Warning: Could not create tokio runtime for systray: {e}");
+                return;
+            }
+        };
+
+        rt.block_on(async move {
+            let Ok(conn) = zbus::Connection::session().await else {
+                eprintln!("Warning: Could not connect to session bus for systray");
+                return;
+            };
+            run(conn, commands, sender).await;
+        });
+    });
+}
+
+/// Registers as a StatusNotifierHost and serves the watcher, or follows
+/// the running watcher until it exits and then takes over.
+async fn run(
+    conn: zbus::Connection,
+    commands: async_channel::Receiver<TrayCommand>,
+    sender: async_channel::Sender<TrayEvent>,
+) {
+    use futures_util::StreamExt;
+    use zbus::fdo::{RequestNameFlags, RequestNameReply};
+
+    let tray = Tray::new(conn.clone(), sender);
+    tokio::spawn(handle_commands(conn.clone(), commands, tray.sender.clone()));
+
+    let host = format!("org.kde.StatusNotifierHost-{}", std::process::id());
+    if let Err(e) = conn.request_name(host.as_str()).await {
+        eprintln!("Warning: Could not register {host}: {e}");
+    }
+
+    let watcher = Watcher::new(tray.clone());
+    let object_server = conn.object_server();
+    if let Err(e) = futures_util::try_join!(
+        object_server.at(WATCHER_PATH, KdeWatcher(watcher.clone())),
+        object_server.at(WATCHER_PATH, FreedesktopWatcher(watcher.clone())),
+    ) {
+        eprintln!("Warning: Could not serve StatusNotifierWatcher: {e}");
+        run_client_mode(tray, host).await;
+        return;
+    }
+    // Before asking for the name, so no early exit is missed
+    tokio::spawn(watch_name_changes(conn.clone(), watcher.clone()));
+
+    let acquired = match zbus::fdo::DBusProxy::new(&conn).await {
+        Ok(dbus) => dbus.receive_name_acquired().await.ok(),
+        Err(_) => None,
+    };
+
+    // No flags: queue behind another watcher (e.g. waybar) rather than
+    // replacing it or failing, so its exit hands the name to us
+    let reply = conn
+        .request_name_with_flags(KDE_WATCHER, Default::default())
+        .await;
+    match (reply, acquired) {
+        (Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner), _) => {}
+        (Ok(RequestNameReply::InQueue), Some(mut acquired)) => {
+            let client = tokio::spawn(run_client_mode(tray.clone(), host.clone()));
+            while let Some(signal) = acquired.next().await {
+                if signal.args().is_ok_and(|args| args.name() == KDE_WATCHER) {
+                    break;
+                }
+            }
+            client.abort();
+            // Keep what the previous watcher knew; apps re-register anyway
+            watcher.adopt_items(tray.watched_items()).await;
+        }
+        _ => {
+            run_client_mode(tray, host).await;
+            return;
+        }
+    }
+
+    // The freedesktop name is optional; most apps only look for KDE's
+    let _ = conn
+        .request_name_with_flags(FREEDESKTOP_WATCHER, RequestNameFlags::DoNotQueue.into())
+        .await;
+    watcher.register_host(&host).await;
+
+    // The spawned tasks do the rest
+    std::future::pending::<()>().await;
+}
+
+// ── StatusNotifierWatcher D-Bus service ──
+
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+const KDE_WATCHER: &str = "org.kde.StatusNotifierWatcher";
+const FREEDESKTOP_WATCHER: &str = "org.freedesktop.StatusNotifierWatcher";
+
+#[derive(Default)]
+struct Registry {
+    // Bus names, optionally followed by an object path
+    items: Vec<String>,
+    // Bus names of registered hosts
+    hosts: Vec<String>,
+}
+
+/// The watcher state, served under both the KDE and freedesktop
+/// interface names.
+#[derive(Clone)]
+struct Watcher {
+    registry: Arc<tokio::sync::Mutex<Registry>>,
+    tray: Tray,
+}
+
+impl Watcher {
+    fn new(tray: Tray) -> Self {
+        Self {
+            registry: Arc::default(),
+            tray,
+        }
+    }
+
+    async fn register_item(&self, service: &str, sender: Option<&str>) {
+        // Items may pass just an object path on their own connection
+        let full_service = if service.starts_with('/') {
+            format!("{}{}", sender.unwrap_or_default(), service)
+        } else {
+            service.to_string()
+        };
+
+        let mut registry = self.registry.lock().await;
+        if registry.items.contains(&full_service) {
+            return;
+        }
+        registry.items.push(full_service.clone());
+        drop(registry);
+
+        self.emit(WatcherSignal::ItemRegistered(&full_service))
+            .await;
+        self.tray.add_item(&full_service);
+    }
+
+    async fn register_host(&self, service: &str) {
+        let mut registry = self.registry.lock().await;
+        if registry.hosts.iter().any(|h| h == service) {
+            return;
+        }
+        registry.hosts.push(service.to_string());
+        drop(registry);
+
+        self.emit(WatcherSignal::HostRegistered).await;
+    }
+
+    async fn adopt_items(&self, items: Vec<String>) {
+        let mut registry = self.registry.lock().await;
+        for item in items {
+            if !registry.items.contains(&item) {
+                registry.items.push(item);
+            }
+        }
+    }
+
+    /// Drops the items and hosts of a service that left the bus. Removing
+    /// the icons is up to each item's own watch task.
+    async fn remove_service(&self, name: &str) {
+        let mut registry = self.registry.lock().await;
+        let (gone, kept): (Vec<String>, Vec<String>) = std::mem::take(&mut registry.items)
+            .into_iter()
+            .partition(|item| item.split('/').next() == Some(name));
+        registry.items = kept;
+        registry.hosts.retain(|host| host != name);
+        drop(registry);
+
+        for item in &gone {
+            self.emit(WatcherSignal::ItemUnregistered(item)).await;
+        }
+    }
+
+    async fn items(&self) -> Vec<String> {
+        self.registry.lock().await.items.clone()
+    }
+
+    async fn has_hosts(&self) -> bool {
+        !self.registry.lock().await.hosts.is_empty()
+    }
+
+    async fn emit(&self, signal: WatcherSignal<'_>) {
+        let result = match zbus::object_server::SignalContext::new(&self.tray.conn, WATCHER_PATH) {
+            Ok(ctxt) => futures_util::try_join!(
+                KdeWatcher::emit(&ctxt, &signal),
+                FreedesktopWatcher::emit(&ctxt, &signal),
+            )
+            .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: Could not emit watcher signal: {e}");
+        }
+    }
+}
+
+enum WatcherSignal<'a> {
+    ItemRegistered(&'a str),
+    ItemUnregistered(&'a str),
+    HostRegistered,
+}
+
+/// The same watcher interface under each of its bus names.
+macro_rules! watcher_interface {
+    ($name:ident, $interface:literal) => {
+        struct $name(Watcher);
+
+        #[zbus::interface(name = $interface)]
+        impl $name {
+            async fn register_status_notifier_item(
+                &self,
+                service: &str,
+                #[zbus(header)] header: zbus::message::Header<'_>,
+            ) {
+                let sender = header.sender().map(|s| s.to_string());
+                self.0.register_item(service, sender.as_deref()).await;
+            }
+
+            async fn register_status_notifier_host(
+                &self,
+                service: &str,
+                #[zbus(header)] header: zbus::message::Header<'_>,
+            ) {
+                let sender = header.sender().map(|s| s.to_string());
+                match (service.is_empty(), sender) {
+                    (true, Some(sender)) => self.0.register_host(&sender).await,
+                    _ => self.0.register_host(service).await,
+                }
+            }
+
+            #[zbus(property)]
+            async fn registered_status_notifier_items(&self) -> Vec<String> {
+                self.0.items().await
+            }
+
+            #[zbus(property)]
+            async fn is_status_notifier_host_registered(&self) -> bool {
+                self.0.has_hosts().await
+            }
+
+            #[zbus(property)]
+            fn protocol_version(&self) -> i32 {
+                0
+            }
+
+            #[zbus(signal)]
+            async fn status_notifier_item_registered(
+                signal_ctxt: &zbus::object_server::SignalContext<'_>,
+                service: &str,
+            ) -> zbus::Result<()>;
+
+            #[zbus(signal)]
+            async fn status_notifier_item_unregistered(
+                signal_ctxt: &zbus::object_server::SignalContext<'_>,
+                service: &str,
+            ) -> zbus::Result<()>;
+
+            #[zbus(signal)]
+            async fn status_notifier_host_registered(
+                signal_ctxt: &zbus::object_server::SignalContext<'_>,
+            ) -> zbus::Result<()>;
+        }
+
+        impl $name {
+            async fn emit(
+                ctxt: &zbus::object_server::SignalContext<'_>,
+                signal: &WatcherSignal<'_>,
+            ) -> zbus::Result<()> {
+                match signal {
+                    WatcherSignal::ItemRegistered(service) => {
+                        Self::status_notifier_item_registered(ctxt, service).await
+                    }
+                    WatcherSignal::ItemUnregistered(service) => {
+                        Self::status_notifier_item_unregistered(ctxt, service).await
+                    }
+                    WatcherSignal::HostRegistered => {
+                        Self::status_notifier_host_registered(ctxt).await
+                    }
+                }
+            }
+        }
+    };
+}
+
+watcher_interface!(KdeWatcher, "org.kde.StatusNotifierWatcher");
+watcher_interface!(FreedesktopWatcher, "org.freedesktop.StatusNotifierWatcher");
+
+// ── NameOwnerChanged watcher ──
+
+async fn watch_name_changes(conn: zbus::Connection, watcher: Watcher) {
+    use futures_util::StreamExt;
+
+    let Ok(dbus_proxy) = zbus::fdo::DBusProxy::new(&conn).await else {
+        eprintln!("Warning: Could not create DBus proxy for name watching");
+        return;
+    };
+
+    let Ok(mut stream) = dbus_proxy.receive_name_owner_changed().await else {
+        eprintln!("Warning: Could not subscribe to NameOwnerChanged");
+        return;
+    };
+
+    while let Some(signal) = stream.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+
+        // Only care when a name vanishes (new_owner is empty)
+        if args.new_owner().is_none() {
+            watcher.remove_service(args.name().as_str()).await;
+        }
+    }
+}
+
+// ── Client mode ──
+
+/// Follows another watcher: registers `host` with it and shows its items.
+async fn run_client_mode(tray: Tray, host: String) {
+    let Ok(proxy) = zbus::Proxy::new(&tray.conn, KDE_WATCHER, WATCHER_PATH, KDE_WATCHER).await
+    else {
+        return;
+    };
+
+    if let Err(e) = proxy
+        .call::<_, _, ()>("RegisterStatusNotifierHost", &(host.as_str(),))
+        .await
+    {
+        eprintln!("Warning: Could not register tray host with the watcher: {e}");
+    }
+
+    // Subscribe first so nothing registered meanwhile is missed
+    let Ok(mut stream) = proxy.receive_signal("StatusNotifierItemRegistered").await else {
+        return;
+    };
+
+    // Fetch currently registered items
+    if let Ok(registered) = proxy
+        .get_property::<Vec<String>>("RegisteredStatusNotifierItems")
+        .await
+    {
+        for service_str in registered {
+            tray.add_item(&service_str);
+        }
+    }
+
+    use futures_util::StreamExt;
+    while let Some(signal) = stream.next().await {
+        if let Ok(args) = signal.body().deserialize::<(String,)>() {
+            tray.add_item(&args.0);
+        }
+    }
+}
+
+// ── Item properties ──
+
+/// The host side: reports items to the GTK side and keeps them updated.
+#[derive(Clone)]
+struct Tray {
+    conn: zbus::Connection,
+    sender: async_channel::Sender<TrayEvent>,
+    // Keys of the items being watched
+    watched: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+impl Tray {
+    fn new(conn: zbus::Connection, sender: async_channel::Sender<TrayEvent>) -> Self {
+        Self {
+            conn,
+            sender,
+            watched: Arc::default(),
+        }
+    }
+
+    /// Reports a newly registered item and keeps it up to date until its
+    /// service leaves the bus. Items already shown are left alone.
+    fn add_item(&self, service_str: &str) {
+        let item = TrayItem::new(service_str);
+        if self.watched.lock().unwrap().insert(item.key()) {
+            tokio::spawn(self.clone().watch_item(item));
+        }
+    }
+
+    /// Keys of the items shown, which double as their registration strings.
+    fn watched_items(&self) -> Vec<String> {
+        self.watched.lock().unwrap().iter().cloned().collect()
+    }
+
+    async fn watch_item(self, mut item: TrayItem) {
+        use futures_util::StreamExt;
+
+        // Subscribe first so no change after the first read is missed
+        let changes = self.subscribe(&item).await;
+        if let Err(e) = &changes {
+            eprintln!("Warning: Could not watch tray item {}: {e}", item.service);
+        }
+        if let Err(e) = fetch_item_properties(&self.conn, &mut item).await {
+            eprintln!("Warning: Could not read tray item {}: {e}", item.service);
+        }
+        let _ = self.sender.send(TrayEvent::Added(item.clone())).await;
+
+        let Ok(mut changes) = changes else {
+            return;
+        };
+        while let Some(true) = changes.next().await {
+            if fetch_item_properties(&self.conn, &mut item).await.is_ok() {
+                let _ = self.sender.send(TrayEvent::Updated(item.clone())).await;
+            }
+        }
+        self.watched.lock().unwrap().remove(&item.key());
+        let _ = self.sender.send(TrayEvent::Removed(item.key())).await;
+    }
+
+    /// The item's New* signals as true, and false once its service has
+    /// left the bus.
+    async fn subscribe(
+        &self,
+        item: &TrayItem,
+    ) -> zbus::Result<impl futures_util::Stream<Item = bool>> {
+        use futures_util::StreamExt;
+
+        let proxy = zbus::proxy::Builder::<zbus::Proxy>::new(&self.conn)
+            .destination(item.service.clone())?
+            .path(item.path.clone())?
+            .interface(ITEM_INTERFACE)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?;
+        let signals = proxy.receive_all_signals().await?.map(|_| true);
+
+        let dbus = zbus::fdo::DBusProxy::new(&self.conn).await?;
+        let owners = dbus
+            .receive_name_owner_changed_with_args(&[(0, item.service.as_str())])
+            .await?
+            .map(|signal| signal.args().is_ok_and(|args| args.new_owner().is_some()));
+
+        // It may have left before we subscribed
+        let alive = dbus.name_has_owner(proxy.destination().to_owned()).await?;
+        let gone = futures_util::stream::iter((!alive).then_some(false));
+        Ok(gone.chain(futures_util::stream::select(signals, owners)))
+    }
+}
+
+async fn fetch_item_properties(conn: &zbus::Connection, item: &mut TrayItem) -> zbus::Result<()> {
+    // One round trip instead of a call per property
+    let proxy = zbus::Proxy::new(
+        conn,
+        item.service.as_str(),
+        item.path.as_str(),
+        "org.freedesktop.DBus.Properties",
+    )
+    .await?;
+    let mut props: HashMap<String, OwnedValue> = proxy.call("GetAll", &(ITEM_INTERFACE,)).await?;
+
+    let string = |props: &mut HashMap<String, OwnedValue>, key: &str| {
+        take::<String>(props, key).unwrap_or_default()
+    };
+    item.id = string(&mut props, "Id");
+    item.title = string(&mut props, "Title");
+    item.icon_name = string(&mut props, "IconName");
+    item.icon_theme_path = string(&mut props, "IconThemePath");
+    item.overlay_icon_name = string(&mut props, "OverlayIconName");
+    item.attention_icon_name = string(&mut props, "AttentionIconName");
+    item.status = match string(&mut props, "Status").as_str() {
+        "Passive" => ItemStatus::Passive,
+        "NeedsAttention" => ItemStatus::NeedsAttention,
+        _ => ItemStatus::Active,
+    };
+
+    item.icon_pixmaps = pixmaps(take(&mut props, "IconPixmap"));
+    item.overlay_pixmaps = pixmaps(take(&mut props, "OverlayIconPixmap"));
+    item.attention_pixmaps = pixmaps(take(&mut props, "AttentionIconPixmap"));
+
+    // `(sa(iiay)ss)`: icon name, icon pixmaps, title, description
+    item.tooltip = take::<(String, RawPixmaps, String, String)>(&mut props, "ToolTip")
+        .map(|(_, _, title, description)| ToolTip { title, description })
+        .filter(|t| !t.title.is_empty() || !t.description.is_empty());
+
+    // Some items report "/" for no menu
+    item.menu = take::<OwnedObjectPath>(&mut props, "Menu")
+        .filter(|menu| menu.as_str() != "/")
+        .map(|menu| menu.to_string());
+    item.item_is_menu = take::<bool>(&mut props, "ItemIsMenu").unwrap_or(false);
+
+    Ok(())
+}
+
+fn take<T: TryFrom<OwnedValue>>(props: &mut HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    props.remove(key).and_then(|value| T::try_from(value).ok())
+}
+
+fn pixmaps(raw: Option<RawPixmaps>) -> Vec<Pixmap> {
+    raw.unwrap_or_default()
+        .into_iter()
+        .map(|(width, height, data)| Pixmap {
+            width,
+            height,
+            data,
+        })
+        .collect()
+}
+
+// ── Item actions and menus ──
+
+async fn handle_commands(
+    conn: zbus::Connection,
+    commands: async_channel::Receiver<TrayCommand>,
+    sender: async_channel::Sender<TrayEvent>,
+) {
+    while let Ok(command) = commands.recv().await {
+        // Run each on its own so a hung app doesn't hold up the others
+        tokio::spawn(run_command(conn.clone(), command, sender.clone()));
+    }
+}
+
+async fn run_command(
+    conn: zbus::Connection,
+    command: TrayCommand,
+    sender: async_channel::Sender<TrayEvent>,
+) {
+    match command {
+        TrayCommand::Item(item, action) => {
+            let result = call_item_action(&conn, &item, action).await;
+            if let Err(e) = result {
+                // Items without Activate usually still have a menu
+                if matches!(action, ItemAction::Activate) && item.menu.is_some() {
+                    open_menu(&conn, item, &sender).await;
+                } else {
+                    eprintln!("Warning: Tray item {} failed {action:?}: {e}", item.service);
+                }
+            }
+        }
+        TrayCommand::OpenMenu(item) => open_menu(&conn, item, &sender).await,
+        TrayCommand::MenuClicked { service, menu, id } => {
+            if let Err(e) = dbusmenu::send_clicked(&conn, &service, &menu, id).await {
+                eprintln!("Warning: Tray menu event for {service} failed: {e}");
+            }
+        }
+    }
+}
+
+async fn call_item_action(
+    conn: &zbus::Connection,
+    item: &TrayItem,
+    action: ItemAction,
+) -> zbus::Result<()> {
+    let proxy = zbus::Proxy::new(
+        conn,
+        item.service.as_str(),
+        item.path.as_str(),
+        ITEM_INTERFACE,
+    )
+    .await?;
+    // Wayland doesn't give us global coordinates; apps place menus themselves
+    match action {
+        ItemAction::Activate => proxy.call("Activate", &(0i32, 0i32)).await,
+        ItemAction::SecondaryActivate => proxy.call("SecondaryActivate", &(0i32, 0i32)).await,
+        ItemAction::ContextMenu => proxy.call("ContextMenu", &(0i32, 0i32)).await,
+        ItemAction::Scroll(delta, orientation) => proxy.call("Scroll", &(delta, orientation)).await,
+    }
+}
+
+async fn open_menu(
+    conn: &zbus::Connection,
+    item: TrayItem,
+    sender: &async_channel::Sender<TrayEvent>,
+) {
+    let Some(menu_path) = &item.menu else {
+        return;
+    };
+    match dbusmenu::fetch_menu(conn, &item.service, menu_path).await {
+        Ok(menu) => {
+            let _ = sender.send(TrayEvent::Menu(item.key(), menu)).await;
+        }
+        Err(e) => eprintln!(
+            "Warning: Could not fetch tray menu for {}: {e}",
+            item.service
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+    use zbus::object_server::SignalContext;
+
+    /// A private session bus, stopped on drop.
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        /// None when dbus-daemon isn't available.
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        async fn connect(&self) -> zbus::Connection {
+            zbus::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    struct FakeItem {
+        title: String,
+    }
+
+    #[zbus::interface(name = "org.kde.StatusNotifierItem")]
+    impl FakeItem {
+        #[zbus(property)]
+        fn id(&self) -> String {
+            "fake-app".to_string()
+        }
+
+        #[zbus(property)]
+        fn title(&self) -> String {
+            self.title.clone()
+        }
+
+        #[zbus(property)]
+        fn status(&self) -> String {
+            "NeedsAttention".to_string()
+        }
+
+        #[zbus(property)]
+        fn icon_pixmap(&self) -> RawPixmaps {
+            vec![(1, 1, vec![0xff, 0x12, 0x34, 0x56])]
+        }
+
+        #[zbus(signal)]
+        async fn new_title(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+    }
+
+    /// Runs the tray on `bus` and waits until it is the watcher.
+    async fn start_tray(bus: &TestBus) -> (zbus::Connection, async_channel::Receiver<TrayEvent>) {
+        let conn = bus.connect().await;
+        let (_, commands) = async_channel::unbounded();
+        let (sender, events) = async_channel::unbounded();
+        tokio::spawn(run(conn.clone(), commands, sender));
+        let owner = conn.unique_name().unwrap().to_string();
+        eventually(|| async { name_owner(&conn, KDE_WATCHER).await.as_ref() == Some(&owner) })
+            .await;
+        (conn, events)
+    }
+
+    /// Serves a `FakeItem` on a new connection and registers it, by its
+    /// well-known `name` or else by object path only.
+    async fn register_item(bus: &TestBus, name: Option<&str>) -> zbus::Connection {
+        let app = bus.connect().await;
+        let item = FakeItem {
+            title: "Fake".to_string(),
+        };
+        app.object_server()
+            .at("/StatusNotifierItem", item)
+            .await
+            .unwrap();
+        let service = match name {
+            Some(name) => {
+                app.request_name(name).await.unwrap();
+                name.to_string()
+            }
+            None => "/StatusNotifierItem".to_string(),
+        };
+        watcher_proxy(&app, KDE_WATCHER)
+            .await
+            .call::<_, _, ()>("RegisterStatusNotifierItem", &(service,))
+            .await
+            .unwrap();
+        app
+    }
+
+    async fn watcher_proxy<'a>(conn: &zbus::Connection, name: &'a str) -> zbus::Proxy<'a> {
+        zbus::proxy::Builder::new(conn)
+            .destination(name)
+            .unwrap()
+            .path(WATCHER_PATH)
+            .unwrap()
+            .interface(name)
+            .unwrap()
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    async fn name_owner(conn: &zbus::Connection, name: &str) -> Option<String> {
+        let dbus = zbus::fdo::DBusProxy::new(conn).await.unwrap();
+        let name = zbus::names::BusName::try_from(name).unwrap();
+        dbus.get_name_owner(name)
+            .await
+            .ok()
+            .map(|owner| owner.to_string())
+    }
+
+    async fn next_event(events: &async_channel::Receiver<TrayEvent>) -> TrayEvent {
+        tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("timed out waiting for a tray event")
+            .unwrap()
+    }
+
+    async fn eventually<F: Future<Output = bool>>(check: impl Fn() -> F) {
+        for _ in 0..250 {
+            if check().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[tokio::test]
+    async fn registers_items_and_removes_them_on_exit() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        };
+        let (conn, events) = start_tray(&bus).await;
+        let watcher = watcher_proxy(&conn, KDE_WATCHER).await;
+
+        let app = register_item(&bus, None).await;
+        let registered = format!("{}/StatusNotifierItem", app.unique_name().unwrap());
+
+        let TrayEvent::Added(item) = next_event(&events).await else {
+            panic!("expected the item to be added");
+        };
+        assert_eq!(item.key(), registered);
+        assert_eq!(item.display_name(), "Fake");
+        assert_eq!(item.id, "fake-app");
+        assert_eq!(item.status, ItemStatus::NeedsAttention);
+        assert_eq!(item.icon_pixmaps.len(), 1);
+        assert_eq!(item.icon_pixmaps[0].data, [0xff, 0x12, 0x34, 0x56]);
+
+        let items: Vec<String> = watcher
+            .get_property("RegisteredStatusNotifierItems")
+            .await
+            .unwrap();
+        assert_eq!(items, std::slice::from_ref(&registered));
+
+        let mut unregistered = watcher
+            .receive_signal("StatusNotifierItemUnregistered")
+            .await
+            .unwrap();
+        app.close().await.unwrap();
+
+        let TrayEvent::Removed(key) = next_event(&events).await else {
+            panic!("expected the item to be removed");
+        };
+        assert_eq!(key, registered);
+
+        use futures_util::StreamExt;
+        let signal = tokio::time::timeout(Duration::from_secs(5), unregistered.next())
+            .await
+            .unwrap()
+            .unwrap();
+        let (service,): (String,) = signal.body().deserialize().unwrap();
+        assert_eq!(service, registered);
+
+        let items: Vec<String> = watcher
+            .get_property("RegisteredStatusNotifierItems")
+            .await
+            .unwrap();
+        assert!(items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn updates_items_on_their_signals() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        };
+        let (_conn, events) = start_tray(&bus).await;
+
+        let app = register_item(&bus, Some("org.example.FakeApp")).await;
+        let TrayEvent::Added(item) = next_event(&events).await else {
+            panic!("expected the item to be added");
+        };
+        assert_eq!(item.key(), "org.example.FakeApp/StatusNotifierItem");
+
+        let iface = app
+            .object_server()
+            .interface::<_, FakeItem>("/StatusNotifierItem")
+            .await
+            .unwrap();
+        iface.get_mut().await.title = "Renamed".to_string();
+        FakeItem::new_title(iface.signal_context()).await.unwrap();
+
+        let TrayEvent::Updated(item) = next_event(&events).await else {
+            panic!("expected the item to be updated");
+        };
+        assert_eq!(item.title, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn tracks_hosts_under_both_names() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        };
+        let (conn, _events) = start_tray(&bus).await;
+        let host = format!("org.kde.StatusNotifierHost-{}", std::process::id());
+
+        let client = bus.connect().await;
+        eventually(|| async { name_owner(&client, FREEDESKTOP_WATCHER).await.is_some() }).await;
+        assert_eq!(
+            name_owner(&client, &host).await,
+            Some(conn.unique_name().unwrap().to_string())
+        );
+
+        for name in [KDE_WATCHER, FREEDESKTOP_WATCHER] {
+            let watcher = watcher_proxy(&client, name).await;
+            eventually(|| async {
+                watcher
+                    .get_property::<bool>("IsStatusNotifierHostRegistered")
+                    .await
+                    .unwrap()
+            })
+            .await;
+        }
+
+        let watcher = watcher_proxy(&client, FREEDESKTOP_WATCHER).await;
+        let mut registered = watcher
+            .receive_signal("StatusNotifierHostRegistered")
+            .await
+            .unwrap();
+        watcher
+            .call::<_, _, ()>("RegisterStatusNotifierHost", &("org.example.OtherHost",))
+            .await
+            .unwrap();
+
+        use futures_util::StreamExt;
+        tokio::time::timeout(Duration::from_secs(5), registered.next())
+            .await
+            .expect("no StatusNotifierHostRegistered signal")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn takes_over_when_the_other_watcher_exits() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        };
+        let other = bus.connect().await;
+        other.request_name(KDE_WATCHER).await.unwrap();
+
+        let conn = bus.connect().await;
+        let (_, commands) = async_channel::unbounded();
+        let (sender, events) = async_channel::unbounded();
+        tokio::spawn(run(conn.clone(), commands, sender));
+
+        let owner = conn.unique_name().unwrap().to_string();
+        let dbus = zbus::fdo::DBusProxy::new(&other).await.unwrap();
+        eventually(|| async {
+            let name = zbus::names::WellKnownName::try_from(KDE_WATCHER).unwrap();
+            let queued = dbus.list_queued_owners(name).await.unwrap();
+            queued.iter().any(|name| name.as_str() == owner)
+        })
+        .await;
+
+        other.close().await.unwrap();
+        eventually(|| async { name_owner(&conn, KDE_WATCHER).await.as_ref() == Some(&owner) })
+            .await;
+
+        let _app = register_item(&bus, None).await;
+        assert!(matches!(next_event(&events).await, TrayEvent::Added(_)));
+    }
+
+    #[test]
+    fn best_pixmap_skips_oversized_dimensions() {
+        let pixmap = |width, height| Pixmap {
+            width,
+            height,
+            data: vec![0; 16 * 16 * 4],
+        };
+        let pixmaps = [pixmap(i32::MAX, i32::MAX), pixmap(16, 16)];
+        let best = Pixmap::best(&pixmaps, 22).unwrap();
+        assert_eq!((best.width, best.height), (16, 16));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::system::audio::{self, BackendType};
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const SAMPLE_RATE: usize = 44_100;
+// Samples per transform: about 46ms, fine enough for the bass bands
+const FFT_SIZE: usize = 2048;
+// The band the bars spread over, spaced logarithmically like hearing
+const MIN_FREQ: f64 = 50.0;
+const MAX_FREQ: f64 = 12_000.0;
+// Levels this far below full scale read as silence
+const FLOOR_DB: f64 = 70.0;
+
+/// Spectrum of what's playing, from a capture of the default output's
+/// monitor: `parec` for the PulseAudio backends, `pw-record` for
+/// WirePlumber.
+pub struct Visualizer {
+    // The current capture and its worker's keep-going flag, each run with
+    // a flag of its own so a finishing worker can't stop the next one
+    capture: Mutex<Option<(Child, Arc<AtomicBool>)>>,
+}
+
+impl Visualizer {
+    pub fn new() -> Self {
+        Self {
+            capture: Mutex::new(None),
+        }
+    }
+
+    /// Whether a capture is running; false again once it died.
+    pub fn is_running(&self) -> bool {
+        self.capture.lock().is_ok_and(|c| {
+            c.as_ref()
+                .is_some_and(|(_, running)| running.load(Ordering::SeqCst))
+        })
+    }
+
+    /// Starts capturing and sends `bars` levels from 0.0 to 1.0, low to
+    /// high frequencies, `framerate` times a second. Each level falls back
+    /// by `smoothing` (0.0 to 1.0) of the way per frame rather than drop.
+    pub fn start(
+        &self,
+        bars: usize,
+        framerate: u32,
+        smoothing: f64,
+        sender: async_channel::Sender<Vec<f64>>,
+    ) {
+        if self.is_running() {
+            return;
+        }
+        // Clears up a capture that died
+        self.stop();
+
+        let mut child = match capture_command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Warning: Could not capture audio for the visualizer: {e}");
+                return;
+            }
+        };
+        let Some(mut stdout) = child.stdout.take() else {
+            return;
+        };
+        let running = Arc::new(AtomicBool::new(true));
+        if let Ok(mut capture) = self.capture.lock() {
+            *capture = Some((child, running.clone()));
+        }
+        let bands = band_edges(bars.max(1));
+        let hop = SAMPLE_RATE / framerate.clamp(1, 144) as usize;
+        let smoothing = smoothing.clamp(0.0, 0.99);
+
+        std::thread::spawn(move || {
+            let window = hann_window();
+            let mut samples = vec![0.0; FFT_SIZE];
+            let mut levels = vec![0.0; bands.len() - 1];
+            let mut chunk = vec![0u8; hop * 2];
+
+            while running.load(Ordering::SeqCst) {
+                // Killed on stop, which ends the read
+                if stdout.read_exact(&mut chunk).is_err() {
+                    break;
+                }
+                // Slide the window on by what was read (s16le mono)
+                samples.extend(
+                    chunk
+                        .chunks_exact(2)
+                        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / i16::MAX as f64),
+                );
+                samples.drain(..samples.len() - FFT_SIZE);
+
+                let spectrum = magnitudes(&samples, &window);
+                for (level, new) in levels.iter_mut().zip(band_levels(&spectrum, &bands)) {
+                    // Jump up at once, sink back slowly
+                    *level = if new > *level {
+                        new
+                    } else {
+                        *level * smoothing + new * (1.0 - smoothing)
+                    };
+                }
+                // Drop frames the main loop hasn't caught up with
+                if let Err(async_channel::TrySendError::Closed(_)) = sender.try_send(levels.clone())
+                {
+                    break;
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    pub fn stop(&self) {
+        if let Some((mut child, running)) = self.capture.lock().ok().and_then(|mut c| c.take()) {
+            running.store(false, Ordering::SeqCst);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn capture_command() -> Command {
+    let rate = SAMPLE_RATE.to_string();
+    if audio::current_backend() == BackendType::Wpctl {
+        let mut command = Command::new("pw-record");
+        command.args([
+            "-P",
+            "{ stream.capture.sink = true }",
+            "--format",
+            "s16",
+            "--rate",
+            &rate,
+            "--channels",
+            "1",
+            "-",
+        ]);
+        command
+    } else {
+        let mut command = Command::new("parec");
+        command.args([
+            "--device=@DEFAULT_MONITOR@",
+            "--format=s16le",
+            &format!("--rate={rate}"),
+            "--channels=1",
+            "--latency-msec=20",
+            "--client-name=crowbar-visualizer",
+        ]);
+        command
+    }
+}
+
+fn hann_window() -> Vec<f64> {
+    (0..FFT_SIZE)
+        .map(|i| {
+            let phase = 2.0 * std::f64::consts::PI * i as f64 / (FFT_SIZE - 1) as f64;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect()
+}
+
+// FFT bin indices bounding each bar
+fn band_edges(bars: usize) -> Vec<usize> {
+    let bin_hz = SAMPLE_RATE as f64 / FFT_SIZE as f64;
+    let mut edges: Vec<usize> = (0..=bars)
+        .map(|i| {
+            let freq = MIN_FREQ * (MAX_FREQ / MIN_FREQ).powf(i as f64 / bars as f64);
+            (freq / bin_hz).round() as usize
+        })
+        .collect();
+    // Every bar gets at least one bin of its own
+    for i in 1..edges.len() {
+        edges[i] = edges[i].max(edges[i - 1] + 1);
+    }
+    edges
+}
+
+// Loudest bin of each band, on a log scale from silence (0.0) to full (1.0)
+fn band_levels(spectrum: &[f64], edges: &[usize]) -> Vec<f64> {
+    edges
+        .windows(2)
+        .map(|band| {
+            let end = band[1].min(spectrum.len());
+            let peak = spectrum
+                .get(band[0]..end)
+                .and_then(|bins| bins.iter().cloned().reduce(f64::max))
+                .unwrap_or(0.0);
+            let db = 20.0 * peak.max(1e-9).log10();
+            ((db + FLOOR_DB) / FLOOR_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+// Magnitudes of the first half of the spectrum, scaled so a full-scale
+// sine reads about 1.0
+fn magnitudes(samples: &[f64], window: &[f64]) -> Vec<f64> {
+    let mut re: Vec<f64> = samples.iter().zip(window).map(|(s, w)| s * w).collect();
+    let mut im = vec![0.0; re.len()];
+    fft(&mut re, &mut im);
+    // The Hann window halves the amplitude on top of the usual N/2
+    let scale = 4.0 / FFT_SIZE as f64;
+    re.iter()
+        .zip(&im)
+        .take(FFT_SIZE / 2)
+        .map(|(r, i)| (r * r + i * i).sqrt() * scale)
+        .collect()
+}
+
+// In-place iterative radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0, 0.0);
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+}